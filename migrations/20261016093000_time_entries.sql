@@ -0,0 +1,12 @@
+-- Add migration script here
+ALTER TABLE todos ADD COLUMN time_spent BIGINT NOT NULL DEFAULT 0;
+
+CREATE TABLE time_entries
+(
+    id SERIAL PRIMARY KEY,
+    todo_id INTEGER NOT NULL REFERENCES todos (id) ON DELETE CASCADE,
+    started_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    stopped_at TIMESTAMPTZ
+);
+
+CREATE UNIQUE INDEX time_entries_running_idx ON time_entries (todo_id) WHERE stopped_at IS NULL;
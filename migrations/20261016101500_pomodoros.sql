@@ -0,0 +1,13 @@
+-- Add migration script here
+CREATE TABLE pomodoros
+(
+    id SERIAL PRIMARY KEY,
+    todo_id INTEGER NOT NULL REFERENCES todos (id) ON DELETE CASCADE,
+    started_at TIMESTAMPTZ NOT NULL,
+    ends_at TIMESTAMPTZ NOT NULL,
+    interruptions INTEGER NOT NULL DEFAULT 0,
+    finished_at TIMESTAMPTZ,
+    completed BOOLEAN NOT NULL DEFAULT false
+);
+
+CREATE UNIQUE INDEX pomodoros_running_idx ON pomodoros (todo_id) WHERE finished_at IS NULL;
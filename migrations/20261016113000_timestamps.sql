@@ -0,0 +1,4 @@
+-- Add migration script here
+ALTER TABLE todos
+    ADD COLUMN created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    ADD COLUMN updated_at TIMESTAMPTZ NOT NULL DEFAULT now();
@@ -0,0 +1,3 @@
+-- Add migration script here
+-- Ranks run low 0, medium 1, high 2, urgent 3.
+ALTER TABLE todos ADD COLUMN priority INTEGER NOT NULL DEFAULT 1;
@@ -0,0 +1,4 @@
+-- Add migration script here
+ALTER TABLE todos ADD COLUMN parent_id INTEGER REFERENCES todos (id);
+
+CREATE INDEX todos_parent_idx ON todos (parent_id);
@@ -0,0 +1,9 @@
+-- Add migration script here
+CREATE TABLE todo_dependencies
+(
+    todo_id INTEGER NOT NULL REFERENCES todos (id) ON DELETE CASCADE,
+    blocker_id INTEGER NOT NULL REFERENCES todos (id) ON DELETE CASCADE,
+    PRIMARY KEY (todo_id, blocker_id)
+);
+
+CREATE INDEX todo_dependencies_blocker_idx ON todo_dependencies (blocker_id);
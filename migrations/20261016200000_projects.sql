@@ -0,0 +1,15 @@
+-- Add migration script here
+CREATE TABLE projects
+(
+    id SERIAL PRIMARY KEY,
+    name TEXT NOT NULL
+);
+
+-- The default project, which every existing todo moves into.
+INSERT INTO projects (id, name) VALUES (1, 'Inbox');
+SELECT setval('projects_id_seq', 1);
+
+ALTER TABLE todos
+    ADD COLUMN project_id INTEGER NOT NULL DEFAULT 1 REFERENCES projects (id) ON DELETE CASCADE;
+
+CREATE INDEX todos_project_idx ON todos (project_id);
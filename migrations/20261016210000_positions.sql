@@ -0,0 +1,7 @@
+-- Add migration script here
+ALTER TABLE todos ADD COLUMN position BIGINT NOT NULL DEFAULT 0;
+
+-- Keeps the existing todos in creation order.
+UPDATE todos SET position = id;
+
+CREATE INDEX todos_position_idx ON todos (position);
@@ -0,0 +1,6 @@
+-- Add migration script here
+ALTER TABLE todos ADD COLUMN lat DOUBLE PRECISION;
+ALTER TABLE todos ADD COLUMN lon DOUBLE PRECISION;
+ALTER TABLE todos ADD COLUMN place TEXT;
+
+CREATE INDEX todos_location_idx ON todos (lat, lon);
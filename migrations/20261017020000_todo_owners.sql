@@ -0,0 +1,5 @@
+-- Add migration script here
+-- Todos from before authentication stay without an owner.
+ALTER TABLE todos ADD COLUMN user_id INTEGER REFERENCES users (id) ON DELETE CASCADE;
+
+CREATE INDEX todos_user_idx ON todos (user_id);
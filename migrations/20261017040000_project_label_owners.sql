@@ -0,0 +1,11 @@
+-- Add migration script here
+-- Projects and labels from before authentication stay without an owner, as
+-- does the default project, which every user shares.
+ALTER TABLE projects ADD COLUMN user_id INTEGER REFERENCES users (id) ON DELETE CASCADE;
+ALTER TABLE labels ADD COLUMN user_id INTEGER REFERENCES users (id) ON DELETE CASCADE;
+
+CREATE INDEX projects_user_idx ON projects (user_id);
+
+-- Label names are unique per user rather than across every user.
+ALTER TABLE labels DROP CONSTRAINT labels_name_key;
+CREATE UNIQUE INDEX labels_user_name_idx ON labels (coalesce(user_id, 0), name);
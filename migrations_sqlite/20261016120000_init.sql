@@ -0,0 +1,34 @@
+-- Add migration script here
+CREATE TABLE todos
+(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    text TEXT NOT NULL,
+    completed BOOLEAN NOT NULL DEFAULT false,
+    time_spent INTEGER NOT NULL DEFAULT 0,
+    snoozed_until TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE TABLE time_entries
+(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    todo_id INTEGER NOT NULL REFERENCES todos (id) ON DELETE CASCADE,
+    started_at TEXT NOT NULL,
+    stopped_at TEXT
+);
+
+CREATE UNIQUE INDEX time_entries_running_idx ON time_entries (todo_id) WHERE stopped_at IS NULL;
+
+CREATE TABLE pomodoros
+(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    todo_id INTEGER NOT NULL REFERENCES todos (id) ON DELETE CASCADE,
+    started_at TEXT NOT NULL,
+    ends_at TEXT NOT NULL,
+    interruptions INTEGER NOT NULL DEFAULT 0,
+    finished_at TEXT,
+    completed BOOLEAN NOT NULL DEFAULT false
+);
+
+CREATE UNIQUE INDEX pomodoros_running_idx ON pomodoros (todo_id) WHERE finished_at IS NULL;
@@ -0,0 +1,10 @@
+-- Add migration script here
+CREATE TABLE share_links
+(
+    token TEXT PRIMARY KEY,
+    todo_id INTEGER NOT NULL REFERENCES todos (id) ON DELETE CASCADE,
+    expires_at TEXT,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX share_links_todo_idx ON share_links (todo_id);
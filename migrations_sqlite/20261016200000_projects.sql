@@ -0,0 +1,16 @@
+-- Add migration script here
+CREATE TABLE projects
+(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL
+);
+
+-- The default project, which every existing todo moves into.
+INSERT INTO projects (id, name) VALUES (1, 'Inbox');
+
+-- SQLite only adds a referencing column with a NULL default, so existing
+-- todos are moved explicitly and new ones always name their project.
+ALTER TABLE todos ADD COLUMN project_id INTEGER REFERENCES projects (id) ON DELETE CASCADE;
+UPDATE todos SET project_id = 1;
+
+CREATE INDEX todos_project_idx ON todos (project_id);
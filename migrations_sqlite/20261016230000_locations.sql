@@ -0,0 +1,6 @@
+-- Add migration script here
+ALTER TABLE todos ADD COLUMN lat REAL;
+ALTER TABLE todos ADD COLUMN lon REAL;
+ALTER TABLE todos ADD COLUMN place TEXT;
+
+CREATE INDEX todos_location_idx ON todos (lat, lon);
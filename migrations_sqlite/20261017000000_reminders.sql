@@ -0,0 +1,4 @@
+-- Add migration script here
+ALTER TABLE todos ADD COLUMN remind_at TEXT;
+
+CREATE INDEX todos_remind_at_idx ON todos (remind_at);
@@ -0,0 +1,34 @@
+-- Add migration script here
+-- Projects and labels from before authentication stay without an owner, as
+-- does the default project, which every user shares.
+ALTER TABLE projects ADD COLUMN user_id INTEGER REFERENCES users (id) ON DELETE CASCADE;
+
+CREATE INDEX projects_user_idx ON projects (user_id);
+
+-- Label names are unique per user rather than across every user. SQLite can
+-- not drop the old constraint, so the table is rebuilt. Dropping it would
+-- cascade to `todo_labels`, which is set aside and restored around that.
+CREATE TABLE todo_labels_saved AS SELECT todo_id, label_id FROM todo_labels;
+DROP TABLE todo_labels;
+CREATE TABLE labels_owned
+(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    user_id INTEGER REFERENCES users (id) ON DELETE CASCADE
+);
+INSERT INTO labels_owned (id, name) SELECT id, name FROM labels;
+DROP TABLE labels;
+ALTER TABLE labels_owned RENAME TO labels;
+
+CREATE UNIQUE INDEX labels_user_name_idx ON labels (coalesce(user_id, 0), name);
+
+CREATE TABLE todo_labels
+(
+    todo_id INTEGER NOT NULL REFERENCES todos (id) ON DELETE CASCADE,
+    label_id INTEGER NOT NULL REFERENCES labels (id) ON DELETE CASCADE,
+    PRIMARY KEY (todo_id, label_id)
+);
+INSERT INTO todo_labels (todo_id, label_id) SELECT todo_id, label_id FROM todo_labels_saved;
+DROP TABLE todo_labels_saved;
+
+CREATE INDEX todo_labels_label_idx ON todo_labels (label_id);
@@ -0,0 +1,491 @@
+use crate::events::TodoChange;
+use crate::handlers::{repository_error_status, ValidatedJson};
+use crate::repositories::{
+    ApiKey, CreateApiKey, CreateUser, Label, Project, Todo, User, UserRepository,
+    DEFAULT_PROJECT_ID,
+};
+use crate::simple::API_KEY_HEADER;
+use crate::usage::{self, api_key_usage_key, user_usage_key, ANONYMOUS};
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use axum::{
+    async_trait,
+    extract::{Extension, FromRequest, Path, RequestParts},
+    http::{header::AUTHORIZATION, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{convert::Infallible, fmt, marker::PhantomData, sync::Arc};
+use validator::Validate;
+
+/// Lifetime of an access token unless configured otherwise.
+pub const DEFAULT_TOKEN_TTL_SECS: i64 = 3600;
+/// Starts every API key, which tells it apart from an access token.
+pub const API_KEY_PREFIX: &str = "tk_";
+/// Random bytes in an API key, which follow the prefix in hex.
+const API_KEY_BYTES: usize = 32;
+/// How much of a key is kept in the clear to tell keys apart.
+const API_KEY_SHOWN_CHARS: usize = 11;
+
+/// Enables `/auth` and requires a bearer token on the todo resources.
+/// Without it the endpoints answer 404 and the todos stay open, which the
+/// server only starts with when `AUTH_DISABLED` asks for it.
+#[derive(Clone)]
+pub struct AuthConfig {
+    encoding_key: EncodingKey,
+    decoding_key: DecodingKey,
+    token_ttl: Duration,
+}
+
+impl fmt::Debug for AuthConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuthConfig")
+            .field("token_ttl", &self.token_ttl)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AuthConfig {
+    /// Tokens are signed with HS256 under `secret`.
+    pub fn new(secret: &str) -> Self {
+        Self {
+            encoding_key: EncodingKey::from_secret(secret.as_bytes()),
+            decoding_key: DecodingKey::from_secret(secret.as_bytes()),
+            token_ttl: Duration::seconds(DEFAULT_TOKEN_TTL_SECS),
+        }
+    }
+
+    pub fn with_token_ttl(self, token_ttl: Duration) -> Self {
+        Self { token_ttl, ..self }
+    }
+
+    fn issue(&self, user: &User) -> anyhow::Result<String> {
+        let claims = Claims {
+            sub: user.id(),
+            name: user.name().to_string(),
+            exp: (Utc::now() + self.token_ttl).timestamp(),
+        };
+        let token = jsonwebtoken::encode(&Header::default(), &claims, &self.encoding_key)?;
+        Ok(token)
+    }
+
+    /// The claims of `token` if it is signed by us and not expired.
+    fn verify(&self, token: &str) -> Option<Claims> {
+        jsonwebtoken::decode::<Claims>(token, &self.decoding_key, &Validation::default())
+            .ok()
+            .map(|data| data.claims)
+    }
+}
+
+/// What an access token says about its bearer.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Claims {
+    /// The user id.
+    pub sub: i32,
+    pub name: String,
+    /// Expiry in seconds since the epoch.
+    pub exp: i64,
+}
+
+/// What a presented token turned out to be.
+#[derive(Debug, Clone)]
+pub enum Credential {
+    AccessToken(Claims),
+    ApiKey(ApiKey),
+}
+
+impl Credential {
+    /// Checks `token`: an API key if it starts with `API_KEY_PREFIX`, else an
+    /// access token signed under `config`. `None` if it is not valid.
+    pub async fn check<T: UserRepository>(
+        config: &AuthConfig,
+        repository: &T,
+        token: &str,
+    ) -> anyhow::Result<Option<Self>> {
+        if token.starts_with(API_KEY_PREFIX) {
+            let key = repository
+                .find_api_key_by_hash(&hash_api_key(token))
+                .await?;
+            Ok(key.map(Credential::ApiKey))
+        } else {
+            Ok(config.verify(token).map(Credential::AccessToken))
+        }
+    }
+}
+
+/// Rejects requests without a valid access token or API key with 401 when
+/// `AuthConfig` is set. Either goes in `Authorization: Bearer`, a key also
+/// in `x-api-key`; keys are told apart by `API_KEY_PREFIX`. The token's
+/// `Claims` or the `ApiKey` are left in the request extensions for the
+/// handlers. Without `AuthConfig` everything gets through.
+///
+/// It is also where a request is charged to the caller's usage quota, per
+/// user for access tokens and per key for API keys; see `usage::charge`.
+pub struct RequireAuth<T>(PhantomData<T>);
+
+#[async_trait]
+impl<T: UserRepository, B: Send> FromRequest<B> for RequireAuth<T> {
+    type Rejection = Response;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let config = match req
+            .extensions()
+            .and_then(|extensions| extensions.get::<AuthConfig>())
+        {
+            Some(config) => config.clone(),
+            None => {
+                usage::charge(req, ANONYMOUS.to_string()).map_err(IntoResponse::into_response)?;
+                return Ok(Self(PhantomData));
+            }
+        };
+        let token = req
+            .headers()
+            .and_then(presented_token)
+            .ok_or_else(|| StatusCode::UNAUTHORIZED.into_response())?;
+        let repository = req
+            .extensions()
+            .and_then(|extensions| extensions.get::<Arc<T>>())
+            .cloned()
+            .ok_or_else(|| StatusCode::INTERNAL_SERVER_ERROR.into_response())?;
+
+        let credential = Credential::check(&config, &*repository, &token)
+            .await
+            .map_err(|e| repository_error_status(e).into_response())?
+            .ok_or_else(|| StatusCode::UNAUTHORIZED.into_response())?;
+        let key = match &credential {
+            Credential::AccessToken(claims) => user_usage_key(claims.sub),
+            Credential::ApiKey(key) => api_key_usage_key(key.id()),
+        };
+        usage::charge(req, key).map_err(IntoResponse::into_response)?;
+        if let Some(extensions) = req.extensions_mut() {
+            match credential {
+                Credential::AccessToken(claims) => {
+                    extensions.insert(claims);
+                }
+                Credential::ApiKey(key) => {
+                    extensions.insert(key);
+                }
+            }
+        }
+        Ok(Self(PhantomData))
+    }
+}
+
+/// The token from `Authorization: Bearer`, or else from `x-api-key`.
+pub fn presented_token(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .or_else(|| {
+            headers
+                .get(API_KEY_HEADER)
+                .and_then(|value| value.to_str().ok())
+        })
+        .map(|token| token.trim().to_string())
+}
+
+/// Keys carry 256 random bits, so a fast unsalted hash is enough and lets
+/// a presented key be looked up by its hash.
+fn hash_api_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+/// The user a request acts for, as left by `RequireAuth`. Without
+/// `AuthConfig` there is none and every todo is reachable.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Owner(Option<i32>);
+
+impl Owner {
+    pub fn user_id(&self) -> Option<i32> {
+        self.0
+    }
+
+    pub fn may_access(&self, todo: &Todo) -> bool {
+        self.0.is_none() || todo.user_id() == self.0
+    }
+
+    pub fn may_see(&self, change: &TodoChange) -> bool {
+        self.0.is_none() || change.user_id() == self.0
+    }
+
+    pub fn may_access_label(&self, label: &Label) -> bool {
+        self.0.is_none() || label.user_id() == self.0
+    }
+
+    /// Whether todos may be filed under `project`: the owner's own, or the
+    /// default project every user shares.
+    pub fn may_use_project(&self, project: &Project) -> bool {
+        self.may_change_project(project) || project.id() == DEFAULT_PROJECT_ID
+    }
+
+    /// Whether `project` may be renamed or deleted, which the shared default
+    /// project may be by nobody with an owner.
+    pub fn may_change_project(&self, project: &Project) -> bool {
+        self.0.is_none() || project.user_id() == self.0
+    }
+}
+
+impl From<Credential> for Owner {
+    fn from(credential: Credential) -> Self {
+        match credential {
+            Credential::AccessToken(claims) => Self(Some(claims.sub)),
+            Credential::ApiKey(key) => Self(Some(key.user_id())),
+        }
+    }
+}
+
+#[async_trait]
+impl<B: Send> FromRequest<B> for Owner {
+    type Rejection = Infallible;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let user_id = req.extensions().and_then(|extensions| {
+            extensions
+                .get::<Claims>()
+                .map(|claims| claims.sub)
+                .or_else(|| extensions.get::<ApiKey>().map(ApiKey::user_id))
+        });
+        Ok(Self(user_id))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Validate)]
+pub struct Credentials {
+    #[validate(length(min = 1, message = "Can not be empty"))]
+    #[validate(length(max = 50, message = "Over name length"))]
+    name: String,
+    #[validate(length(min = 8, message = "Under password length"))]
+    #[validate(length(max = 128, message = "Over password length"))]
+    password: String,
+}
+
+/// A user as the API shows them, without the password hash.
+#[derive(Debug, Serialize)]
+struct Account {
+    id: i32,
+    name: String,
+    created_at: DateTime<Utc>,
+}
+
+impl From<User> for Account {
+    fn from(user: User) -> Self {
+        Self {
+            id: user.id(),
+            name: user.name().to_string(),
+            created_at: user.created_at(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct AccessToken {
+    access_token: String,
+    token_type: &'static str,
+    /// Seconds until the token expires.
+    expires_in: i64,
+}
+
+/// Argon2 is deliberately slow, so it runs off the async workers.
+async fn hash_password(password: String) -> anyhow::Result<String> {
+    tokio::task::spawn_blocking(move || {
+        let salt = SaltString::generate(&mut OsRng);
+        Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+            .map_err(|e| anyhow::anyhow!("fail hash password: {}", e))
+    })
+    .await?
+}
+
+/// An Argon2 hash with the default parameters, of no password anyone
+/// knows. Login checks against it for unknown names, so that those take
+/// as long as a wrong password and do not give away who has an account.
+const DUMMY_PASSWORD_HASH: &str =
+    "$argon2id$v=19$m=4096,t=3,p=1$yFECFUgNuwFUCg2gP2gnQw$1UDUg8G5nYv4wZlZW43HymEYQAcF37aZopr8q5AKtRY";
+
+async fn verify_password(password: String, hash: String) -> bool {
+    tokio::task::spawn_blocking(move || {
+        PasswordHash::new(&hash)
+            .map(|hash| {
+                Argon2::default()
+                    .verify_password(password.as_bytes(), &hash)
+                    .is_ok()
+            })
+            .unwrap_or(false)
+    })
+    .await
+    .unwrap_or(false)
+}
+
+pub async fn register<T: UserRepository>(
+    ValidatedJson(credentials): ValidatedJson<Credentials>,
+    Extension(repository): Extension<Arc<T>>,
+    config: Option<Extension<AuthConfig>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    config.ok_or(StatusCode::NOT_FOUND)?;
+
+    let password_hash = hash_password(credentials.password)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let user = repository
+        .create_user(CreateUser {
+            name: credentials.name,
+            password_hash,
+        })
+        .await
+        .map_err(repository_error_status)?;
+    Ok((StatusCode::CREATED, Json(Account::from(user))))
+}
+
+/// Answers 401 alike for an unknown name and a wrong password.
+pub async fn login<T: UserRepository>(
+    Json(credentials): Json<Credentials>,
+    Extension(repository): Extension<Arc<T>>,
+    config: Option<Extension<AuthConfig>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let Extension(config) = config.ok_or(StatusCode::NOT_FOUND)?;
+
+    let user = repository
+        .find_user_by_name(&credentials.name)
+        .await
+        .map_err(repository_error_status)?;
+    let hash = user
+        .as_ref()
+        .map(|user| user.password_hash())
+        .unwrap_or(DUMMY_PASSWORD_HASH)
+        .to_string();
+    let verified = verify_password(credentials.password, hash).await;
+    let user = user.filter(|_| verified).ok_or(StatusCode::UNAUTHORIZED)?;
+
+    let access_token = config
+        .issue(&user)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Json(AccessToken {
+        access_token,
+        token_type: "Bearer",
+        expires_in: config.token_ttl.num_seconds(),
+    }))
+}
+
+#[derive(Debug, Clone, Deserialize, Validate)]
+pub struct NewApiKey {
+    /// What the key is for, e.g. the script using it.
+    #[validate(length(min = 1, message = "Can not be empty"))]
+    #[validate(length(max = 50, message = "Over name length"))]
+    name: String,
+}
+
+/// An API key as the API lists it. The key itself is only ever in
+/// `IssuedApiKey`.
+#[derive(Debug, Serialize)]
+struct ApiKeySummary {
+    id: i32,
+    name: String,
+    prefix: String,
+    created_at: DateTime<Utc>,
+}
+
+impl From<ApiKey> for ApiKeySummary {
+    fn from(key: ApiKey) -> Self {
+        Self {
+            id: key.id(),
+            name: key.name().to_string(),
+            prefix: key.prefix().to_string(),
+            created_at: key.created_at(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct IssuedApiKey {
+    #[serde(flatten)]
+    summary: ApiKeySummary,
+    key: String,
+}
+
+/// The `/apikeys` endpoints act for the caller, so they answer 404 like
+/// `/auth` while there are no users.
+fn key_owner(owner: Owner) -> Result<i32, StatusCode> {
+    owner.user_id().ok_or(StatusCode::NOT_FOUND)
+}
+
+pub async fn create_api_key<T: UserRepository>(
+    ValidatedJson(payload): ValidatedJson<NewApiKey>,
+    Extension(repository): Extension<Arc<T>>,
+    owner: Owner,
+) -> Result<impl IntoResponse, StatusCode> {
+    let user_id = key_owner(owner)?;
+
+    let secret: [u8; API_KEY_BYTES] = rand::random();
+    let key = format!("{}{}", API_KEY_PREFIX, hex::encode(secret));
+    let api_key = repository
+        .create_api_key(CreateApiKey {
+            user_id,
+            name: payload.name,
+            prefix: key[..API_KEY_SHOWN_CHARS].to_string(),
+            key_hash: hash_api_key(&key),
+        })
+        .await
+        .map_err(repository_error_status)?;
+    let issued = IssuedApiKey {
+        summary: api_key.into(),
+        key,
+    };
+    Ok((StatusCode::CREATED, Json(issued)))
+}
+
+pub async fn all_api_keys<T: UserRepository>(
+    Extension(repository): Extension<Arc<T>>,
+    owner: Owner,
+) -> Result<impl IntoResponse, StatusCode> {
+    let user_id = key_owner(owner)?;
+
+    let keys: Vec<ApiKeySummary> = repository
+        .api_keys(user_id)
+        .await
+        .map_err(repository_error_status)?
+        .into_iter()
+        .map(ApiKeySummary::from)
+        .collect();
+    Ok((StatusCode::OK, Json(keys)))
+}
+
+/// Revokes a key at once; requests presenting it get 401 from then on.
+pub async fn delete_api_key<T: UserRepository>(
+    Path(id): Path<i32>,
+    Extension(repository): Extension<Arc<T>>,
+    owner: Owner,
+) -> StatusCode {
+    let user_id = match key_owner(owner) {
+        Ok(user_id) => user_id,
+        Err(status) => return status,
+    };
+    repository
+        .delete_api_key(user_id, id)
+        .await
+        .map(|_| StatusCode::NO_CONTENT)
+        .unwrap_or_else(repository_error_status)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn dummy_password_hash_costs_as_much_as_a_real_one() {
+        let hash = PasswordHash::new(DUMMY_PASSWORD_HASH).expect("invalid dummy hash");
+        let params = argon2::Params::try_from(&hash).expect("invalid dummy hash params");
+        let default = argon2::Params::default();
+        assert_eq!(
+            (params.m_cost(), params.t_cost(), params.p_cost()),
+            (default.m_cost(), default.t_cost(), default.p_cost())
+        );
+    }
+}
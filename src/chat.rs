@@ -0,0 +1,172 @@
+use crate::dead_letters::{DeadLetters, Delivery};
+use crate::repositories::Todo;
+use serde_json::{json, Value};
+use std::{collections::HashSet, fmt, str::FromStr};
+use thiserror::Error;
+
+/// What happened to a todo, as forwarded to a chat webhook.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TodoEvent {
+    Created,
+    Completed,
+    /// The due date passed while the todo was still open.
+    Overdue,
+}
+
+pub const ALL_TODO_EVENTS: [TodoEvent; 3] =
+    [TodoEvent::Created, TodoEvent::Completed, TodoEvent::Overdue];
+
+#[derive(Debug, Error)]
+#[error("unknown todo event: [{0}], expected one of created, completed, overdue")]
+pub struct ParseTodoEventError(String);
+
+impl FromStr for TodoEvent {
+    type Err = ParseTodoEventError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "created" => Ok(TodoEvent::Created),
+            "completed" => Ok(TodoEvent::Completed),
+            "overdue" => Ok(TodoEvent::Overdue),
+            _ => Err(ParseTodoEventError(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for TodoEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            TodoEvent::Created => "created",
+            TodoEvent::Completed => "completed",
+            TodoEvent::Overdue => "overdue",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Parses a comma separated list such as `created,overdue`.
+pub fn parse_todo_events(list: &str) -> Result<HashSet<TodoEvent>, ParseTodoEventError> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+/// The two incoming-webhook payload formats we speak.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ChatFlavor {
+    Slack,
+    Discord,
+}
+
+/// Posts a message to a Slack or Discord incoming webhook for every
+/// forwarded event. Which one is told by the host of the URL.
+#[derive(Debug, Clone)]
+pub struct ChatWebhook {
+    url: String,
+    flavor: ChatFlavor,
+    events: HashSet<TodoEvent>,
+    client: reqwest::Client,
+    dead_letters: Option<DeadLetters>,
+}
+
+impl ChatWebhook {
+    pub fn new(url: String, events: HashSet<TodoEvent>) -> Self {
+        let host = url
+            .split("://")
+            .nth(1)
+            .and_then(|rest| rest.split('/').next())
+            .unwrap_or_default();
+        let discord = ["discord.com", "discordapp.com"]
+            .iter()
+            .any(|domain| host == *domain || host.ends_with(&format!(".{}", domain)));
+        let flavor = if discord {
+            ChatFlavor::Discord
+        } else {
+            ChatFlavor::Slack
+        };
+        Self {
+            url,
+            flavor,
+            events,
+            client: reqwest::Client::new(),
+            dead_letters: None,
+        }
+    }
+
+    /// Keeps posts that fail in `dead_letters` instead of dropping them.
+    pub fn with_dead_letters(self, dead_letters: DeadLetters) -> Self {
+        Self {
+            dead_letters: Some(dead_letters),
+            ..self
+        }
+    }
+
+    pub fn forwards(&self, event: TodoEvent) -> bool {
+        self.events.contains(&event)
+    }
+
+    pub fn payload(&self, event: TodoEvent, todo: &Todo) -> Value {
+        let text = match self.flavor {
+            ChatFlavor::Slack => escape_slack(todo.text()),
+            ChatFlavor::Discord => todo.text().to_string(),
+        };
+        let bold = match self.flavor {
+            ChatFlavor::Slack => "*",
+            ChatFlavor::Discord => "**",
+        };
+        let mut message = format!("Todo {}: {}{}{} (#{})", event, bold, text, bold, todo.id());
+        if let (TodoEvent::Overdue, Some(due_date)) = (event, todo.due_date()) {
+            message.push_str(&format!(", was due {}", due_date.to_rfc3339()));
+        }
+        match self.flavor {
+            ChatFlavor::Slack => json!({ "text": message }),
+            // Todo text must not ping anyone.
+            ChatFlavor::Discord => json!({
+                "content": message,
+                "allowed_mentions": { "parse": [] },
+            }),
+        }
+    }
+
+    /// Posts about `todo` on a task of its own if `event` is forwarded.
+    /// Failures are only logged and dead-lettered, so chat trouble never
+    /// fails a request.
+    pub fn send(&self, event: TodoEvent, todo: &Todo) {
+        if self.forwards(event) {
+            self.post(event, todo.clone());
+        }
+    }
+
+    pub fn post(&self, event: TodoEvent, todo: Todo) {
+        let webhook = self.clone();
+        tokio::spawn(async move {
+            let result = webhook
+                .client
+                .post(&webhook.url)
+                .json(&webhook.payload(event, &todo))
+                .send()
+                .await
+                .and_then(reqwest::Response::error_for_status);
+            if let Err(e) = result {
+                tracing::error!("fail post {} event of todo {}: {}", event, todo.id(), e);
+                if let Some(dead_letters) = webhook.dead_letters.clone() {
+                    let delivery = Delivery::Chat {
+                        webhook,
+                        event,
+                        todo,
+                    };
+                    dead_letters.push(delivery, e.to_string());
+                }
+            }
+        });
+    }
+}
+
+/// Slack treats `&`, `<` and `>` as control characters in message text.
+fn escape_slack(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
@@ -0,0 +1,177 @@
+use crate::chat::{ChatWebhook, TodoEvent};
+use crate::email::EmailNotifier;
+use crate::repositories::Todo;
+use crate::webhooks::Webhooks;
+use axum::{
+    extract::{Extension, Path},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use lettre::Message;
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+/// A delivery that failed for good, kept until an operator retries or
+/// discards it.
+#[derive(Clone, Serialize)]
+pub struct DeadLetter {
+    id: u64,
+    kind: &'static str,
+    todo_id: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    event: Option<String>,
+    error: String,
+    failed_at: DateTime<Utc>,
+    #[serde(skip)]
+    delivery: Delivery,
+}
+
+/// What it takes to attempt a failed delivery once more.
+#[derive(Clone)]
+pub enum Delivery {
+    Chat {
+        webhook: ChatWebhook,
+        event: TodoEvent,
+        todo: Todo,
+    },
+    Email {
+        notifier: EmailNotifier,
+        todo_id: i32,
+        message: Message,
+    },
+    Webhook {
+        webhooks: Webhooks,
+        url: String,
+        event: &'static str,
+        todo_id: i32,
+        body: String,
+    },
+}
+
+impl Delivery {
+    /// Starts the delivery again; if it fails once more it comes back as a
+    /// new dead letter.
+    fn redeliver(self) {
+        match self {
+            Delivery::Chat {
+                webhook,
+                event,
+                todo,
+            } => webhook.post(event, todo),
+            Delivery::Email {
+                notifier,
+                todo_id,
+                message,
+            } => notifier.deliver(todo_id, message),
+            Delivery::Webhook {
+                webhooks,
+                url,
+                event,
+                todo_id,
+                body,
+            } => webhooks.deliver(url, event, todo_id, body),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Letters {
+    next_id: u64,
+    letters: BTreeMap<u64, DeadLetter>,
+}
+
+/// Failed chat posts, webhook deliveries and reminder emails, held in
+/// memory.
+#[derive(Clone, Default)]
+pub struct DeadLetters {
+    inner: Arc<Mutex<Letters>>,
+}
+
+impl fmt::Debug for DeadLetters {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DeadLetters").finish_non_exhaustive()
+    }
+}
+
+impl DeadLetters {
+    pub fn push(&self, delivery: Delivery, error: String) -> u64 {
+        let (kind, todo_id, event) = match &delivery {
+            Delivery::Chat { event, todo, .. } => {
+                ("chat_webhook", todo.id(), Some(event.to_string()))
+            }
+            Delivery::Email { todo_id, .. } => ("reminder_email", *todo_id, None),
+            Delivery::Webhook { event, todo_id, .. } => {
+                ("webhook", *todo_id, Some(event.to_string()))
+            }
+        };
+        let mut inner = self.inner.lock().unwrap();
+        inner.next_id += 1;
+        let id = inner.next_id;
+        inner.letters.insert(
+            id,
+            DeadLetter {
+                id,
+                kind,
+                todo_id,
+                event,
+                error,
+                failed_at: Utc::now(),
+                delivery,
+            },
+        );
+        id
+    }
+
+    /// Oldest first.
+    pub fn all(&self) -> Vec<DeadLetter> {
+        self.inner
+            .lock()
+            .unwrap()
+            .letters
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    pub fn take(&self, id: u64) -> Option<DeadLetter> {
+        self.inner.lock().unwrap().letters.remove(&id)
+    }
+}
+
+pub async fn all_dead_letters(
+    dead_letters: Option<Extension<DeadLetters>>,
+) -> Json<Vec<DeadLetter>> {
+    let letters = dead_letters
+        .map(|Extension(dead_letters)| dead_letters.all())
+        .unwrap_or_default();
+    Json(letters)
+}
+
+/// Takes the letter off the queue and delivers it again in the background.
+pub async fn retry_dead_letter(
+    Path(id): Path<u64>,
+    dead_letters: Option<Extension<DeadLetters>>,
+) -> StatusCode {
+    match dead_letters.and_then(|Extension(dead_letters)| dead_letters.take(id)) {
+        Some(letter) => {
+            letter.delivery.redeliver();
+            StatusCode::ACCEPTED
+        }
+        None => StatusCode::NOT_FOUND,
+    }
+}
+
+pub async fn discard_dead_letter(
+    Path(id): Path<u64>,
+    dead_letters: Option<Extension<DeadLetters>>,
+) -> StatusCode {
+    match dead_letters.and_then(|Extension(dead_letters)| dead_letters.take(id)) {
+        Some(_) => StatusCode::NO_CONTENT,
+        None => StatusCode::NOT_FOUND,
+    }
+}
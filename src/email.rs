@@ -0,0 +1,210 @@
+use crate::dead_letters::{DeadLetters, Delivery};
+use crate::reminders::Notifier;
+use crate::repositories::Todo;
+use anyhow::Context;
+use axum::async_trait;
+use lettre::{
+    message::Mailbox, transport::smtp::authentication::Credentials, AsyncSmtpTransport,
+    AsyncTransport, Message, Tokio1Executor,
+};
+use std::{env, fmt, str::FromStr, time::Duration};
+use thiserror::Error;
+
+/// Attempts per email before giving up on transient failures.
+const MAX_SEND_ATTEMPTS: u32 = 4;
+/// Wait before the first retry, doubled for every one after it.
+const RETRY_BACKOFF: Duration = Duration::from_secs(2);
+
+/// How the SMTP connection is secured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SmtpTls {
+    /// TLS from the start, usually on port 465.
+    Tls,
+    /// Upgraded with STARTTLS, usually on port 587.
+    #[default]
+    StartTls,
+    /// Plain text, for a relay on localhost.
+    None,
+}
+
+#[derive(Debug, Error)]
+#[error("unknown smtp tls mode: [{0}], expected one of tls, starttls, none")]
+pub struct ParseSmtpTlsError(String);
+
+impl FromStr for SmtpTls {
+    type Err = ParseSmtpTlsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tls" => Ok(SmtpTls::Tls),
+            "starttls" => Ok(SmtpTls::StartTls),
+            "none" => Ok(SmtpTls::None),
+            _ => Err(ParseSmtpTlsError(s.to_string())),
+        }
+    }
+}
+
+/// Where and as whom to send reminder emails.
+#[derive(Clone)]
+pub struct SmtpConfig {
+    host: String,
+    port: Option<u16>,
+    tls: SmtpTls,
+    credentials: Option<Credentials>,
+    from: Mailbox,
+    to: Mailbox,
+}
+
+impl fmt::Debug for SmtpConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SmtpConfig")
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("tls", &self.tls)
+            .field("from", &self.from)
+            .field("to", &self.to)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SmtpConfig {
+    /// Reads `$SMTP_HOST`, `$SMTP_PORT`, `$SMTP_TLS`, `$SMTP_USERNAME`,
+    /// `$SMTP_PASSWORD`, `$SMTP_FROM` and `$SMTP_TO`. `None` without
+    /// `$SMTP_HOST`, which leaves email off.
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        let host = match env::var("SMTP_HOST") {
+            Ok(host) => host,
+            Err(_) => return Ok(None),
+        };
+        let port = env::var("SMTP_PORT")
+            .ok()
+            .map(|port| port.parse().context("invalid env variable: $SMTP_PORT"))
+            .transpose()?;
+        let tls = env::var("SMTP_TLS")
+            .ok()
+            .map(|tls| tls.parse())
+            .transpose()?
+            .unwrap_or_default();
+        let credentials = env::var("SMTP_USERNAME").ok().map(|username| {
+            Credentials::new(username, env::var("SMTP_PASSWORD").unwrap_or_default())
+        });
+        let mailbox = |name: &str| -> anyhow::Result<Mailbox> {
+            let value = env::var(name).with_context(|| format!("email needs ${}", name))?;
+            value
+                .parse()
+                .with_context(|| format!("invalid env variable: ${}", name))
+        };
+
+        Ok(Some(Self {
+            host,
+            port,
+            tls,
+            credentials,
+            from: mailbox("SMTP_FROM")?,
+            to: mailbox("SMTP_TO")?,
+        }))
+    }
+}
+
+/// Sends reminders by email. Delivery runs on a task of its own, so a slow
+/// or flaky server holds up neither the scheduler nor the other reminders.
+#[derive(Clone)]
+pub struct EmailNotifier {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+    to: Mailbox,
+    dead_letters: Option<DeadLetters>,
+}
+
+impl EmailNotifier {
+    pub fn new(config: SmtpConfig) -> anyhow::Result<Self> {
+        let mut builder = match config.tls {
+            SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host)?,
+            SmtpTls::StartTls => {
+                AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)?
+            }
+            SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.host),
+        };
+        if let Some(port) = config.port {
+            builder = builder.port(port);
+        }
+        if let Some(credentials) = config.credentials {
+            builder = builder.credentials(credentials);
+        }
+
+        Ok(Self {
+            transport: builder.build(),
+            from: config.from,
+            to: config.to,
+            dead_letters: None,
+        })
+    }
+
+    /// Keeps emails that can not be delivered in `dead_letters`.
+    pub fn with_dead_letters(self, dead_letters: DeadLetters) -> Self {
+        Self {
+            dead_letters: Some(dead_letters),
+            ..self
+        }
+    }
+
+    pub fn message(&self, todo: &Todo) -> anyhow::Result<Message> {
+        let mut body = format!("Reminder for todo #{}:\n\n{}\n", todo.id(), todo.text());
+        if let Some(due_date) = todo.due_date() {
+            body.push_str(&format!("\nDue {}\n", due_date.to_rfc3339()));
+        }
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(self.to.clone())
+            .subject(format!("Reminder: {}", todo.text()))
+            .body(body)?;
+        Ok(message)
+    }
+
+    /// Retries failures that may pass, i.e. anything but a permanent (5xx)
+    /// rejection, backing off between attempts.
+    async fn send(&self, message: Message) -> anyhow::Result<()> {
+        let mut attempt = 1;
+        let mut backoff = RETRY_BACKOFF;
+        loop {
+            match self.transport.send(message.clone()).await {
+                Ok(_) => return Ok(()),
+                Err(e) if attempt < MAX_SEND_ATTEMPTS && !e.is_permanent() => {
+                    tracing::warn!("fail send email, attempt {}: {}", attempt, e);
+                    tokio::time::sleep(backoff).await;
+                    attempt += 1;
+                    backoff *= 2;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// Sends `message` on a task of its own; a delivery that fails for good
+    /// is logged and dead-lettered.
+    pub fn deliver(&self, todo_id: i32, message: Message) {
+        let notifier = self.clone();
+        tokio::spawn(async move {
+            if let Err(e) = notifier.send(message.clone()).await {
+                tracing::error!("fail email reminder for todo {}: {:#}", todo_id, e);
+                if let Some(dead_letters) = notifier.dead_letters.clone() {
+                    let delivery = Delivery::Email {
+                        notifier,
+                        todo_id,
+                        message,
+                    };
+                    dead_letters.push(delivery, format!("{:#}", e));
+                }
+            }
+        });
+    }
+}
+
+#[async_trait]
+impl Notifier for EmailNotifier {
+    /// Counts as sent once the email is queued.
+    async fn notify(&self, todo: &Todo) -> anyhow::Result<()> {
+        self.deliver(todo.id(), self.message(todo)?);
+        Ok(())
+    }
+}
@@ -0,0 +1,184 @@
+use axum::{
+    body::{self, Full},
+    http::{header, HeaderValue, Request, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde_json::{json, Value};
+use std::{
+    future::Future,
+    pin::Pin,
+    str::FromStr,
+    task::{Context, Poll},
+};
+use thiserror::Error;
+use tower::{Layer, Service};
+
+pub const ENVELOPE_HEADER: &str = "x-envelope";
+/// Paths whose callers expect their own response format.
+const PASSTHROUGH_PREFIXES: [&str; 6] = [
+    "/integrations/",
+    "/simple/",
+    "/ws",
+    "/openapi.json",
+    "/docs",
+    "/graphql",
+];
+/// Likewise, for pages, images and event streams that are served as they are.
+const PASSTHROUGH_SUFFIXES: [&str; 3] = ["/embed", "/qr.png", "/todos/events"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EnvelopeMode {
+    Never,
+    #[default]
+    OnRequest,
+    Always,
+}
+
+#[derive(Debug, Error)]
+#[error("unknown envelope mode: [{0}], expected one of never, header, always")]
+pub struct ParseEnvelopeModeError(String);
+
+impl FromStr for EnvelopeMode {
+    type Err = ParseEnvelopeModeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "never" => Ok(EnvelopeMode::Never),
+            "header" => Ok(EnvelopeMode::OnRequest),
+            "always" => Ok(EnvelopeMode::Always),
+            _ => Err(ParseEnvelopeModeError(s.to_string())),
+        }
+    }
+}
+
+impl EnvelopeMode {
+    fn applies_to<B>(&self, req: &Request<B>) -> bool {
+        let path = req.uri().path();
+        if PASSTHROUGH_PREFIXES
+            .iter()
+            .any(|prefix| path.starts_with(prefix))
+            || PASSTHROUGH_SUFFIXES
+                .iter()
+                .any(|suffix| path.ends_with(suffix))
+        {
+            return false;
+        }
+
+        let requested = req
+            .headers()
+            .get(ENVELOPE_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| matches!(value, "true" | "1"));
+
+        match self {
+            EnvelopeMode::Never => false,
+            EnvelopeMode::OnRequest => requested.unwrap_or(false),
+            EnvelopeMode::Always => requested.unwrap_or(true),
+        }
+    }
+}
+
+/// Wraps every response as `{ "data": ..., "error": ..., "meta": {...} }`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EnvelopeLayer {
+    mode: EnvelopeMode,
+}
+
+impl EnvelopeLayer {
+    pub fn new(mode: EnvelopeMode) -> Self {
+        Self { mode }
+    }
+}
+
+impl<S> Layer<S> for EnvelopeLayer {
+    type Service = Envelope<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Envelope {
+            inner,
+            mode: self.mode,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Envelope<S> {
+    inner: S,
+    mode: EnvelopeMode,
+}
+
+type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send + 'static>>;
+
+impl<S, B> Service<Request<B>> for Envelope<S>
+where
+    S: Service<Request<B>, Response = Response>,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = BoxFuture<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        let enveloped = self.mode.applies_to(&req);
+        let future = self.inner.call(req);
+
+        Box::pin(async move {
+            let res = future.await?;
+            if !enveloped {
+                return Ok(res);
+            }
+            Ok(wrap(res).await)
+        })
+    }
+}
+
+async fn wrap(res: Response) -> Response {
+    let (mut parts, body) = res.into_parts();
+    if parts.status == StatusCode::NO_CONTENT {
+        return Response::from_parts(parts, body);
+    }
+
+    let bytes = match hyper::body::to_bytes(body).await {
+        Ok(bytes) => bytes,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    let is_json = parts
+        .headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.starts_with(mime::APPLICATION_JSON.as_ref()))
+        .unwrap_or(false);
+    let payload = if bytes.is_empty() {
+        parts
+            .status
+            .canonical_reason()
+            .map(|reason| Value::String(reason.to_string()))
+            .unwrap_or(Value::Null)
+    } else if is_json {
+        serde_json::from_slice(&bytes).unwrap_or(Value::Null)
+    } else {
+        Value::String(String::from_utf8_lossy(&bytes).into_owned())
+    };
+
+    let (data, error) = if parts.status.is_client_error() || parts.status.is_server_error() {
+        (Value::Null, payload)
+    } else {
+        (payload, Value::Null)
+    };
+    let envelope = json!({
+        "data": data,
+        "error": error,
+        "meta": { "status": parts.status.as_u16() },
+    });
+
+    parts.headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, body::boxed(Full::from(envelope.to_string())))
+}
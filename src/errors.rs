@@ -0,0 +1,231 @@
+use axum::{
+    body::{self, Full},
+    http::{header, HeaderValue, Request, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tower::{Layer, Service};
+
+pub const ERROR_CODE_HEADER: &str = "x-error-code";
+/// Paths whose callers expect their own error format, as with the envelope.
+const PASSTHROUGH_PREFIXES: [&str; 3] = ["/integrations/", "/simple/", "/graphql"];
+
+/// Every failure the REST API reports. The codes are part of the API: they
+/// are never renamed or reused, only added.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    MalformedRequest,
+    Unauthorized,
+    Forbidden,
+    TodoLimitReached,
+    NotFound,
+    MethodNotAllowed,
+    Conflict,
+    Gone,
+    ValidationFailed,
+    QuotaExceeded,
+    Internal,
+    Unavailable,
+}
+
+impl ErrorCode {
+    pub const ALL: [ErrorCode; 12] = [
+        ErrorCode::MalformedRequest,
+        ErrorCode::Unauthorized,
+        ErrorCode::Forbidden,
+        ErrorCode::TodoLimitReached,
+        ErrorCode::NotFound,
+        ErrorCode::MethodNotAllowed,
+        ErrorCode::Conflict,
+        ErrorCode::Gone,
+        ErrorCode::ValidationFailed,
+        ErrorCode::QuotaExceeded,
+        ErrorCode::Internal,
+        ErrorCode::Unavailable,
+    ];
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorCode::MalformedRequest => "malformed_request",
+            ErrorCode::Unauthorized => "unauthorized",
+            ErrorCode::Forbidden => "forbidden",
+            ErrorCode::TodoLimitReached => "todo_limit_reached",
+            ErrorCode::NotFound => "not_found",
+            ErrorCode::MethodNotAllowed => "method_not_allowed",
+            ErrorCode::Conflict => "conflict",
+            ErrorCode::Gone => "gone",
+            ErrorCode::ValidationFailed => "validation_failed",
+            ErrorCode::QuotaExceeded => "quota_exceeded",
+            ErrorCode::Internal => "internal",
+            ErrorCode::Unavailable => "unavailable",
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ErrorCode::MalformedRequest => StatusCode::BAD_REQUEST,
+            ErrorCode::Unauthorized => StatusCode::UNAUTHORIZED,
+            ErrorCode::Forbidden | ErrorCode::TodoLimitReached => StatusCode::FORBIDDEN,
+            ErrorCode::NotFound => StatusCode::NOT_FOUND,
+            ErrorCode::MethodNotAllowed => StatusCode::METHOD_NOT_ALLOWED,
+            ErrorCode::Conflict => StatusCode::CONFLICT,
+            ErrorCode::Gone => StatusCode::GONE,
+            ErrorCode::ValidationFailed => StatusCode::UNPROCESSABLE_ENTITY,
+            ErrorCode::QuotaExceeded => StatusCode::TOO_MANY_REQUESTS,
+            ErrorCode::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+            ErrorCode::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            ErrorCode::MalformedRequest => "The request could not be parsed.",
+            ErrorCode::Unauthorized => "Missing or invalid credentials.",
+            ErrorCode::Forbidden => "The credentials do not allow this request.",
+            ErrorCode::TodoLimitReached => "No more todos may be stored.",
+            ErrorCode::NotFound => "The resource does not exist.",
+            ErrorCode::MethodNotAllowed => "The resource does not support this method.",
+            ErrorCode::Conflict => "The request conflicts with the current state.",
+            ErrorCode::Gone => "The resource existed but has expired.",
+            ErrorCode::ValidationFailed => "The payload failed validation; see `errors`.",
+            ErrorCode::QuotaExceeded => "The monthly request quota is used up.",
+            ErrorCode::Internal => "The server failed unexpectedly.",
+            ErrorCode::Unavailable => "The server can not take the request right now.",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|error| error.code() == code)
+    }
+
+    /// The generic code of an error `status`, for failures that did not
+    /// name a more specific one.
+    pub fn for_status(status: StatusCode) -> Self {
+        Self::ALL
+            .into_iter()
+            .find(|error| error.status() == status)
+            .unwrap_or(if status.is_server_error() {
+                ErrorCode::Internal
+            } else {
+                ErrorCode::MalformedRequest
+            })
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ErrorCatalogEntry {
+    code: &'static str,
+    status: u16,
+    description: &'static str,
+}
+
+pub async fn error_catalog() -> Json<Vec<ErrorCatalogEntry>> {
+    let entries = ErrorCode::ALL
+        .iter()
+        .map(|error| ErrorCatalogEntry {
+            code: error.code(),
+            status: error.status().as_u16(),
+            description: error.description(),
+        })
+        .collect();
+    Json(entries)
+}
+
+/// Gives every error response a `code` from `ErrorCode`, both in the JSON
+/// body and as `x-error-code`. A body that already has a `code` keeps it,
+/// so handlers can name a more specific failure than the status does.
+/// Empty and plain-text bodies become `{ "code": ..., "message": ... }`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ErrorCodeLayer;
+
+impl<S> Layer<S> for ErrorCodeLayer {
+    type Service = ErrorCodes<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ErrorCodes { inner }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ErrorCodes<S> {
+    inner: S,
+}
+
+type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send + 'static>>;
+
+impl<S, B> Service<Request<B>> for ErrorCodes<S>
+where
+    S: Service<Request<B>, Response = Response>,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = BoxFuture<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        let path = req.uri().path();
+        let passthrough = PASSTHROUGH_PREFIXES
+            .iter()
+            .any(|prefix| path.starts_with(prefix));
+        let future = self.inner.call(req);
+
+        Box::pin(async move {
+            let res = future.await?;
+            if passthrough || !(res.status().is_client_error() || res.status().is_server_error()) {
+                return Ok(res);
+            }
+            Ok(attach_code(res).await)
+        })
+    }
+}
+
+async fn attach_code(res: Response) -> Response {
+    let (mut parts, body) = res.into_parts();
+    let bytes = match hyper::body::to_bytes(body).await {
+        Ok(bytes) => bytes,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    let fallback = ErrorCode::for_status(parts.status);
+    let mut payload = match serde_json::from_slice::<Value>(&bytes) {
+        Ok(Value::Object(fields)) => fields,
+        _ => {
+            let message = if bytes.is_empty() {
+                fallback.description().to_string()
+            } else {
+                String::from_utf8_lossy(&bytes).into_owned()
+            };
+            let mut fields = Map::new();
+            fields.insert("message".to_string(), Value::from(message));
+            fields
+        }
+    };
+    let code = payload
+        .get("code")
+        .and_then(Value::as_str)
+        .and_then(ErrorCode::from_code)
+        .unwrap_or(fallback);
+    payload.insert("code".to_string(), Value::from(code.code()));
+
+    parts
+        .headers
+        .insert(ERROR_CODE_HEADER, HeaderValue::from_static(code.code()));
+    parts.headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+    parts.headers.remove(header::CONTENT_LENGTH);
+    let body = Value::Object(payload).to_string();
+    Response::from_parts(parts, body::boxed(Full::from(body)))
+}
@@ -0,0 +1,113 @@
+use crate::auth::Owner;
+use crate::repositories::Todo;
+use axum::{
+    extract::Extension,
+    http::StatusCode,
+    response::{
+        sse::{Event, KeepAlive},
+        IntoResponse, Sse,
+    },
+};
+use chrono::Utc;
+use serde_json::{json, Value};
+use tokio::sync::broadcast;
+use tokio_stream::{
+    wrappers::{errors::BroadcastStreamRecvError, BroadcastStream},
+    StreamExt,
+};
+
+/// Changes a subscriber may fall behind by before it misses some.
+const EVENTS_CAPACITY: usize = 1024;
+
+/// A change to a todo, as published by the handlers that make it. A
+/// deletion names the user the todo belonged to, as it is gone by then.
+#[derive(Debug, Clone)]
+pub enum TodoChange {
+    Created(Todo),
+    Updated(Todo),
+    Deleted { id: i32, user_id: Option<i32> },
+}
+
+impl TodoChange {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TodoChange::Created(_) => "todo.created",
+            TodoChange::Updated(_) => "todo.updated",
+            TodoChange::Deleted { .. } => "todo.deleted",
+        }
+    }
+
+    pub fn todo_id(&self) -> i32 {
+        match self {
+            TodoChange::Created(todo) | TodoChange::Updated(todo) => todo.id(),
+            TodoChange::Deleted { id, .. } => *id,
+        }
+    }
+
+    pub fn user_id(&self) -> Option<i32> {
+        match self {
+            TodoChange::Created(todo) | TodoChange::Updated(todo) => todo.user_id(),
+            TodoChange::Deleted { user_id, .. } => *user_id,
+        }
+    }
+
+    /// The body that webhooks and the event stream carry.
+    pub fn payload(&self) -> Value {
+        let todo = match self {
+            TodoChange::Created(todo) | TodoChange::Updated(todo) => Some(todo),
+            TodoChange::Deleted { .. } => None,
+        };
+        json!({
+            "event": self.name(),
+            "todo_id": self.todo_id(),
+            "todo": todo,
+            "occurred_at": Utc::now(),
+        })
+    }
+}
+
+/// Broadcasts todo changes to every subscriber in the process.
+#[derive(Debug, Clone)]
+pub struct TodoEvents {
+    sender: broadcast::Sender<TodoChange>,
+}
+
+impl Default for TodoEvents {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(EVENTS_CAPACITY);
+        Self { sender }
+    }
+}
+
+impl TodoEvents {
+    /// Nobody listening is fine, the change is then simply dropped.
+    pub fn publish(&self, change: TodoChange) {
+        let _ = self.sender.send(change);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<TodoChange> {
+        self.sender.subscribe()
+    }
+}
+
+/// Streams changes to the caller's todos as server-sent events named after
+/// the change, e.g. `todo.updated`, until the client goes away.
+pub async fn todo_events(
+    owner: Owner,
+    events: Option<Extension<TodoEvents>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let Extension(events) = events.ok_or(StatusCode::NOT_FOUND)?;
+    let stream = BroadcastStream::new(events.subscribe()).filter_map(move |change| match change {
+        Ok(change) if !owner.may_see(&change) => None,
+        Ok(change) => Some(
+            Event::default()
+                .event(change.name())
+                .json_data(change.payload()),
+        ),
+        Err(BroadcastStreamRecvError::Lagged(missed)) => {
+            tracing::warn!("event stream fell behind, {} changes missed", missed);
+            None
+        }
+    });
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
@@ -0,0 +1,421 @@
+use crate::auth::Owner;
+use crate::chat::ChatWebhook;
+use crate::events::{TodoChange, TodoEvents};
+use crate::handlers::{
+    blockers_resolved, create_and_notify, ensure_owner, update_and_notify, validation_messages,
+};
+use crate::repositories::{
+    CreateTodo, Label, Page, Priority, RepositoryError, Todo, TodoFilter, TodoLimits,
+    TodoRepository, TodoSort, UpdateTodo,
+};
+use async_graphql::{
+    http::{playground_source, GraphQLPlaygroundConfig},
+    Context, EmptySubscription, Enum, ErrorExtensions, InputObject, MaybeUndefined, Object, Schema,
+    ID,
+};
+use axum::{extract::Extension, response::Html, Json};
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{marker::PhantomData, sync::Arc};
+use validator::Validate;
+
+pub type TodoSchema<T> = Schema<QueryRoot<T>, MutationRoot<T>, EmptySubscription>;
+
+pub fn todo_schema<T: TodoRepository>() -> TodoSchema<T> {
+    Schema::new(
+        QueryRoot(PhantomData),
+        MutationRoot(PhantomData),
+        EmptySubscription,
+    )
+}
+
+/// Runs a GraphQL request against the same repository, limits, event bus
+/// and chat webhook as the REST handlers. The request and response are plain JSON, as
+/// async-graphql's own axum integration targets a newer axum.
+pub async fn graphql<T: TodoRepository>(
+    Extension(schema): Extension<TodoSchema<T>>,
+    Extension(repository): Extension<Arc<T>>,
+    owner: Owner,
+    events: Option<Extension<TodoEvents>>,
+    limits: Option<Extension<TodoLimits>>,
+    chat: Option<Extension<ChatWebhook>>,
+    Json(req): Json<async_graphql::Request>,
+) -> Json<async_graphql::Response> {
+    let mut req = req.data(repository).data(owner);
+    if let Some(Extension(events)) = events {
+        req = req.data(events);
+    }
+    if let Some(Extension(limits)) = limits {
+        req = req.data(limits);
+    }
+    if let Some(Extension(chat)) = chat {
+        req = req.data(chat);
+    }
+    Json(schema.execute(req).await)
+}
+
+pub async fn graphql_playground() -> Html<String> {
+    Html(playground_source(GraphQLPlaygroundConfig::new("/graphql")))
+}
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq, Serialize)]
+#[graphql(name = "Priority", remote = "Priority")]
+#[serde(rename_all = "snake_case")]
+pub enum PriorityValue {
+    Low,
+    Medium,
+    High,
+    Urgent,
+}
+
+/// A todo as GraphQL sees it.
+pub struct TodoNode(Todo);
+
+#[Object(name = "Todo")]
+impl TodoNode {
+    async fn id(&self) -> ID {
+        ID::from(self.0.id().to_string())
+    }
+
+    async fn text(&self) -> &str {
+        self.0.text()
+    }
+
+    async fn completed(&self) -> bool {
+        self.0.is_completed()
+    }
+
+    /// Seconds spent on the todo so far.
+    async fn time_spent(&self) -> i64 {
+        self.0.time_spent()
+    }
+
+    async fn snoozed_until(&self) -> Option<DateTime<Utc>> {
+        self.0.snoozed_until()
+    }
+
+    async fn due_date(&self) -> Option<DateTime<Utc>> {
+        self.0.due_date()
+    }
+
+    async fn priority(&self) -> PriorityValue {
+        self.0.priority().into()
+    }
+
+    async fn parent_id(&self) -> Option<ID> {
+        self.0.parent_id().map(|id| ID::from(id.to_string()))
+    }
+
+    async fn project_id(&self) -> ID {
+        ID::from(self.0.project_id().to_string())
+    }
+
+    async fn position(&self) -> i64 {
+        self.0.position()
+    }
+
+    async fn recurrence(&self) -> Option<&str> {
+        self.0.recurrence()
+    }
+
+    async fn lat(&self) -> Option<f64> {
+        self.0.location().map(|(lat, _)| lat)
+    }
+
+    async fn lon(&self) -> Option<f64> {
+        self.0.location().map(|(_, lon)| lon)
+    }
+
+    async fn place(&self) -> Option<&str> {
+        self.0.place()
+    }
+
+    async fn remind_at(&self) -> Option<DateTime<Utc>> {
+        self.0.remind_at()
+    }
+
+    async fn created_at(&self) -> DateTime<Utc> {
+        self.0.created_at()
+    }
+
+    async fn updated_at(&self) -> DateTime<Utc> {
+        self.0.updated_at()
+    }
+
+    async fn labels(&self) -> Vec<LabelNode> {
+        self.0.labels().iter().cloned().map(LabelNode).collect()
+    }
+
+    async fn blocked_by(&self) -> Vec<ID> {
+        self.0
+            .blocked_by()
+            .iter()
+            .map(|id| ID::from(id.to_string()))
+            .collect()
+    }
+}
+
+pub struct LabelNode(Label);
+
+#[Object(name = "Label")]
+impl LabelNode {
+    async fn id(&self) -> ID {
+        ID::from(self.0.id().to_string())
+    }
+
+    async fn name(&self) -> &str {
+        self.0.name()
+    }
+}
+
+/// Narrows down `todos`. Snoozed todos are left out unless asked for, as in
+/// `GET /todos`.
+#[derive(InputObject, Default)]
+pub struct TodoFilterInput {
+    completed: Option<bool>,
+    /// Only todos whose text contains this, ignoring case.
+    text_contains: Option<String>,
+    /// Only todos with the label of this name.
+    label: Option<String>,
+    due_before: Option<DateTime<Utc>>,
+    due_after: Option<DateTime<Utc>>,
+    /// Only open todos whose due date has passed.
+    overdue: Option<bool>,
+    priority: Option<PriorityValue>,
+    project_id: Option<i32>,
+    parent_id: Option<i32>,
+    include_snoozed: Option<bool>,
+}
+
+impl TodoFilterInput {
+    fn into_filter(self, now: DateTime<Utc>, owner: Owner) -> TodoFilter {
+        TodoFilter {
+            awake_at: if self.include_snoozed.unwrap_or(false) {
+                None
+            } else {
+                Some(now)
+            },
+            completed: self.completed,
+            text_contains: self.text_contains,
+            label: self.label,
+            due_before: self.due_before,
+            due_after: self.due_after,
+            overdue_at: self.overdue.unwrap_or(false).then_some(now),
+            priority: self.priority.map(Into::into),
+            project_id: self.project_id,
+            parent_id: self.parent_id,
+            user_id: owner.user_id(),
+            ..TodoFilter::default()
+        }
+    }
+}
+
+/// Same fields as the body of `POST /todos`.
+#[derive(InputObject, Serialize)]
+pub struct CreateTodoInput {
+    text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    label_ids: Option<Vec<i32>>,
+    due_date: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    priority: Option<PriorityValue>,
+    parent_id: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    blocked_by: Option<Vec<i32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    project_id: Option<i32>,
+    recurrence: Option<String>,
+    lat: Option<f64>,
+    lon: Option<f64>,
+    place: Option<String>,
+    remind_at: Option<DateTime<Utc>>,
+}
+
+/// Same fields as the body of `PATCH /todos/:id`; `null` clears a field
+/// where the REST API allows it.
+#[derive(InputObject, Serialize)]
+pub struct UpdateTodoInput {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    completed: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    label_ids: Option<Vec<i32>>,
+    #[serde(skip_serializing_if = "MaybeUndefined::is_undefined")]
+    due_date: MaybeUndefined<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    priority: Option<PriorityValue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    blocked_by: Option<Vec<i32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    project_id: Option<i32>,
+    #[serde(skip_serializing_if = "MaybeUndefined::is_undefined")]
+    recurrence: MaybeUndefined<String>,
+    #[serde(skip_serializing_if = "MaybeUndefined::is_undefined")]
+    lat: MaybeUndefined<f64>,
+    #[serde(skip_serializing_if = "MaybeUndefined::is_undefined")]
+    lon: MaybeUndefined<f64>,
+    #[serde(skip_serializing_if = "MaybeUndefined::is_undefined")]
+    place: MaybeUndefined<String>,
+    #[serde(skip_serializing_if = "MaybeUndefined::is_undefined")]
+    remind_at: MaybeUndefined<DateTime<Utc>>,
+}
+
+/// Turns an input into the REST payload it mirrors, so that both APIs
+/// share the defaults and validation.
+fn payload<I: Serialize, P: DeserializeOwned + Validate>(input: I) -> async_graphql::Result<P> {
+    let payload: P = serde_json::from_value(serde_json::to_value(input)?)?;
+    payload.validate().map_err(|errors| {
+        async_graphql::Error::new(validation_messages(&errors).join(", "))
+            .extend_with(|_, e| e.set("code", "UNPROCESSABLE_ENTITY"))
+    })?;
+    Ok(payload)
+}
+
+fn not_found(id: i32) -> async_graphql::Error {
+    async_graphql::Error::new(format!("Todo not found, id is {}", id))
+        .extend_with(|_, e| e.set("code", "NOT_FOUND"))
+}
+
+/// Keeps the repository's `NotFound` and `Conflict` recognisable.
+fn repository_error(error: anyhow::Error) -> async_graphql::Error {
+    match error.downcast_ref::<RepositoryError>() {
+        Some(RepositoryError::NotFound(id)) => not_found(*id),
+        Some(RepositoryError::Conflict(message)) => {
+            async_graphql::Error::new(message.clone()).extend_with(|_, e| e.set("code", "CONFLICT"))
+        }
+        Some(RepositoryError::TodoLimitReached { .. })
+        | Some(RepositoryError::ProjectTodoLimitReached { .. }) => {
+            async_graphql::Error::new(error.to_string())
+                .extend_with(|_, e| e.set("code", "FORBIDDEN"))
+        }
+        _ => async_graphql::Error::new(error.to_string()),
+    }
+}
+
+/// The caller, as `RequireAuth` left them for the request.
+fn owner(ctx: &Context<'_>) -> Owner {
+    ctx.data_opt::<Owner>().copied().unwrap_or_default()
+}
+
+fn publish(ctx: &Context<'_>, change: TodoChange) {
+    if let Some(events) = ctx.data_opt::<TodoEvents>() {
+        events.publish(change);
+    }
+}
+
+pub struct QueryRoot<T>(PhantomData<T>);
+
+#[Object(name = "Query")]
+impl<T: TodoRepository> QueryRoot<T> {
+    async fn todo(&self, ctx: &Context<'_>, id: i32) -> async_graphql::Result<Option<TodoNode>> {
+        let repository = ctx.data_unchecked::<Arc<T>>();
+        match repository.find(id).await {
+            Ok(todo) if owner(ctx).may_access(&todo) => Ok(Some(TodoNode(todo))),
+            Ok(_) => Ok(None),
+            Err(e)
+                if matches!(
+                    e.downcast_ref::<RepositoryError>(),
+                    Some(RepositoryError::NotFound(_))
+                ) =>
+            {
+                Ok(None)
+            }
+            Err(e) => Err(repository_error(e)),
+        }
+    }
+
+    /// Newest first.
+    async fn todos(
+        &self,
+        ctx: &Context<'_>,
+        filter: Option<TodoFilterInput>,
+        #[graphql(default = 50, validator(minimum = 1, maximum = 200))] limit: i32,
+        #[graphql(default = 0, validator(minimum = 0))] offset: i32,
+    ) -> async_graphql::Result<Vec<TodoNode>> {
+        let repository = ctx.data_unchecked::<Arc<T>>();
+        let filter = filter
+            .unwrap_or_default()
+            .into_filter(Utc::now(), owner(ctx));
+        let page = Page {
+            limit: Some(limit as usize),
+            offset: offset as usize,
+        };
+        let todos = repository
+            .all(&filter, TodoSort::default(), page)
+            .await
+            .map_err(repository_error)?;
+        Ok(todos.into_iter().map(TodoNode).collect())
+    }
+}
+
+pub struct MutationRoot<T>(PhantomData<T>);
+
+#[Object(name = "Mutation")]
+impl<T: TodoRepository> MutationRoot<T> {
+    async fn create_todo(
+        &self,
+        ctx: &Context<'_>,
+        input: CreateTodoInput,
+    ) -> async_graphql::Result<TodoNode> {
+        let repository = ctx.data_unchecked::<Arc<T>>();
+        let limits = ctx.data_opt::<TodoLimits>().copied().unwrap_or_default();
+        let payload: CreateTodo = payload(input)?;
+        let todo = create_and_notify(
+            &**repository,
+            owner(ctx),
+            payload.with_limits(limits),
+            ctx.data_opt::<ChatWebhook>(),
+            ctx.data_opt::<TodoEvents>(),
+        )
+        .await
+        .map_err(repository_error)?;
+        Ok(TodoNode(todo))
+    }
+
+    async fn update_todo(
+        &self,
+        ctx: &Context<'_>,
+        id: i32,
+        input: UpdateTodoInput,
+    ) -> async_graphql::Result<TodoNode> {
+        let repository = ctx.data_unchecked::<Arc<T>>();
+        let payload: UpdateTodo = payload(input)?;
+        ensure_owner(&**repository, owner(ctx), id)
+            .await
+            .map_err(repository_error)?;
+        if payload.completes() {
+            let resolved = blockers_resolved(&**repository, owner(ctx), id, &payload)
+                .await
+                .map_err(|status| async_graphql::Error::new(status.to_string()))?;
+            if !resolved {
+                return Err(async_graphql::Error::new("Todo has open blockers")
+                    .extend_with(|_, e| e.set("code", "CONFLICT")));
+            }
+        }
+
+        let todo = update_and_notify(
+            &**repository,
+            owner(ctx),
+            id,
+            payload,
+            ctx.data_opt::<ChatWebhook>(),
+            ctx.data_opt::<TodoEvents>(),
+        )
+        .await
+        .map_err(repository_error)?;
+        Ok(TodoNode(todo))
+    }
+
+    /// The id of the deleted todo.
+    async fn delete_todo(&self, ctx: &Context<'_>, id: i32) -> async_graphql::Result<ID> {
+        let repository = ctx.data_unchecked::<Arc<T>>();
+        ensure_owner(&**repository, owner(ctx), id)
+            .await
+            .map_err(repository_error)?;
+        repository.delete(id).await.map_err(repository_error)?;
+        let user_id = owner(ctx).user_id();
+        publish(ctx, TodoChange::Deleted { id, user_id });
+        Ok(ID::from(id.to_string()))
+    }
+}
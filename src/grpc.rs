@@ -0,0 +1,393 @@
+// `tonic::Status` is large, but it is what every service method returns.
+#![allow(clippy::result_large_err)]
+
+use crate::auth::{presented_token, AuthConfig, Credential, Owner};
+use crate::chat::ChatWebhook;
+use crate::events::{TodoChange, TodoEvents};
+use crate::handlers::{
+    blockers_resolved, create_and_notify, ensure_owner, update_and_notify, validation_messages,
+};
+use crate::repositories::{
+    CreateTodo, Page, Priority, RepositoryError, Todo, TodoFilter, TodoLimits, TodoRepository,
+    TodoSort, UpdateTodo, UserRepository,
+};
+use axum::http::StatusCode;
+use chrono::{DateTime, TimeZone, Utc};
+use prost_types::Timestamp;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use tokio::{sync::mpsc, task::JoinHandle};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{transport::Server, Request, Response, Status};
+use validator::Validate;
+
+pub mod proto {
+    tonic::include_proto!("todo.v1");
+}
+
+use proto::todo_service_server::{TodoService, TodoServiceServer};
+
+/// Todos per repository query while streaming a listing.
+const LIST_PAGE_SIZE: usize = 100;
+/// Todos a listing may read ahead of a slow client.
+const LIST_BUFFER: usize = 16;
+
+/// Serves `proto/todo.proto` on a port of its own, next to the HTTP server,
+/// with the same limits, event bus and chat webhook. With `AuthConfig` set,
+/// calls need an access token or API key in their `authorization` or
+/// `x-api-key` metadata, as HTTP requests do.
+#[derive(Debug, Clone)]
+pub struct GrpcServer {
+    addr: SocketAddr,
+    events: Option<TodoEvents>,
+    limits: TodoLimits,
+    chat: Option<ChatWebhook>,
+    auth: Option<AuthConfig>,
+}
+
+impl GrpcServer {
+    pub fn new(addr: SocketAddr) -> Self {
+        Self {
+            addr,
+            events: None,
+            limits: TodoLimits::default(),
+            chat: None,
+            auth: None,
+        }
+    }
+
+    pub fn with_events(self, events: TodoEvents) -> Self {
+        Self {
+            events: Some(events),
+            ..self
+        }
+    }
+
+    pub fn with_limits(self, limits: TodoLimits) -> Self {
+        Self { limits, ..self }
+    }
+
+    pub fn with_chat(self, chat: ChatWebhook) -> Self {
+        Self {
+            chat: Some(chat),
+            ..self
+        }
+    }
+
+    pub fn with_auth(self, auth: AuthConfig) -> Self {
+        Self {
+            auth: Some(auth),
+            ..self
+        }
+    }
+
+    /// Runs on a tokio task until the process exits.
+    pub fn spawn<T: TodoRepository + UserRepository>(self, repository: T) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            tracing::debug!("grpc listening on {}", self.addr);
+            let service = TodoGrpc {
+                repository,
+                events: self.events,
+                limits: self.limits,
+                chat: self.chat,
+                auth: self.auth,
+            };
+            if let Err(e) = Server::builder()
+                .add_service(TodoServiceServer::new(service))
+                .serve(self.addr)
+                .await
+            {
+                tracing::error!("grpc server stopped: {}", e);
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct TodoGrpc<T> {
+    repository: T,
+    events: Option<TodoEvents>,
+    limits: TodoLimits,
+    chat: Option<ChatWebhook>,
+    auth: Option<AuthConfig>,
+}
+
+impl<T: UserRepository> TodoGrpc<T> {
+    fn publish(&self, change: TodoChange) {
+        if let Some(events) = &self.events {
+            events.publish(change);
+        }
+    }
+
+    /// The caller, checked as `RequireAuth` checks HTTP requests. Without
+    /// `AuthConfig` everyone gets through.
+    async fn authenticate<R>(&self, request: &Request<R>) -> Result<Owner, Status> {
+        let config = match &self.auth {
+            Some(config) => config,
+            None => return Ok(Owner::default()),
+        };
+        let token = presented_token(&request.metadata().clone().into_headers())
+            .ok_or_else(|| Status::unauthenticated("Missing access token or API key"))?;
+        let credential = Credential::check(config, &self.repository, &token)
+            .await
+            .map_err(status_of)?
+            .ok_or_else(|| Status::unauthenticated("Invalid access token or API key"))?;
+        Ok(Owner::from(credential))
+    }
+}
+
+fn status_of(error: anyhow::Error) -> Status {
+    match error.downcast_ref::<RepositoryError>() {
+        Some(RepositoryError::NotFound(_)) => Status::not_found(error.to_string()),
+        Some(RepositoryError::Conflict(_)) => Status::failed_precondition(error.to_string()),
+        Some(RepositoryError::TodoLimitReached { .. })
+        | Some(RepositoryError::ProjectTodoLimitReached { .. }) => {
+            Status::resource_exhausted(error.to_string())
+        }
+        _ => Status::internal(error.to_string()),
+    }
+}
+
+fn timestamp(time: DateTime<Utc>) -> Timestamp {
+    Timestamp {
+        seconds: time.timestamp(),
+        nanos: time.timestamp_subsec_nanos() as i32,
+    }
+}
+
+fn datetime(timestamp: Timestamp) -> Result<DateTime<Utc>, Status> {
+    u32::try_from(timestamp.nanos)
+        .ok()
+        .and_then(|nanos| Utc.timestamp_opt(timestamp.seconds, nanos).single())
+        .ok_or_else(|| Status::invalid_argument("Out of timestamp range"))
+}
+
+/// `None` for `PRIORITY_UNSPECIFIED`.
+fn priority(value: i32) -> Result<Option<Priority>, Status> {
+    match proto::Priority::from_i32(value) {
+        Some(proto::Priority::Unspecified) => Ok(None),
+        Some(proto::Priority::Low) => Ok(Some(Priority::Low)),
+        Some(proto::Priority::Medium) => Ok(Some(Priority::Medium)),
+        Some(proto::Priority::High) => Ok(Some(Priority::High)),
+        Some(proto::Priority::Urgent) => Ok(Some(Priority::Urgent)),
+        None => Err(Status::invalid_argument("Unknown priority")),
+    }
+}
+
+impl From<Todo> for proto::Todo {
+    fn from(todo: Todo) -> Self {
+        let priority = match todo.priority() {
+            Priority::Low => proto::Priority::Low,
+            Priority::Medium => proto::Priority::Medium,
+            Priority::High => proto::Priority::High,
+            Priority::Urgent => proto::Priority::Urgent,
+        };
+        proto::Todo {
+            id: todo.id(),
+            text: todo.text().to_string(),
+            completed: todo.is_completed(),
+            time_spent: todo.time_spent(),
+            due_date: todo.due_date().map(timestamp),
+            priority: priority as i32,
+            parent_id: todo.parent_id(),
+            project_id: todo.project_id(),
+            labels: todo
+                .labels()
+                .iter()
+                .map(|label| proto::Label {
+                    id: label.id(),
+                    name: label.name().to_string(),
+                })
+                .collect(),
+            blocked_by: todo.blocked_by().to_vec(),
+            created_at: Some(timestamp(todo.created_at())),
+            updated_at: Some(timestamp(todo.updated_at())),
+        }
+    }
+}
+
+/// Goes through the JSON the REST payloads are read from, so that both APIs
+/// share the defaults and validation.
+fn payload<P: DeserializeOwned + Validate>(value: Value) -> Result<P, Status> {
+    let payload: P =
+        serde_json::from_value(value).map_err(|e| Status::invalid_argument(e.to_string()))?;
+    payload
+        .validate()
+        .map_err(|errors| Status::invalid_argument(validation_messages(&errors).join(", ")))?;
+    Ok(payload)
+}
+
+fn create_payload(req: proto::CreateTodoRequest) -> Result<CreateTodo, Status> {
+    let mut value = json!({
+        "text": req.text,
+        "label_ids": req.label_ids,
+        "due_date": req.due_date.map(datetime).transpose()?,
+        "parent_id": req.parent_id,
+        "blocked_by": req.blocked_by,
+    });
+    if let Some(priority) = priority(req.priority)? {
+        value["priority"] = json!(priority);
+    }
+    if let Some(project_id) = req.project_id {
+        value["project_id"] = json!(project_id);
+    }
+    payload(value)
+}
+
+fn update_payload(req: proto::UpdateTodoRequest) -> Result<UpdateTodo, Status> {
+    let mut value = json!({
+        "text": req.text,
+        "completed": req.completed,
+        "priority": priority(req.priority)?,
+        "project_id": req.project_id,
+    });
+    if req.clear_due_date {
+        value["due_date"] = Value::Null;
+    } else if let Some(due_date) = req.due_date {
+        value["due_date"] = json!(datetime(due_date)?);
+    }
+    payload(value)
+}
+
+#[tonic::async_trait]
+impl<T: TodoRepository + UserRepository> TodoService for TodoGrpc<T> {
+    async fn get_todo(
+        &self,
+        request: Request<proto::GetTodoRequest>,
+    ) -> Result<Response<proto::Todo>, Status> {
+        let owner = self.authenticate(&request).await?;
+        let id = request.into_inner().id;
+        let todo = self.repository.find(id).await.map_err(status_of)?;
+        if !owner.may_access(&todo) {
+            return Err(status_of(RepositoryError::NotFound(id).into()));
+        }
+        Ok(Response::new(todo.into()))
+    }
+
+    type ListTodosStream = ReceiverStream<Result<proto::Todo, Status>>;
+
+    /// Reads a page at a time, resuming after the last todo sent, and stops
+    /// once the client hangs up.
+    async fn list_todos(
+        &self,
+        request: Request<proto::ListTodosRequest>,
+    ) -> Result<Response<Self::ListTodosStream>, Status> {
+        let owner = self.authenticate(&request).await?;
+        let req = request.into_inner();
+        let mut filter = TodoFilter {
+            awake_at: (!req.include_snoozed).then(Utc::now),
+            completed: req.completed,
+            text_contains: req.text_contains,
+            label: req.label,
+            priority: priority(req.priority)?,
+            project_id: req.project_id,
+            user_id: owner.user_id(),
+            ..TodoFilter::default()
+        };
+        let page = Page {
+            limit: Some(LIST_PAGE_SIZE),
+            offset: 0,
+        };
+
+        let (sender, receiver) = mpsc::channel(LIST_BUFFER);
+        let repository = self.repository.clone();
+        tokio::spawn(async move {
+            loop {
+                let todos = match repository.all(&filter, TodoSort::default(), page).await {
+                    Ok(todos) => todos,
+                    Err(e) => {
+                        let _ = sender.send(Err(status_of(e))).await;
+                        return;
+                    }
+                };
+                let last = match todos.last() {
+                    Some(todo) => todo.id(),
+                    None => return,
+                };
+                let full = todos.len() == LIST_PAGE_SIZE;
+                for todo in todos {
+                    if sender.send(Ok(todo.into())).await.is_err() {
+                        return;
+                    }
+                }
+                if !full {
+                    return;
+                }
+                filter.before_id = Some(last);
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(receiver)))
+    }
+
+    async fn create_todo(
+        &self,
+        request: Request<proto::CreateTodoRequest>,
+    ) -> Result<Response<proto::Todo>, Status> {
+        let owner = self.authenticate(&request).await?;
+        let payload = create_payload(request.into_inner())?.with_limits(self.limits);
+        let todo = create_and_notify(
+            &self.repository,
+            owner,
+            payload,
+            self.chat.as_ref(),
+            self.events.as_ref(),
+        )
+        .await
+        .map_err(status_of)?;
+        Ok(Response::new(todo.into()))
+    }
+
+    async fn update_todo(
+        &self,
+        request: Request<proto::UpdateTodoRequest>,
+    ) -> Result<Response<proto::Todo>, Status> {
+        let owner = self.authenticate(&request).await?;
+        let req = request.into_inner();
+        let id = req.id;
+        let payload = update_payload(req)?;
+        ensure_owner(&self.repository, owner, id)
+            .await
+            .map_err(status_of)?;
+        if payload.completes() {
+            let resolved = blockers_resolved(&self.repository, owner, id, &payload)
+                .await
+                .map_err(|status| match status {
+                    StatusCode::NOT_FOUND => Status::not_found(format!("NotFound, id is {}", id)),
+                    status => Status::internal(status.to_string()),
+                })?;
+            if !resolved {
+                return Err(Status::failed_precondition("Todo has open blockers"));
+            }
+        }
+
+        let todo = update_and_notify(
+            &self.repository,
+            owner,
+            id,
+            payload,
+            self.chat.as_ref(),
+            self.events.as_ref(),
+        )
+        .await
+        .map_err(status_of)?;
+        Ok(Response::new(todo.into()))
+    }
+
+    async fn delete_todo(
+        &self,
+        request: Request<proto::DeleteTodoRequest>,
+    ) -> Result<Response<proto::DeleteTodoResponse>, Status> {
+        let owner = self.authenticate(&request).await?;
+        let id = request.into_inner().id;
+        ensure_owner(&self.repository, owner, id)
+            .await
+            .map_err(status_of)?;
+        self.repository.delete(id).await.map_err(status_of)?;
+        self.publish(TodoChange::Deleted {
+            id,
+            user_id: owner.user_id(),
+        });
+        Ok(Response::new(proto::DeleteTodoResponse {}))
+    }
+}
@@ -1,17 +1,37 @@
-use crate::repositories::{CreateTodo, TodoRepository, UpdateTodo};
+use crate::auth::Owner;
+use crate::chat::{ChatWebhook, TodoEvent};
+use crate::errors::ErrorCode;
+use crate::events::{TodoChange, TodoEvents};
+use crate::repositories::{
+    all_by_urgency, CreateLabel, CreateProject, CreateShareLink, CreateTodo, Nearby, Page,
+    Priority, ReorderTodo, RepositoryError, ShareLink, SnoozeTodo, SortField, SortOrder, Todo,
+    TodoFilter, TodoLimits, TodoRepository, TodoSort, TriageTodo, UpdateLabel, UpdateProject,
+    UpdateTodo, DEFAULT_PROJECT_ID,
+};
 use axum::{
     async_trait,
-    extract::{Extension, FromRequest, Path, RequestParts},
-    http::StatusCode,
-    response::IntoResponse,
+    extract::{rejection::QueryRejection, Extension, FromRequest, Path, Query, RequestParts},
+    http::{header::CONTENT_TYPE, StatusCode},
+    response::{Headers, Html, IntoResponse, Response},
     BoxError, Json,
 };
-use serde::de::DeserializeOwned;
-use std::sync::Arc;
-use validator::Validate;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use image::{DynamicImage, ImageOutputFormat, Luma};
+use qrcode::QrCode;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::json;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::Arc,
+};
+use utoipa::{
+    openapi::schema::{ObjectBuilder, OneOfBuilder, Ref, Schema, SchemaType},
+    IntoParams, ToSchema,
+};
+use validator::{Validate, ValidationErrors};
 
 #[derive(Debug)]
-pub struct ValidatedJson<T>(T);
+pub struct ValidatedJson<T>(pub T);
 
 #[async_trait]
 impl<T, B> FromRequest<B> for ValidatedJson<T>
@@ -21,69 +41,1691 @@ where
     B::Data: Send,
     B::Error: Into<BoxError>,
 {
-    type Rejection = (StatusCode, String);
+    type Rejection = Response;
 
     async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
         let Json(value) = Json::<T>::from_request(req).await.map_err(|rejection| {
             let message = format!("Json parse error: [{}]", rejection);
-            (StatusCode::BAD_REQUEST, message)
+            (StatusCode::BAD_REQUEST, message).into_response()
         })?;
 
         value.validate().map_err(|rejection| {
-            let message = format!("Validation error: [{}]", rejection).replace('\n', ", ");
-            (StatusCode::BAD_REQUEST, message)
+            let body = ValidationErrorBody::from(rejection);
+            (StatusCode::UNPROCESSABLE_ENTITY, Json(body)).into_response()
         })?;
 
         Ok(ValidatedJson(value))
     }
 }
 
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ValidationErrorBody {
+    errors: Vec<FieldError>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub(crate) struct FieldError {
+    field: String,
+    code: String,
+    message: Option<String>,
+    /// The rejected value.
+    #[schema(value_type = Option<Object>)]
+    value: Option<serde_json::Value>,
+}
+
+impl From<ValidationErrors> for ValidationErrorBody {
+    fn from(errors: ValidationErrors) -> Self {
+        let mut errors: Vec<FieldError> = errors
+            .field_errors()
+            .into_iter()
+            .flat_map(|(field, errors)| {
+                errors.iter().map(move |error| FieldError {
+                    field: field.to_string(),
+                    code: error.code.to_string(),
+                    message: error.message.as_ref().map(|message| message.to_string()),
+                    value: error.params.get("value").cloned(),
+                })
+            })
+            .collect();
+        errors.sort_by(|a, b| a.field.cmp(&b.field));
+
+        Self { errors }
+    }
+}
+
+/// Flattens validation errors into their human readable messages, for
+/// callers that answer in plain text rather than `ValidationErrorBody`.
+pub(crate) fn validation_messages(errors: &ValidationErrors) -> Vec<String> {
+    errors
+        .field_errors()
+        .values()
+        .flat_map(|errors| errors.iter())
+        .map(|error| {
+            error
+                .message
+                .as_ref()
+                .map(|message| message.to_string())
+                .unwrap_or_else(|| error.code.to_string())
+        })
+        .collect()
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/todos",
+    request_body = CreateTodo,
+    responses(
+        (status = 201, description = "The created todo", body = Todo),
+        (status = 403, description = "The todo limit is reached"),
+        (status = 404, description = "A label, parent or project does not exist"),
+        (status = 409, description = "The blockers would form a cycle"),
+        (status = 422, description = "Invalid todo", body = ValidationErrorBody)
+    ),
+    tag = "todos"
+)]
 pub async fn create_todo<T: TodoRepository>(
     ValidatedJson(payload): ValidatedJson<CreateTodo>,
     Extension(repository): Extension<Arc<T>>,
-) -> Result<impl IntoResponse, StatusCode> {
-    let todo = repository
-        .create(payload)
-        .await
-        .or(Err(StatusCode::NOT_FOUND))?;
+    owner: Owner,
+    limits: Option<Extension<TodoLimits>>,
+    chat: Option<Extension<ChatWebhook>>,
+    events: Option<Extension<TodoEvents>>,
+) -> Result<impl IntoResponse, Response> {
+    let limits = limits.map(|Extension(limits)| limits).unwrap_or_default();
+    let todo = create_and_notify(
+        &*repository,
+        owner,
+        payload.with_limits(limits),
+        chat.as_ref().map(|Extension(chat)| chat),
+        events.as_ref().map(|Extension(events)| events),
+    )
+    .await
+    .map_err(create_error_response)?;
 
     Ok((StatusCode::CREATED, Json(todo)))
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/v1/todos/{id}",
+    params(("id" = i32, Path, description = "Todo id")),
+    responses(
+        (status = 200, description = "The todo", body = Todo),
+        (status = 404, description = "No such todo")
+    ),
+    tag = "todos"
+)]
 pub async fn find_todo<T: TodoRepository>(
     Path(id): Path<i32>,
     Extension(repository): Extension<Arc<T>>,
+    owner: Owner,
 ) -> Result<impl IntoResponse, StatusCode> {
-    let todo = repository.find(id).await.or(Err(StatusCode::NOT_FOUND))?;
+    let todo = repository.find(id).await.map_err(repository_error_status)?;
+    if !owner.may_access(&todo) {
+        return Err(StatusCode::NOT_FOUND);
+    }
     Ok((StatusCode::OK, Json(todo)))
 }
 
+/// Fails with `NotFound` unless `owner` may access todo `id`, so that
+/// someone else's todo looks like a missing one. Without an owner nothing
+/// is looked up. Every API checks ownership through here.
+pub(crate) async fn ensure_owner<T: TodoRepository>(
+    repository: &T,
+    owner: Owner,
+    id: i32,
+) -> anyhow::Result<()> {
+    if owner.user_id().is_none() {
+        return Ok(());
+    }
+    let todo = repository.find(id).await?;
+    if owner.may_access(&todo) {
+        Ok(())
+    } else {
+        Err(RepositoryError::NotFound(id).into())
+    }
+}
+
+/// Fails with `NotFound` unless `owner` may attach label `id`, which is
+/// only their own.
+pub(crate) async fn ensure_label_owner<T: TodoRepository>(
+    repository: &T,
+    owner: Owner,
+    id: i32,
+) -> anyhow::Result<()> {
+    if owner.user_id().is_none() {
+        return Ok(());
+    }
+    let label = repository.find_label(id).await?;
+    if owner.may_access_label(&label) {
+        Ok(())
+    } else {
+        Err(RepositoryError::NotFound(id).into())
+    }
+}
+
+/// Fails with `NotFound` unless `owner` may file todos under project `id`;
+/// see `Owner::may_use_project`.
+pub(crate) async fn ensure_project_owner<T: TodoRepository>(
+    repository: &T,
+    owner: Owner,
+    id: i32,
+) -> anyhow::Result<()> {
+    if owner.user_id().is_none() {
+        return Ok(());
+    }
+    let project = repository.find_project(id).await?;
+    if owner.may_use_project(&project) {
+        Ok(())
+    } else {
+        Err(RepositoryError::NotFound(id).into())
+    }
+}
+
+/// `ensure_owner`, answering 404 for someone else's todo.
+async fn check_owner<T: TodoRepository>(
+    repository: &T,
+    owner: Owner,
+    id: i32,
+) -> Result<(), StatusCode> {
+    ensure_owner(repository, owner, id)
+        .await
+        .map_err(repository_error_status)
+}
+
+/// Page size of `GET /todos` when `limit` is not given.
+pub const DEFAULT_PAGE_LIMIT: usize = 50;
+/// Largest `limit` honoured by `GET /todos`; larger values are clamped.
+pub const MAX_PAGE_LIMIT: usize = 200;
+pub const TOTAL_COUNT_HEADER: &str = "x-total-count";
+/// Most ids one lookup may ask for.
+pub const MAX_LOOKUP_IDS: usize = 1000;
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListOptions {
+    /// Looks up exactly these todos, e.g. `1,5,9`, instead of listing.
+    ids: Option<String>,
+    include_snoozed: Option<bool>,
+    /// Only open todos that have not been updated for this many days.
+    stale_days: Option<u32>,
+    completed: Option<bool>,
+    /// Only todos with the label of this name.
+    label: Option<String>,
+    due_before: Option<DateTime<Utc>>,
+    due_after: Option<DateTime<Utc>>,
+    /// Only open todos whose due date has passed.
+    overdue: Option<bool>,
+    priority: Option<Priority>,
+    sort: Option<ListSort>,
+    order: Option<SortOrder>,
+    limit: Option<usize>,
+    offset: Option<usize>,
+    /// Switches to cursor paging: a `next_cursor` from a previous page, or
+    /// empty for the first page.
+    after: Option<String>,
+    /// Nests subtasks under their parents and lists every matching todo at
+    /// once, so it can not be combined with paging.
+    tree: Option<bool>,
+}
+
+/// Order of `GET /todos`: a field the repository sorts by, or `urgency`,
+/// which is scored here as it changes with the current time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ListSort {
+    /// What to do next first, see `urgency`.
+    Urgency,
+    #[serde(untagged)]
+    Field(SortField),
+}
+
+// By hand, as the derive documents the untagged variant as an object.
+impl ToSchema for ListSort {
+    fn schema() -> Schema {
+        OneOfBuilder::new()
+            .item(
+                ObjectBuilder::new()
+                    .schema_type(SchemaType::String)
+                    .enum_values(Some(["urgency"])),
+            )
+            .item(Ref::from_schema_name("SortField"))
+            .into()
+    }
+}
+
+/// A page of `GET /todos` in cursor mode. `next_cursor` is absent on the
+/// last page.
+#[derive(Debug, Serialize)]
+pub struct TodoCursorPage {
+    todos: Vec<Todo>,
+    next_cursor: Option<String>,
+}
+
+/// The body of `POST /todos/lookup`, for id lists too long for a URL.
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct LookupTodos {
+    #[validate(length(min = 1, max = 1000, message = "Specify 1 to 1000 ids"))]
+    ids: Vec<i32>,
+}
+
+/// The todos found by a lookup in the order asked for, and the ids that
+/// were not.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TodoLookup {
+    todos: Vec<Todo>,
+    missing: Vec<i32>,
+}
+
+/// A todo with its subtasks, in `?tree=true` mode.
+#[derive(Debug, Serialize)]
+pub struct TodoNode {
+    #[serde(flatten)]
+    todo: Todo,
+    children: Vec<TodoNode>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/todos",
+    params(ListOptions),
+    responses(
+        (status = 200, description = "A page of todos, newest first. `after` answers with a cursor page, `tree` with nested todos and `ids` with a lookup instead.", body = [Todo]),
+        (status = 400, description = "Invalid options")
+    ),
+    tag = "todos"
+)]
 pub async fn all_todo<T: TodoRepository>(
+    options: Result<Query<ListOptions>, QueryRejection>,
+    Extension(repository): Extension<Arc<T>>,
+    owner: Owner,
+) -> Result<Response, StatusCode> {
+    // An unknown sort field is as invalid as a bad cursor, so both get 400.
+    let Query(options) = options.map_err(|_| StatusCode::BAD_REQUEST)?;
+    if let Some(ids) = options.ids {
+        let ids = ids
+            .split(',')
+            .map(|id| id.trim().parse())
+            .collect::<Result<Vec<i32>, _>>()
+            .map_err(|_| StatusCode::BAD_REQUEST)?;
+        if ids.len() > MAX_LOOKUP_IDS {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        let lookup = lookup_todos(&*repository, owner, ids).await?;
+        return Ok((StatusCode::OK, Json(lookup)).into_response());
+    }
+
+    let now = Utc::now();
+    let include_snoozed = options.include_snoozed.unwrap_or(false);
+    let filter = TodoFilter {
+        stale_before: options
+            .stale_days
+            .map(|days| now - Duration::days(days.into())),
+        awake_at: if include_snoozed { None } else { Some(now) },
+        completed: options.completed,
+        label: options.label,
+        due_before: options.due_before,
+        due_after: options.due_after,
+        overdue_at: options.overdue.unwrap_or(false).then_some(now),
+        priority: options.priority,
+        user_id: owner.user_id(),
+        ..TodoFilter::default()
+    };
+    let limit = options
+        .limit
+        .unwrap_or(DEFAULT_PAGE_LIMIT)
+        .min(MAX_PAGE_LIMIT);
+
+    let total = repository
+        .count(&filter)
+        .await
+        .map_err(repository_error_status)?;
+    let headers = Headers([(TOTAL_COUNT_HEADER, total.to_string())]);
+
+    let sort = TodoSort {
+        field: match options.sort {
+            Some(ListSort::Field(field)) => field,
+            _ => SortField::default(),
+        },
+        order: options.order.unwrap_or_default(),
+    };
+    let by_urgency = options.sort == Some(ListSort::Urgency);
+
+    if options.tree.unwrap_or(false) {
+        if options.limit.is_some() || options.offset.is_some() || options.after.is_some() {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        let todos = if by_urgency {
+            all_by_urgency(&*repository, &filter, sort.order, Page::default())
+                .await
+                .map_err(repository_error_status)?
+        } else {
+            repository
+                .all(&filter, sort, Page::default())
+                .await
+                .map_err(repository_error_status)?
+        };
+        return Ok((StatusCode::OK, headers, Json(todo_tree(todos))).into_response());
+    }
+
+    let after = match options.after {
+        Some(after) => after,
+        None => {
+            let page = Page {
+                limit: Some(limit),
+                offset: options.offset.unwrap_or(0),
+            };
+            if by_urgency {
+                let todos = all_by_urgency(&*repository, &filter, sort.order, page)
+                    .await
+                    .map_err(repository_error_status)?;
+                return Ok((StatusCode::OK, headers, Json(todos)).into_response());
+            }
+            let body = repository
+                .all_json(&filter, sort, page)
+                .await
+                .map_err(repository_error_status)?;
+            let json = Headers([(CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())]);
+            return Ok((StatusCode::OK, headers, (json, body)).into_response());
+        }
+    };
+    // Cursors resume the default newest-first order only.
+    if options.offset.is_some() || options.sort.is_some() || options.order.is_some() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let before_id = match after.as_str() {
+        "" => None,
+        cursor => Some(decode_cursor(cursor).ok_or(StatusCode::BAD_REQUEST)?),
+    };
+    let filter = TodoFilter {
+        before_id,
+        ..filter
+    };
+    // One extra todo tells whether another page follows.
+    let page = Page {
+        limit: Some(limit + 1),
+        offset: 0,
+    };
+    let mut todos = repository
+        .all(&filter, TodoSort::default(), page)
+        .await
+        .map_err(repository_error_status)?;
+    let next_cursor = if todos.len() > limit {
+        todos.truncate(limit);
+        todos.last().map(|todo| encode_cursor(todo.id()))
+    } else {
+        None
+    };
+
+    let page = TodoCursorPage { todos, next_cursor };
+    Ok((StatusCode::OK, headers, Json(page)).into_response())
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/todos/lookup",
+    request_body = LookupTodos,
+    responses(
+        (status = 200, description = "The todos found and the ids that were not", body = TodoLookup),
+        (status = 422, description = "Too few or too many ids", body = ValidationErrorBody)
+    ),
+    tag = "todos"
+)]
+pub async fn lookup_todos_by_ids<T: TodoRepository>(
+    ValidatedJson(payload): ValidatedJson<LookupTodos>,
     Extension(repository): Extension<Arc<T>>,
+    owner: Owner,
 ) -> Result<impl IntoResponse, StatusCode> {
-    let todo = repository.all().await.unwrap();
-    Ok((StatusCode::OK, Json(todo)))
+    let lookup = lookup_todos(&*repository, owner, payload.ids).await?;
+    Ok((StatusCode::OK, Json(lookup)))
+}
+
+/// Fetches `ids` in one query, snoozed and completed todos included.
+/// Todos of other users count as missing.
+async fn lookup_todos<T: TodoRepository>(
+    repository: &T,
+    owner: Owner,
+    mut ids: Vec<i32>,
+) -> Result<TodoLookup, StatusCode> {
+    let mut seen = HashSet::new();
+    ids.retain(|id| seen.insert(*id));
+    let filter = TodoFilter {
+        ids: Some(ids.clone()),
+        user_id: owner.user_id(),
+        ..TodoFilter::default()
+    };
+    let mut found: HashMap<i32, Todo> = repository
+        .all(&filter, TodoSort::default(), Page::default())
+        .await
+        .map_err(repository_error_status)?
+        .into_iter()
+        .map(|todo| (todo.id(), todo))
+        .collect();
+
+    let mut lookup = TodoLookup {
+        todos: Vec::with_capacity(found.len()),
+        missing: Vec::new(),
+    };
+    for id in ids {
+        match found.remove(&id) {
+            Some(todo) => lookup.todos.push(todo),
+            None => lookup.missing.push(id),
+        }
+    }
+    Ok(lookup)
+}
+
+/// Nests `todos` under their parents, keeping their order among siblings.
+/// A todo whose parent is not in `todos` becomes a root.
+fn todo_tree(todos: Vec<Todo>) -> Vec<TodoNode> {
+    let ids: HashSet<i32> = todos.iter().map(Todo::id).collect();
+    let mut children: HashMap<i32, Vec<Todo>> = HashMap::new();
+    let mut roots = Vec::new();
+    for todo in todos {
+        match todo.parent_id() {
+            Some(parent_id) if ids.contains(&parent_id) => {
+                children.entry(parent_id).or_default().push(todo)
+            }
+            _ => roots.push(todo),
+        }
+    }
+
+    fn node(todo: Todo, children: &mut HashMap<i32, Vec<Todo>>) -> TodoNode {
+        let subtasks = children.remove(&todo.id()).unwrap_or_default();
+        TodoNode {
+            children: subtasks
+                .into_iter()
+                .map(|subtask| node(subtask, children))
+                .collect(),
+            todo,
+        }
+    }
+    roots
+        .into_iter()
+        .map(|todo| node(todo, &mut children))
+        .collect()
+}
+
+pub async fn all_children<T: TodoRepository>(
+    Path(id): Path<i32>,
+    Extension(repository): Extension<Arc<T>>,
+    owner: Owner,
+) -> Result<impl IntoResponse, StatusCode> {
+    let todo = repository.find(id).await.map_err(repository_error_status)?;
+    if !owner.may_access(&todo) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let filter = TodoFilter {
+        parent_id: Some(id),
+        user_id: owner.user_id(),
+        ..TodoFilter::default()
+    };
+    let todos = repository
+        .all(&filter, TodoSort::default(), Page::default())
+        .await
+        .map_err(repository_error_status)?;
+    Ok((StatusCode::OK, Json(todos)))
+}
+
+/// The unfinished todos that todo `id` waits for.
+pub async fn all_blockers<T: TodoRepository>(
+    Path(id): Path<i32>,
+    Extension(repository): Extension<Arc<T>>,
+    owner: Owner,
+) -> Result<impl IntoResponse, StatusCode> {
+    check_owner(&*repository, owner, id).await?;
+    let mut todos = repository
+        .blockers(id)
+        .await
+        .map_err(repository_error_status)?;
+    todos.retain(|todo| owner.may_access(todo));
+    Ok((StatusCode::OK, Json(todos)))
+}
+
+/// Open todos within `radius` metres of `lat`/`lon`, closest first.
+pub async fn nearby_todos<T: TodoRepository>(
+    Query(nearby): Query<Nearby>,
+    Extension(repository): Extension<Arc<T>>,
+    owner: Owner,
+) -> Result<impl IntoResponse, StatusCode> {
+    nearby.validate().map_err(|_| StatusCode::BAD_REQUEST)?;
+    let mut todos = repository
+        .nearby(&nearby)
+        .await
+        .map_err(repository_error_status)?;
+    todos.retain(|todo| owner.may_access(todo));
+    Ok((StatusCode::OK, Json(todos)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SearchOptions {
+    q: String,
+    limit: Option<usize>,
+    offset: Option<usize>,
 }
 
+/// Todos whose text contains `q`, ignoring case. Completed and snoozed
+/// todos are included.
+pub async fn search_todos<T: TodoRepository>(
+    Query(options): Query<SearchOptions>,
+    Extension(repository): Extension<Arc<T>>,
+    owner: Owner,
+) -> Result<impl IntoResponse, StatusCode> {
+    let query = options.q.trim();
+    if query.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let filter = TodoFilter {
+        text_contains: Some(query.to_string()),
+        user_id: owner.user_id(),
+        ..TodoFilter::default()
+    };
+    let page = Page {
+        limit: Some(
+            options
+                .limit
+                .unwrap_or(DEFAULT_PAGE_LIMIT)
+                .min(MAX_PAGE_LIMIT),
+        ),
+        offset: options.offset.unwrap_or(0),
+    };
+
+    let total = repository
+        .count(&filter)
+        .await
+        .map_err(repository_error_status)?;
+    let todos = repository
+        .all(&filter, TodoSort::default(), page)
+        .await
+        .map_err(repository_error_status)?;
+    let headers = Headers([(TOTAL_COUNT_HEADER, total.to_string())]);
+    Ok((StatusCode::OK, headers, Json(todos)))
+}
+
+/// Days without an update after which an open todo is stale, unless `days`
+/// says otherwise.
+const DEFAULT_STALE_DAYS: u32 = 30;
+
+#[derive(Debug, Deserialize)]
+pub struct StaleOptions {
+    days: Option<u32>,
+}
+
+/// Open todos that have not been updated for `days` days, for grooming the
+/// backlog. Snoozed todos are left out, as they wait on purpose.
+pub async fn stale_todos<T: TodoRepository>(
+    options: Result<Query<StaleOptions>, QueryRejection>,
+    Extension(repository): Extension<Arc<T>>,
+    owner: Owner,
+) -> Result<impl IntoResponse, StatusCode> {
+    let Query(options) = options.map_err(|_| StatusCode::BAD_REQUEST)?;
+    let days = options.days.unwrap_or(DEFAULT_STALE_DAYS);
+    let now = Utc::now();
+    let filter = TodoFilter {
+        stale_before: Some(now - Duration::days(days.into())),
+        awake_at: Some(now),
+        user_id: owner.user_id(),
+        ..TodoFilter::default()
+    };
+    let todos = repository
+        .all(&filter, TodoSort::default(), Page::default())
+        .await
+        .map_err(repository_error_status)?;
+    Ok((StatusCode::OK, Json(todos)))
+}
+
+/// How many todos are stored against how many may be. `limit` is `null`
+/// when there is none.
+#[derive(Debug, Serialize)]
+pub struct LimitUsage {
+    usage: usize,
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProjectUsage {
+    project_id: i32,
+    name: String,
+    #[serde(flatten)]
+    todos: LimitUsage,
+}
+
+/// Pomodoros completed on one day (UTC).
+#[derive(Debug, Serialize)]
+pub struct DailyPomodoros {
+    date: NaiveDate,
+    completed: usize,
+}
+
+/// The caller's usage of the todo limits, overall and per project of theirs,
+/// and the pomodoros they completed per day, oldest day first.
+#[derive(Debug, Serialize)]
+pub struct TodoStats {
+    todos: LimitUsage,
+    projects: Vec<ProjectUsage>,
+    pomodoros: Vec<DailyPomodoros>,
+}
+
+pub async fn todo_stats<T: TodoRepository>(
+    Extension(repository): Extension<Arc<T>>,
+    limits: Option<Extension<TodoLimits>>,
+    owner: Owner,
+) -> Result<impl IntoResponse, StatusCode> {
+    let limits = limits.map(|Extension(limits)| limits).unwrap_or_default();
+    let filter = TodoFilter {
+        user_id: owner.user_id(),
+        ..TodoFilter::default()
+    };
+    let usage = repository
+        .count(&filter)
+        .await
+        .map_err(repository_error_status)?;
+    let mut projects = Vec::new();
+    for project in repository
+        .all_projects(owner.user_id())
+        .await
+        .map_err(repository_error_status)?
+    {
+        let usage = repository
+            .count(&TodoFilter {
+                project_id: Some(project.id()),
+                ..filter.clone()
+            })
+            .await
+            .map_err(repository_error_status)?;
+        projects.push(ProjectUsage {
+            project_id: project.id(),
+            name: project.name().to_string(),
+            todos: LimitUsage {
+                usage,
+                limit: limits.max_project_todos,
+            },
+        });
+    }
+    let mut days = BTreeMap::<NaiveDate, usize>::new();
+    for pomodoro in repository
+        .completed_pomodoros(owner.user_id())
+        .await
+        .map_err(repository_error_status)?
+    {
+        if let Some(finished_at) = pomodoro.finished_at() {
+            *days.entry(finished_at.date_naive()).or_default() += 1;
+        }
+    }
+
+    let stats = TodoStats {
+        todos: LimitUsage {
+            usage,
+            limit: limits.max_todos,
+        },
+        projects,
+        pomodoros: days
+            .into_iter()
+            .map(|(date, completed)| DailyPomodoros { date, completed })
+            .collect(),
+    };
+    Ok((StatusCode::OK, Json(stats)))
+}
+
+/// Open todos untouched for this long show up as stale in the weekly review.
+const REVIEW_STALE_DAYS: i64 = 14;
+
+/// Everything a weekly review goes through, each list newest first.
+#[derive(Debug, Serialize)]
+pub struct WeeklyReview {
+    /// Completed within the last seven days.
+    completed: Vec<Todo>,
+    overdue: Vec<Todo>,
+    /// Open and due within the next seven days.
+    due_next_week: Vec<Todo>,
+    stale: Vec<Todo>,
+    /// Open todos still in the default project.
+    inbox: Vec<Todo>,
+}
+
+pub async fn weekly_review<T: TodoRepository>(
+    Extension(repository): Extension<Arc<T>>,
+    owner: Owner,
+) -> Result<impl IntoResponse, StatusCode> {
+    let now = Utc::now();
+    let week = Duration::days(7);
+    let list = |filter: TodoFilter| {
+        let repository = repository.clone();
+        let filter = TodoFilter {
+            user_id: owner.user_id(),
+            ..filter
+        };
+        async move {
+            repository
+                .all(&filter, TodoSort::default(), Page::default())
+                .await
+                .map_err(repository_error_status)
+        }
+    };
+
+    let review = WeeklyReview {
+        completed: list(TodoFilter {
+            completed: Some(true),
+            updated_after: Some(now - week),
+            ..TodoFilter::default()
+        })
+        .await?,
+        overdue: list(TodoFilter {
+            overdue_at: Some(now),
+            ..TodoFilter::default()
+        })
+        .await?,
+        due_next_week: list(TodoFilter {
+            completed: Some(false),
+            due_after: Some(now),
+            due_before: Some(now + week),
+            ..TodoFilter::default()
+        })
+        .await?,
+        stale: list(TodoFilter {
+            stale_before: Some(now - Duration::days(REVIEW_STALE_DAYS)),
+            ..TodoFilter::default()
+        })
+        .await?,
+        inbox: list(TodoFilter {
+            completed: Some(false),
+            project_id: Some(DEFAULT_PROJECT_ID),
+            ..TodoFilter::default()
+        })
+        .await?,
+    };
+    Ok((StatusCode::OK, Json(review)))
+}
+
+const CURSOR_PREFIX: &str = "todo:";
+
+/// Cursors are the hex of `todo:<id>`, opaque to clients so that the keyset
+/// can change without breaking them.
+fn encode_cursor(id: i32) -> String {
+    hex::encode(format!("{}{}", CURSOR_PREFIX, id))
+}
+
+fn decode_cursor(cursor: &str) -> Option<i32> {
+    let bytes = hex::decode(cursor).ok()?;
+    let text = String::from_utf8(bytes).ok()?;
+    text.strip_prefix(CURSOR_PREFIX)?.parse().ok()
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/v1/todos/{id}",
+    params(("id" = i32, Path, description = "Todo id")),
+    request_body = UpdateTodo,
+    responses(
+        (status = 201, description = "The updated todo", body = Todo),
+        (status = 404, description = "No such todo, label or project"),
+        (status = 409, description = "Completing a todo with open blockers"),
+        (status = 422, description = "Invalid update", body = ValidationErrorBody)
+    ),
+    tag = "todos"
+)]
 pub async fn update_todo<T: TodoRepository>(
     Path(id): Path<i32>,
     ValidatedJson(payload): ValidatedJson<UpdateTodo>,
     Extension(repository): Extension<Arc<T>>,
+    owner: Owner,
+    chat: Option<Extension<ChatWebhook>>,
+    events: Option<Extension<TodoEvents>>,
 ) -> Result<impl IntoResponse, StatusCode> {
+    check_owner(&*repository, owner, id).await?;
+    if payload.completes() && !blockers_resolved(&*repository, owner, id, &payload).await? {
+        return Err(StatusCode::CONFLICT);
+    }
+    let todo = update_and_notify(
+        &*repository,
+        owner,
+        id,
+        payload,
+        chat.as_ref().map(|Extension(chat)| chat),
+        events.as_ref().map(|Extension(events)| events),
+    )
+    .await
+    .map_err(repository_error_status)?;
+    Ok((StatusCode::CREATED, Json(todo)))
+}
+
+/// Whether todo `id` may be completed, judged by the blockers `payload`
+/// gives it or, failing that, the ones it already has. A blocker `owner` may
+/// not access answers 404 rather than telling whether it is done.
+pub(crate) async fn blockers_resolved<T: TodoRepository>(
+    repository: &T,
+    owner: Owner,
+    id: i32,
+    payload: &UpdateTodo,
+) -> Result<bool, StatusCode> {
+    let blocked_by = match payload.blocked_by() {
+        Some(blocked_by) => blocked_by,
+        None => {
+            let blockers = repository
+                .blockers(id)
+                .await
+                .map_err(repository_error_status)?;
+            return Ok(blockers.is_empty());
+        }
+    };
+    for blocker in blocked_by {
+        let blocker = repository
+            .find(*blocker)
+            .await
+            .map_err(repository_error_status)?;
+        if !owner.may_access(&blocker) {
+            return Err(StatusCode::NOT_FOUND);
+        }
+        if !blocker.is_completed() {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Creates a todo for `owner`, then tells the chat webhook and the event
+/// bus. Every API creates todos through here, so they all notify alike and
+/// none can hang a todo off someone else's.
+pub(crate) async fn create_and_notify<T: TodoRepository>(
+    repository: &T,
+    owner: Owner,
+    payload: CreateTodo,
+    chat: Option<&ChatWebhook>,
+    events: Option<&TodoEvents>,
+) -> anyhow::Result<Todo> {
+    for id in payload.linked_ids() {
+        ensure_owner(repository, owner, id).await?;
+    }
+    ensure_project_owner(repository, owner, payload.project_id()).await?;
+    for id in payload.label_ids() {
+        ensure_label_owner(repository, owner, *id).await?;
+    }
+    let todo = repository
+        .create(payload.with_user(owner.user_id()))
+        .await?;
+    if let Some(chat) = chat {
+        chat.send(TodoEvent::Created, &todo);
+    }
+    if let Some(events) = events {
+        events.publish(TodoChange::Created(todo.clone()));
+    }
+    Ok(todo)
+}
+
+/// Updates a todo, then tells the event bus and, if the update completed
+/// it, the chat webhook. Every API updates todos through here, so they all
+/// notify alike and none can block a todo on someone else's. The caller
+/// has checked that `owner` may access todo `id` itself.
+pub(crate) async fn update_and_notify<T: TodoRepository>(
+    repository: &T,
+    owner: Owner,
+    id: i32,
+    payload: UpdateTodo,
+    chat: Option<&ChatWebhook>,
+    events: Option<&TodoEvents>,
+) -> anyhow::Result<Todo> {
+    for blocker in payload.blocked_by().unwrap_or_default() {
+        ensure_owner(repository, owner, *blocker).await?;
+    }
+    if let Some(project_id) = payload.project_id() {
+        ensure_project_owner(repository, owner, project_id).await?;
+    }
+    for id in payload.label_ids().unwrap_or_default() {
+        ensure_label_owner(repository, owner, *id).await?;
+    }
+    // Only a todo that was open before counts as completed now.
+    let chat = match chat {
+        Some(chat) if payload.completes() && chat.forwards(TodoEvent::Completed) => {
+            let before = repository.find(id).await?;
+            (!before.is_completed()).then_some(chat)
+        }
+        _ => None,
+    };
+    let todo = repository.update(id, payload).await?;
+    if let Some(chat) = chat {
+        chat.send(TodoEvent::Completed, &todo);
+    }
+    if let Some(events) = events {
+        events.publish(TodoChange::Updated(todo.clone()));
+    }
+    Ok(todo)
+}
+
+/// Files a todo out of the inbox, setting its project, priority and due
+/// date in a single update.
+pub async fn triage_todo<T: TodoRepository>(
+    Path(id): Path<i32>,
+    ValidatedJson(payload): ValidatedJson<TriageTodo>,
+    Extension(repository): Extension<Arc<T>>,
+    owner: Owner,
+    events: Option<Extension<TodoEvents>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    check_owner(&*repository, owner, id).await?;
+    let payload = UpdateTodo::from(payload);
+    if let Some(project_id) = payload.project_id() {
+        ensure_project_owner(&*repository, owner, project_id)
+            .await
+            .map_err(repository_error_status)?;
+    }
     let todo = repository
         .update(id, payload)
         .await
-        .or(Err(StatusCode::NOT_FOUND))?;
-    Ok((StatusCode::CREATED, Json(todo)))
+        .map_err(repository_error_status)?;
+    if let Some(Extension(events)) = events {
+        events.publish(TodoChange::Updated(todo.clone()));
+    }
+    Ok((StatusCode::OK, Json(todo)))
 }
 
+/// Moves a todo within the manual order, as read back with `?sort=position`.
+pub async fn reorder_todo<T: TodoRepository>(
+    Path(id): Path<i32>,
+    ValidatedJson(payload): ValidatedJson<ReorderTodo>,
+    Extension(repository): Extension<Arc<T>>,
+    owner: Owner,
+    events: Option<Extension<TodoEvents>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    check_owner(&*repository, owner, id).await?;
+    let todo = repository
+        .reorder(id, payload)
+        .await
+        .map_err(repository_error_status)?;
+    if let Some(Extension(events)) = events {
+        events.publish(TodoChange::Updated(todo.clone()));
+    }
+    Ok((StatusCode::OK, Json(todo)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/todos/{id}",
+    params(("id" = i32, Path, description = "Todo id")),
+    responses(
+        (status = 204, description = "Deleted"),
+        (status = 404, description = "No such todo")
+    ),
+    tag = "todos"
+)]
 pub async fn delete_todo<T: TodoRepository>(
     Path(id): Path<i32>,
     Extension(repository): Extension<Arc<T>>,
+    owner: Owner,
+    events: Option<Extension<TodoEvents>>,
+) -> StatusCode {
+    if let Err(status) = check_owner(&*repository, owner, id).await {
+        return status;
+    }
+    if let Err(e) = repository.delete(id).await {
+        return repository_error_status(e);
+    }
+    if let Some(Extension(events)) = events {
+        events.publish(TodoChange::Deleted {
+            id,
+            user_id: owner.user_id(),
+        });
+    }
+    StatusCode::NO_CONTENT
+}
+
+pub async fn snooze_todo<T: TodoRepository>(
+    Path(id): Path<i32>,
+    ValidatedJson(payload): ValidatedJson<SnoozeTodo>,
+    Extension(repository): Extension<Arc<T>>,
+    owner: Owner,
+    events: Option<Extension<TodoEvents>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    check_owner(&*repository, owner, id).await?;
+    let todo = repository
+        .snooze(id, Some(payload.until(Utc::now())))
+        .await
+        .map_err(repository_error_status)?;
+    if let Some(Extension(events)) = events {
+        events.publish(TodoChange::Updated(todo.clone()));
+    }
+    Ok((StatusCode::OK, Json(todo)))
+}
+
+pub async fn unsnooze_todo<T: TodoRepository>(
+    Path(id): Path<i32>,
+    Extension(repository): Extension<Arc<T>>,
+    owner: Owner,
+    events: Option<Extension<TodoEvents>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    check_owner(&*repository, owner, id).await?;
+    let todo = repository
+        .snooze(id, None)
+        .await
+        .map_err(repository_error_status)?;
+    if let Some(Extension(events)) = events {
+        events.publish(TodoChange::Updated(todo.clone()));
+    }
+    Ok((StatusCode::OK, Json(todo)))
+}
+
+pub async fn start_timer<T: TodoRepository>(
+    Path(id): Path<i32>,
+    Extension(repository): Extension<Arc<T>>,
+    owner: Owner,
+) -> Result<impl IntoResponse, StatusCode> {
+    check_owner(&*repository, owner, id).await?;
+    let entry = repository
+        .start_timer(id)
+        .await
+        .map_err(repository_error_status)?;
+    Ok((StatusCode::CREATED, Json(entry)))
+}
+
+pub async fn stop_timer<T: TodoRepository>(
+    Path(id): Path<i32>,
+    Extension(repository): Extension<Arc<T>>,
+    owner: Owner,
+    events: Option<Extension<TodoEvents>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    check_owner(&*repository, owner, id).await?;
+    let entry = repository
+        .stop_timer(id)
+        .await
+        .map_err(repository_error_status)?;
+    publish_time_spent(&*repository, events, id).await;
+    Ok((StatusCode::OK, Json(entry)))
+}
+
+pub async fn all_time_entries<T: TodoRepository>(
+    Path(id): Path<i32>,
+    Extension(repository): Extension<Arc<T>>,
+    owner: Owner,
+) -> Result<impl IntoResponse, StatusCode> {
+    check_owner(&*repository, owner, id).await?;
+    let entries = repository
+        .time_entries(id)
+        .await
+        .map_err(repository_error_status)?;
+    Ok((StatusCode::OK, Json(entries)))
+}
+
+pub async fn start_pomodoro<T: TodoRepository>(
+    Path(id): Path<i32>,
+    Extension(repository): Extension<Arc<T>>,
+    owner: Owner,
+) -> Result<impl IntoResponse, StatusCode> {
+    check_owner(&*repository, owner, id).await?;
+    let pomodoro = repository
+        .start_pomodoro(id)
+        .await
+        .map_err(repository_error_status)?;
+    Ok((StatusCode::CREATED, Json(pomodoro)))
+}
+
+pub async fn all_pomodoros<T: TodoRepository>(
+    Path(id): Path<i32>,
+    Extension(repository): Extension<Arc<T>>,
+    owner: Owner,
+) -> Result<impl IntoResponse, StatusCode> {
+    check_owner(&*repository, owner, id).await?;
+    let pomodoros = repository
+        .pomodoros(id)
+        .await
+        .map_err(repository_error_status)?;
+    Ok((StatusCode::OK, Json(pomodoros)))
+}
+
+pub async fn interrupt_pomodoro<T: TodoRepository>(
+    Path(id): Path<i32>,
+    Extension(repository): Extension<Arc<T>>,
+    owner: Owner,
+) -> Result<impl IntoResponse, StatusCode> {
+    check_pomodoro_owner(&*repository, owner, id).await?;
+    let pomodoro = repository
+        .interrupt_pomodoro(id)
+        .await
+        .map_err(repository_error_status)?;
+    Ok((StatusCode::OK, Json(pomodoro)))
+}
+
+pub async fn finish_pomodoro<T: TodoRepository>(
+    Path(id): Path<i32>,
+    Extension(repository): Extension<Arc<T>>,
+    owner: Owner,
+    events: Option<Extension<TodoEvents>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    check_pomodoro_owner(&*repository, owner, id).await?;
+    let pomodoro = repository
+        .finish_pomodoro(id)
+        .await
+        .map_err(repository_error_status)?;
+    publish_time_spent(&*repository, events, pomodoro.todo_id()).await;
+    Ok((StatusCode::OK, Json(pomodoro)))
+}
+
+/// Answers 404 unless `owner` may access the todo pomodoro `id` runs for.
+async fn check_pomodoro_owner<T: TodoRepository>(
+    repository: &T,
+    owner: Owner,
+    id: i32,
+) -> Result<(), StatusCode> {
+    if owner.user_id().is_none() {
+        return Ok(());
+    }
+    let pomodoro = repository
+        .pomodoro(id)
+        .await
+        .map_err(repository_error_status)?;
+    check_owner(repository, owner, pomodoro.todo_id()).await
+}
+
+/// Stopping a timer or finishing a pomodoro adds to the todo's time spent,
+/// so the todo itself is published as updated.
+async fn publish_time_spent<T: TodoRepository>(
+    repository: &T,
+    events: Option<Extension<TodoEvents>>,
+    id: i32,
+) {
+    if let Some(Extension(events)) = events {
+        match repository.find(id).await {
+            Ok(todo) => events.publish(TodoChange::Updated(todo)),
+            Err(e) => tracing::warn!("fail publish time spent on todo {}: {:#}", id, e),
+        }
+    }
+}
+
+pub(crate) fn repository_error_status(error: anyhow::Error) -> StatusCode {
+    match error.downcast_ref::<RepositoryError>() {
+        Some(RepositoryError::NotFound(_)) => StatusCode::NOT_FOUND,
+        Some(RepositoryError::Conflict(_)) => StatusCode::CONFLICT,
+        Some(RepositoryError::TodoLimitReached { .. })
+        | Some(RepositoryError::ProjectTodoLimitReached { .. }) => StatusCode::FORBIDDEN,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// Whether a create failed only because a `TodoLimits` bound is reached.
+pub(crate) fn is_limit_reached(error: &anyhow::Error) -> bool {
+    matches!(
+        error.downcast_ref::<RepositoryError>(),
+        Some(RepositoryError::TodoLimitReached { .. })
+            | Some(RepositoryError::ProjectTodoLimitReached { .. })
+    )
+}
+
+/// Like `repository_error_status`, but tells which limit a create reached
+/// and how far it is used.
+fn create_error_response(error: anyhow::Error) -> Response {
+    let body = match error.downcast_ref::<RepositoryError>() {
+        Some(RepositoryError::TodoLimitReached { limit, usage }) => json!({
+            "code": ErrorCode::TodoLimitReached.code(),
+            "message": error.to_string(),
+            "limit": limit,
+            "usage": usage,
+        }),
+        Some(RepositoryError::ProjectTodoLimitReached {
+            project_id,
+            limit,
+            usage,
+        }) => json!({
+            "code": ErrorCode::TodoLimitReached.code(),
+            "message": error.to_string(),
+            "project_id": project_id,
+            "limit": limit,
+            "usage": usage,
+        }),
+        _ => return repository_error_status(error).into_response(),
+    };
+    (StatusCode::FORBIDDEN, Json(body)).into_response()
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/labels",
+    request_body = CreateLabel,
+    responses(
+        (status = 201, description = "The created label", body = Label),
+        (status = 422, description = "Invalid label", body = ValidationErrorBody)
+    ),
+    tag = "labels"
+)]
+pub async fn create_label<T: TodoRepository>(
+    ValidatedJson(payload): ValidatedJson<CreateLabel>,
+    Extension(repository): Extension<Arc<T>>,
+    owner: Owner,
+) -> Result<impl IntoResponse, StatusCode> {
+    let label = repository
+        .create_label(payload.with_user(owner.user_id()))
+        .await
+        .map_err(repository_error_status)?;
+    Ok((StatusCode::CREATED, Json(label)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/labels/{id}",
+    params(("id" = i32, Path, description = "Label id")),
+    responses(
+        (status = 200, description = "The label", body = Label),
+        (status = 404, description = "No such label")
+    ),
+    tag = "labels"
+)]
+pub async fn find_label<T: TodoRepository>(
+    Path(id): Path<i32>,
+    Extension(repository): Extension<Arc<T>>,
+    owner: Owner,
+) -> Result<impl IntoResponse, StatusCode> {
+    let label = repository
+        .find_label(id)
+        .await
+        .map_err(repository_error_status)?;
+    if !owner.may_access_label(&label) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    Ok((StatusCode::OK, Json(label)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/labels",
+    responses(
+        (status = 200, description = "The caller's labels", body = [Label])
+    ),
+    tag = "labels"
+)]
+pub async fn all_labels<T: TodoRepository>(
+    Extension(repository): Extension<Arc<T>>,
+    owner: Owner,
+) -> Result<impl IntoResponse, StatusCode> {
+    let labels = repository
+        .all_labels(owner.user_id())
+        .await
+        .map_err(repository_error_status)?;
+    Ok((StatusCode::OK, Json(labels)))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/v1/labels/{id}",
+    params(("id" = i32, Path, description = "Label id")),
+    request_body = UpdateLabel,
+    responses(
+        (status = 200, description = "The updated label", body = Label),
+        (status = 404, description = "No such label"),
+        (status = 422, description = "Invalid update", body = ValidationErrorBody)
+    ),
+    tag = "labels"
+)]
+pub async fn update_label<T: TodoRepository>(
+    Path(id): Path<i32>,
+    ValidatedJson(payload): ValidatedJson<UpdateLabel>,
+    Extension(repository): Extension<Arc<T>>,
+    owner: Owner,
+) -> Result<impl IntoResponse, StatusCode> {
+    ensure_label_owner(&*repository, owner, id)
+        .await
+        .map_err(repository_error_status)?;
+    let label = repository
+        .update_label(id, payload)
+        .await
+        .map_err(repository_error_status)?;
+    Ok((StatusCode::OK, Json(label)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/labels/{id}",
+    params(("id" = i32, Path, description = "Label id")),
+    responses(
+        (status = 204, description = "Deleted"),
+        (status = 404, description = "No such label")
+    ),
+    tag = "labels"
+)]
+pub async fn delete_label<T: TodoRepository>(
+    Path(id): Path<i32>,
+    Extension(repository): Extension<Arc<T>>,
+    owner: Owner,
+) -> StatusCode {
+    if let Err(e) = ensure_label_owner(&*repository, owner, id).await {
+        return repository_error_status(e);
+    }
+    repository
+        .delete_label(id)
+        .await
+        .map(|_| StatusCode::NO_CONTENT)
+        .unwrap_or_else(repository_error_status)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/projects",
+    request_body = CreateProject,
+    responses(
+        (status = 201, description = "The created project", body = Project),
+        (status = 422, description = "Invalid project", body = ValidationErrorBody)
+    ),
+    tag = "projects"
+)]
+pub async fn create_project<T: TodoRepository>(
+    ValidatedJson(payload): ValidatedJson<CreateProject>,
+    Extension(repository): Extension<Arc<T>>,
+    owner: Owner,
+) -> Result<impl IntoResponse, StatusCode> {
+    let project = repository
+        .create_project(payload.with_user(owner.user_id()))
+        .await
+        .map_err(repository_error_status)?;
+    Ok((StatusCode::CREATED, Json(project)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/projects/{id}",
+    params(("id" = i32, Path, description = "Project id")),
+    responses(
+        (status = 200, description = "The project", body = Project),
+        (status = 404, description = "No such project")
+    ),
+    tag = "projects"
+)]
+pub async fn find_project<T: TodoRepository>(
+    Path(id): Path<i32>,
+    Extension(repository): Extension<Arc<T>>,
+    owner: Owner,
+) -> Result<impl IntoResponse, StatusCode> {
+    let project = repository
+        .find_project(id)
+        .await
+        .map_err(repository_error_status)?;
+    if !owner.may_use_project(&project) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    Ok((StatusCode::OK, Json(project)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/projects",
+    responses(
+        (status = 200, description = "The caller's projects and the default one", body = [Project])
+    ),
+    tag = "projects"
+)]
+pub async fn all_projects<T: TodoRepository>(
+    Extension(repository): Extension<Arc<T>>,
+    owner: Owner,
+) -> Result<impl IntoResponse, StatusCode> {
+    let projects = repository
+        .all_projects(owner.user_id())
+        .await
+        .map_err(repository_error_status)?;
+    Ok((StatusCode::OK, Json(projects)))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/v1/projects/{id}",
+    params(("id" = i32, Path, description = "Project id")),
+    request_body = UpdateProject,
+    responses(
+        (status = 200, description = "The updated project", body = Project),
+        (status = 403, description = "The default project is shared"),
+        (status = 404, description = "No such project"),
+        (status = 422, description = "Invalid update", body = ValidationErrorBody)
+    ),
+    tag = "projects"
+)]
+pub async fn update_project<T: TodoRepository>(
+    Path(id): Path<i32>,
+    ValidatedJson(payload): ValidatedJson<UpdateProject>,
+    Extension(repository): Extension<Arc<T>>,
+    owner: Owner,
+) -> Result<impl IntoResponse, StatusCode> {
+    check_project_changeable(&*repository, owner, id).await?;
+    let project = repository
+        .update_project(id, payload)
+        .await
+        .map_err(repository_error_status)?;
+    Ok((StatusCode::OK, Json(project)))
+}
+
+/// Deletes the project along with its todos.
+#[utoipa::path(
+    delete,
+    path = "/api/v1/projects/{id}",
+    params(("id" = i32, Path, description = "Project id")),
+    responses(
+        (status = 204, description = "Deleted"),
+        (status = 403, description = "The default project is shared"),
+        (status = 409, description = "The default project can not be deleted, or the project holds todos of other users"),
+        (status = 404, description = "No such project")
+    ),
+    tag = "projects"
+)]
+pub async fn delete_project<T: TodoRepository>(
+    Path(id): Path<i32>,
+    Extension(repository): Extension<Arc<T>>,
+    owner: Owner,
+) -> StatusCode {
+    if let Err(status) = check_project_changeable(&*repository, owner, id).await {
+        return status;
+    }
+    repository
+        .delete_project(id)
+        .await
+        .map(|_| StatusCode::NO_CONTENT)
+        .unwrap_or_else(repository_error_status)
+}
+
+/// Answers 404 unless `owner` may see project `id`, and 403 unless they may
+/// also change it; see `Owner::may_change_project`.
+async fn check_project_changeable<T: TodoRepository>(
+    repository: &T,
+    owner: Owner,
+    id: i32,
+) -> Result<(), StatusCode> {
+    let project = repository
+        .find_project(id)
+        .await
+        .map_err(repository_error_status)?;
+    if !owner.may_use_project(&project) {
+        Err(StatusCode::NOT_FOUND)
+    } else if !owner.may_change_project(&project) {
+        Err(StatusCode::FORBIDDEN)
+    } else {
+        Ok(())
+    }
+}
+
+/// Open todos still waiting in the default project, newest first.
+pub async fn inbox<T: TodoRepository>(
+    Extension(repository): Extension<Arc<T>>,
+    owner: Owner,
+) -> Result<impl IntoResponse, StatusCode> {
+    let filter = TodoFilter {
+        completed: Some(false),
+        project_id: Some(DEFAULT_PROJECT_ID),
+        user_id: owner.user_id(),
+        ..TodoFilter::default()
+    };
+    let todos = repository
+        .all(&filter, TodoSort::default(), Page::default())
+        .await
+        .map_err(repository_error_status)?;
+    Ok((StatusCode::OK, Json(todos)))
+}
+
+pub async fn all_project_todos<T: TodoRepository>(
+    Path(id): Path<i32>,
+    Extension(repository): Extension<Arc<T>>,
+    owner: Owner,
+) -> Result<impl IntoResponse, StatusCode> {
+    ensure_project_owner(&*repository, owner, id)
+        .await
+        .map_err(repository_error_status)?;
+    let filter = TodoFilter {
+        project_id: Some(id),
+        user_id: owner.user_id(),
+        ..TodoFilter::default()
+    };
+    let todos = repository
+        .all(&filter, TodoSort::default(), Page::default())
+        .await
+        .map_err(repository_error_status)?;
+    Ok((StatusCode::OK, Json(todos)))
+}
+
+pub async fn create_share_link<T: TodoRepository>(
+    Path(id): Path<i32>,
+    ValidatedJson(payload): ValidatedJson<CreateShareLink>,
+    Extension(repository): Extension<Arc<T>>,
+    owner: Owner,
+) -> Result<impl IntoResponse, StatusCode> {
+    check_owner(&*repository, owner, id).await?;
+    let link = ShareLink::new(id, payload.expires_at(Utc::now()));
+    let link = repository
+        .create_share_link(link)
+        .await
+        .map_err(repository_error_status)?;
+    Ok((StatusCode::CREATED, Json(link)))
+}
+
+pub async fn delete_share_link<T: TodoRepository>(
+    Path((id, token)): Path<(i32, String)>,
+    Extension(repository): Extension<Arc<T>>,
+    owner: Owner,
 ) -> StatusCode {
+    if let Err(status) = check_owner(&*repository, owner, id).await {
+        return status;
+    }
     repository
-        .delete(id)
+        .delete_share_link(id, &token)
         .await
         .map(|_| StatusCode::NO_CONTENT)
-        .unwrap_or(StatusCode::NOT_FOUND)
+        .unwrap_or_else(repository_error_status)
+}
+
+pub async fn find_shared_todo<T: TodoRepository>(
+    Path(token): Path<String>,
+    Extension(repository): Extension<Arc<T>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let todo = shared_todo(&*repository, &token).await?;
+    Ok((StatusCode::OK, Json(todo)))
+}
+
+/// A standalone HTML page for an `<iframe>`. It loads nothing else and runs
+/// no script, and any site may frame it.
+pub async fn embed_shared_todo<T: TodoRepository>(
+    Path(token): Path<String>,
+    Extension(repository): Extension<Arc<T>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let todo = shared_todo(&*repository, &token).await?;
+    let headers = Headers([(
+        "content-security-policy",
+        "default-src 'none'; style-src 'unsafe-inline'; frame-ancestors *",
+    )]);
+    Ok((StatusCode::OK, headers, Html(embed_page(&[todo]))))
+}
+
+/// Smallest edge of a QR code image in pixels, so it scans from a screen
+/// across the room.
+const QR_MIN_PIXELS: u32 = 256;
+
+/// Where share links are published. QR codes never point at the request's
+/// Host header, which a client can set to anything.
+#[derive(Debug, Clone)]
+pub struct ShareConfig {
+    public_url: String,
+}
+
+impl ShareConfig {
+    pub fn new(public_url: String) -> Self {
+        Self { public_url }
+    }
+}
+
+/// A PNG QR code of the link to the embed page, for opening a shared todo on
+/// a phone.
+pub async fn shared_todo_qr<T: TodoRepository>(
+    Path(token): Path<String>,
+    Extension(repository): Extension<Arc<T>>,
+    config: Option<Extension<ShareConfig>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let Extension(config) = config.ok_or(StatusCode::NOT_FOUND)?;
+    shared_todo(&*repository, &token).await?;
+    let url = format!(
+        "{}/shared/{}/embed",
+        config.public_url.trim_end_matches('/'),
+        token
+    );
+
+    let code = QrCode::new(url.as_bytes()).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let image = code
+        .render::<Luma<u8>>()
+        .min_dimensions(QR_MIN_PIXELS, QR_MIN_PIXELS)
+        .build();
+    let mut png = Vec::new();
+    DynamicImage::ImageLuma8(image)
+        .write_to(&mut png, ImageOutputFormat::Png)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let headers = Headers([("content-type", "image/png"), ("cache-control", "no-store")]);
+    Ok((StatusCode::OK, headers, png))
+}
+
+/// The todo behind a share link. Unknown, revoked and expired links all
+/// answer 404, so a token can not be probed for having existed.
+async fn shared_todo<T: TodoRepository>(repository: &T, token: &str) -> Result<Todo, StatusCode> {
+    let link = repository
+        .find_share_link(token)
+        .await
+        .map_err(repository_error_status)?
+        .filter(|link| !link.is_expired(Utc::now()))
+        .ok_or(StatusCode::NOT_FOUND)?;
+    repository
+        .find(link.todo_id())
+        .await
+        .map_err(repository_error_status)
+}
+
+fn embed_page(todos: &[Todo]) -> String {
+    let items: String = todos
+        .iter()
+        .map(|todo| {
+            let (class, mark) = if todo.is_completed() {
+                ("done", "&#x2611;")
+            } else {
+                ("open", "&#x2610;")
+            };
+            format!(
+                r#"<li class="{}">{} {}</li>"#,
+                class,
+                mark,
+                escape_html(todo.text())
+            )
+        })
+        .collect();
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Todos</title>
+<style>
+body {{ margin: 0; padding: 8px; font-family: sans-serif; }}
+ul {{ margin: 0; padding: 0; list-style: none; }}
+.done {{ color: #888; text-decoration: line-through; }}
+</style>
+</head>
+<body>
+<ul>{}</ul>
+</body>
+</html>
+"#,
+        items
+    )
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
 }
@@ -0,0 +1,121 @@
+use crate::auth::Owner;
+use crate::chat::ChatWebhook;
+use crate::events::TodoEvents;
+use crate::handlers::{create_and_notify, is_limit_reached, repository_error_status};
+use crate::repositories::{CreateTodo, TodoLimits, TodoRepository};
+use axum::{body::Bytes, extract::Extension, http::StatusCode};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+use std::sync::Arc;
+use validator::Validate;
+
+/// Requests signed further from now than this are rejected as replays.
+const MAX_REQUEST_AGE_SECS: i64 = 60 * 5;
+
+/// Enables the inbound email route. Without it the route answers 404.
+#[derive(Debug, Clone)]
+pub struct InboundEmailConfig {
+    signing_key: String,
+    /// Only emails sent to this address become todos.
+    address: String,
+    /// Owner of the todos, so they show up for that user once auth is on.
+    user_id: Option<i32>,
+}
+
+impl InboundEmailConfig {
+    pub fn new(signing_key: String, address: String) -> Self {
+        Self {
+            signing_key,
+            address,
+            user_id: None,
+        }
+    }
+
+    pub fn with_user(self, user_id: i32) -> Self {
+        Self {
+            user_id: Some(user_id),
+            ..self
+        }
+    }
+}
+
+/// The fields of a Mailgun route's form-encoded post we use; the body,
+/// headers and attachments are ignored.
+#[derive(Debug, Deserialize)]
+struct InboundEmail {
+    recipient: String,
+    #[serde(default)]
+    subject: String,
+    timestamp: String,
+    token: String,
+    signature: String,
+}
+
+/// Turns an email forwarded by a Mailgun route into a todo whose text is
+/// the subject.
+///
+/// Mailgun retries any answer but 200 and 406, so emails that can never
+/// become a todo are answered with 406.
+pub async fn inbound_email<T: TodoRepository>(
+    Extension(repository): Extension<Arc<T>>,
+    config: Option<Extension<InboundEmailConfig>>,
+    limits: Option<Extension<TodoLimits>>,
+    chat: Option<Extension<ChatWebhook>>,
+    events: Option<Extension<TodoEvents>>,
+    body: Bytes,
+) -> Result<StatusCode, StatusCode> {
+    let Extension(config) = config.ok_or(StatusCode::NOT_FOUND)?;
+    let email: InboundEmail =
+        serde_urlencoded::from_bytes(&body).map_err(|_| StatusCode::BAD_REQUEST)?;
+    if !verify_signature(&config.signing_key, &email, Utc::now().timestamp()) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    if !email
+        .recipient
+        .split(',')
+        .any(|recipient| recipient.trim().eq_ignore_ascii_case(&config.address))
+    {
+        return Ok(StatusCode::NOT_ACCEPTABLE);
+    }
+
+    let payload = CreateTodo::new(email.subject.trim().to_string()).with_user(config.user_id);
+    if payload.validate().is_err() {
+        return Ok(StatusCode::NOT_ACCEPTABLE);
+    }
+    let limits = limits.map(|Extension(limits)| limits).unwrap_or_default();
+    match create_and_notify(
+        &*repository,
+        Owner::default(),
+        payload.with_limits(limits),
+        chat.as_ref().map(|Extension(chat)| chat),
+        events.as_ref().map(|Extension(events)| events),
+    )
+    .await
+    {
+        Ok(_) => Ok(StatusCode::OK),
+        Err(e) if is_limit_reached(&e) => Ok(StatusCode::NOT_ACCEPTABLE),
+        Err(e) => Err(repository_error_status(e)),
+    }
+}
+
+/// Checks Mailgun's webhook signature: a hex HMAC-SHA256 over the timestamp
+/// followed by the token, keyed with the account's webhook signing key.
+fn verify_signature(key: &str, email: &InboundEmail, now: i64) -> bool {
+    let fresh = email
+        .timestamp
+        .parse::<i64>()
+        .map(|timestamp| (now - timestamp).abs() <= MAX_REQUEST_AGE_SECS)
+        .unwrap_or(false);
+    let signature = match hex::decode(&email.signature) {
+        Ok(signature) => signature,
+        Err(_) => return false,
+    };
+
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(key.as_bytes()).expect("HMAC takes keys of any size");
+    mac.update(email.timestamp.as_bytes());
+    mac.update(email.token.as_bytes());
+    fresh && mac.verify_slice(&signature).is_ok()
+}
@@ -0,0 +1,349 @@
+use axum::{
+    body::{self, Body, Full},
+    http::{header, HeaderMap, Request, StatusCode, Uri},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, SecondsFormat, TimeZone, Utc};
+use serde_json::{Map, Value};
+use std::{
+    future::Future,
+    pin::Pin,
+    str::FromStr,
+    task::{Context, Poll},
+};
+use thiserror::Error;
+use tower::{Layer, Service};
+
+/// Paths whose callers expect their own format, as with the envelope.
+const PASSTHROUGH_PREFIXES: [&str; 4] = ["/integrations/", "/simple/", "/graphql", "/openapi.json"];
+/// Fields holding a timestamp end in one of these, once in snake case.
+const TIMESTAMP_SUFFIXES: [&str; 5] = ["_at", "_date", "until", "_before", "_after"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FieldCasing {
+    #[default]
+    Snake,
+    Camel,
+}
+
+#[derive(Debug, Error)]
+#[error("unknown field casing: [{0}], expected one of snake, camel")]
+pub struct ParseFieldCasingError(String);
+
+impl FromStr for FieldCasing {
+    type Err = ParseFieldCasingError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "snake" => Ok(FieldCasing::Snake),
+            "camel" => Ok(FieldCasing::Camel),
+            _ => Err(ParseFieldCasingError(s.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimestampFormat {
+    #[default]
+    Rfc3339,
+    EpochMillis,
+}
+
+#[derive(Debug, Error)]
+#[error("unknown timestamp format: [{0}], expected one of rfc3339, epoch_millis")]
+pub struct ParseTimestampFormatError(String);
+
+impl FromStr for TimestampFormat {
+    type Err = ParseTimestampFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rfc3339" => Ok(TimestampFormat::Rfc3339),
+            "epoch_millis" => Ok(TimestampFormat::EpochMillis),
+            _ => Err(ParseTimestampFormatError(s.to_string())),
+        }
+    }
+}
+
+/// How field names and timestamps are written on the wire. The handlers
+/// always speak snake case and RFC 3339.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct JsonFormat {
+    pub casing: FieldCasing,
+    pub timestamps: TimestampFormat,
+}
+
+impl JsonFormat {
+    fn is_native(&self) -> bool {
+        *self == JsonFormat::default()
+    }
+
+    fn applies_to(&self, path: &str) -> bool {
+        !self.is_native()
+            && !PASSTHROUGH_PREFIXES
+                .iter()
+                .any(|prefix| path.starts_with(prefix))
+    }
+
+    /// The handlers' name for a field sent by the client.
+    fn native_key(&self, key: &str) -> String {
+        match self.casing {
+            FieldCasing::Snake => key.to_string(),
+            FieldCasing::Camel => to_snake_case(key),
+        }
+    }
+
+    /// The client's name for a field the handlers wrote.
+    fn wire_key(&self, key: &str) -> String {
+        match self.casing {
+            FieldCasing::Snake => key.to_string(),
+            FieldCasing::Camel => to_camel_case(key),
+        }
+    }
+
+    fn native_timestamp(&self, value: Value) -> Value {
+        match (self.timestamps, &value) {
+            (TimestampFormat::EpochMillis, Value::Number(millis)) => millis
+                .as_i64()
+                .and_then(|millis| Utc.timestamp_millis_opt(millis).single())
+                .map(|time| Value::String(time.to_rfc3339_opts(SecondsFormat::AutoSi, true)))
+                .unwrap_or(value),
+            _ => value,
+        }
+    }
+
+    fn wire_timestamp(&self, value: Value) -> Value {
+        match (self.timestamps, &value) {
+            (TimestampFormat::EpochMillis, Value::String(time)) => {
+                DateTime::parse_from_rfc3339(time)
+                    .map(|time| Value::from(time.timestamp_millis()))
+                    .unwrap_or(value)
+            }
+            _ => value,
+        }
+    }
+
+    /// Rewrites a request body into the handlers' format.
+    fn to_native(self, value: Value) -> Value {
+        self.rewrite(value, &|key| self.native_key(key), &|value| {
+            self.native_timestamp(value)
+        })
+    }
+
+    /// Rewrites a response body into the client's format.
+    fn to_wire(self, value: Value) -> Value {
+        self.rewrite(value, &|key| self.wire_key(key), &|value| {
+            self.wire_timestamp(value)
+        })
+    }
+
+    fn rewrite(
+        &self,
+        value: Value,
+        key: &dyn Fn(&str) -> String,
+        timestamp: &dyn Fn(Value) -> Value,
+    ) -> Value {
+        match value {
+            Value::Array(values) => Value::Array(
+                values
+                    .into_iter()
+                    .map(|value| self.rewrite(value, key, timestamp))
+                    .collect(),
+            ),
+            Value::Object(fields) => Value::Object(
+                fields
+                    .into_iter()
+                    .map(|(name, value)| {
+                        let value = if is_timestamp_field(&to_snake_case(&name)) {
+                            timestamp(value)
+                        } else {
+                            self.rewrite(value, key, timestamp)
+                        };
+                        (key(&name), value)
+                    })
+                    .collect::<Map<_, _>>(),
+            ),
+            value => value,
+        }
+    }
+
+    /// Query parameters are renamed and converted like body fields.
+    fn native_uri(&self, uri: &Uri) -> Option<Uri> {
+        let query = uri.query()?;
+        let pairs: Vec<(String, String)> = serde_urlencoded::from_str(query).ok()?;
+        let pairs: Vec<(String, String)> = pairs
+            .into_iter()
+            .map(|(name, value)| {
+                let name = self.native_key(&name);
+                let value = match value.parse::<i64>() {
+                    Ok(millis) if is_timestamp_field(&name) => {
+                        match self.native_timestamp(Value::from(millis)) {
+                            Value::String(time) => time,
+                            _ => value,
+                        }
+                    }
+                    _ => value,
+                };
+                (name, value)
+            })
+            .collect();
+        let query = serde_urlencoded::to_string(pairs).ok()?;
+
+        let mut parts = uri.clone().into_parts();
+        parts.path_and_query = Some(format!("{}?{}", uri.path(), query).parse().ok()?);
+        Uri::from_parts(parts).ok()
+    }
+}
+
+fn is_timestamp_field(name: &str) -> bool {
+    TIMESTAMP_SUFFIXES
+        .iter()
+        .any(|suffix| name.ends_with(suffix))
+}
+
+fn to_camel_case(name: &str) -> String {
+    let mut camel = String::with_capacity(name.len());
+    let mut upper = false;
+    for c in name.chars() {
+        if c == '_' && !camel.is_empty() {
+            upper = true;
+        } else if upper {
+            camel.extend(c.to_uppercase());
+            upper = false;
+        } else {
+            camel.push(c);
+        }
+    }
+    camel
+}
+
+fn to_snake_case(name: &str) -> String {
+    let mut snake = String::with_capacity(name.len() + 4);
+    for c in name.chars() {
+        if c.is_uppercase() {
+            if !snake.is_empty() {
+                snake.push('_');
+            }
+            snake.extend(c.to_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+fn is_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.starts_with(mime::APPLICATION_JSON.as_ref()))
+        .unwrap_or(false)
+}
+
+/// Translates JSON bodies and query parameters between the handlers'
+/// snake case and RFC 3339 and the format the client asked for.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonFormatLayer {
+    format: JsonFormat,
+}
+
+impl JsonFormatLayer {
+    pub fn new(format: JsonFormat) -> Self {
+        Self { format }
+    }
+}
+
+impl<S> Layer<S> for JsonFormatLayer {
+    type Service = JsonFormatService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        JsonFormatService {
+            inner,
+            format: self.format,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct JsonFormatService<S> {
+    inner: S,
+    format: JsonFormat,
+}
+
+type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send + 'static>>;
+
+impl<S> Service<Request<Body>> for JsonFormatService<S>
+where
+    S: Service<Request<Body>, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = BoxFuture<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<Body>) -> Self::Future {
+        let format = self.format;
+        if !format.applies_to(req.uri().path()) {
+            return Box::pin(self.inner.call(req));
+        }
+
+        // The clone may not be ready, so keep the one that is.
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        Box::pin(async move {
+            let req = match translate_request(format, req).await {
+                Ok(req) => req,
+                Err(res) => return Ok(res),
+            };
+            let res = inner.call(req).await?;
+            Ok(translate_response(format, res).await)
+        })
+    }
+}
+
+async fn translate_request(
+    format: JsonFormat,
+    req: Request<Body>,
+) -> Result<Request<Body>, Response> {
+    let (mut parts, body) = req.into_parts();
+    if let Some(uri) = format.native_uri(&parts.uri) {
+        parts.uri = uri;
+    }
+    if !is_json(&parts.headers) {
+        return Ok(Request::from_parts(parts, body));
+    }
+
+    let bytes = hyper::body::to_bytes(body)
+        .await
+        .map_err(|_| StatusCode::BAD_REQUEST.into_response())?;
+    let bytes = match serde_json::from_slice::<Value>(&bytes) {
+        Ok(value) => serde_json::to_vec(&format.to_native(value)).unwrap_or_default(),
+        // Left as it is for the handler to reject.
+        Err(_) => bytes.to_vec(),
+    };
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Ok(Request::from_parts(parts, Body::from(bytes)))
+}
+
+async fn translate_response(format: JsonFormat, res: Response) -> Response {
+    if !is_json(res.headers()) {
+        return res;
+    }
+
+    let (mut parts, body) = res.into_parts();
+    let bytes = match hyper::body::to_bytes(body).await {
+        Ok(bytes) => bytes,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    let bytes = match serde_json::from_slice::<Value>(&bytes) {
+        Ok(value) => serde_json::to_vec(&format.to_wire(value)).unwrap_or_default(),
+        Err(_) => bytes.to_vec(),
+    };
+
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, body::boxed(Full::from(bytes)))
+}
@@ -0,0 +1,196 @@
+use crate::auth::Owner;
+use crate::chat::ChatWebhook;
+use crate::events::TodoEvents;
+use crate::handlers::{
+    blockers_resolved, create_and_notify, ensure_owner, is_limit_reached, repository_error_status,
+    update_and_notify, ValidationErrorBody,
+};
+use crate::repositories::{CreateTodo, Todo, TodoLimits, TodoRepository, UpdateTodo};
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Extension,
+    },
+    http::StatusCode,
+    response::Response,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+use validator::Validate;
+
+/// A command sent over the socket. `id` is echoed in the reply so that the
+/// client can match the two up.
+#[derive(Debug, Deserialize)]
+struct SyncRequest {
+    id: Option<Value>,
+    #[serde(flatten)]
+    command: SyncCommand,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "command", rename_all = "lowercase")]
+enum SyncCommand {
+    Create { todo: CreateTodo },
+    Update { todo_id: i32, todo: UpdateTodo },
+}
+
+type CommandResult = Result<(StatusCode, Todo), (StatusCode, Value)>;
+
+/// Upgrades to a WebSocket that pushes changes to the caller's todos, as the
+/// event stream does, and takes `create` and `update` commands.
+///
+/// Messages from the server all carry an `event`: a change such as
+/// `todo.created`, a `reply` to a command, or `lagged` when the client read
+/// too slowly and missed changes, after which it should fetch afresh.
+pub async fn live_sync<T: TodoRepository>(
+    ws: WebSocketUpgrade,
+    Extension(repository): Extension<Arc<T>>,
+    owner: Owner,
+    events: Option<Extension<TodoEvents>>,
+    limits: Option<Extension<TodoLimits>>,
+    chat: Option<Extension<ChatWebhook>>,
+) -> Result<Response, StatusCode> {
+    let Extension(events) = events.ok_or(StatusCode::NOT_FOUND)?;
+    let session = Session {
+        repository,
+        owner,
+        events,
+        limits: limits.map(|Extension(limits)| limits).unwrap_or_default(),
+        chat: chat.map(|Extension(chat)| chat),
+    };
+    Ok(ws.on_upgrade(move |socket| sync(socket, session)))
+}
+
+/// What the commands of one connection run against.
+struct Session<T> {
+    repository: Arc<T>,
+    owner: Owner,
+    events: TodoEvents,
+    limits: TodoLimits,
+    chat: Option<ChatWebhook>,
+}
+
+/// Handles one message at a time, so a client that does not read its
+/// socket stalls only its own commands, while changes queue up in its
+/// bounded subscription until it lags.
+async fn sync<T: TodoRepository>(mut socket: WebSocket, session: Session<T>) {
+    let mut changes = session.events.subscribe();
+    loop {
+        let message = tokio::select! {
+            change = changes.recv() => match change {
+                Ok(change) if session.owner.may_see(&change) => change.payload(),
+                Ok(_) => continue,
+                Err(RecvError::Lagged(missed)) => json!({ "event": "lagged", "missed": missed }),
+                Err(RecvError::Closed) => break,
+            },
+            message = socket.recv() => match message {
+                Some(Ok(Message::Text(text))) => run_command(&session, &text).await,
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => continue,
+            },
+        };
+        if socket
+            .send(Message::Text(message.to_string()))
+            .await
+            .is_err()
+        {
+            break;
+        }
+    }
+}
+
+async fn run_command<T: TodoRepository>(session: &Session<T>, text: &str) -> Value {
+    let request: SyncRequest = match serde_json::from_str(text) {
+        Ok(request) => request,
+        Err(e) => {
+            let message = format!("Json parse error: [{}]", e);
+            return json!({
+                "event": "reply",
+                "id": null,
+                "status": StatusCode::BAD_REQUEST.as_u16(),
+                "error": { "message": message },
+            });
+        }
+    };
+    let result = match request.command {
+        SyncCommand::Create { todo } => create(session, todo).await,
+        SyncCommand::Update { todo_id, todo } => update(session, todo_id, todo).await,
+    };
+    match result {
+        Ok((status, todo)) => json!({
+            "event": "reply",
+            "id": request.id,
+            "status": status.as_u16(),
+            "todo": todo,
+        }),
+        Err((status, error)) => json!({
+            "event": "reply",
+            "id": request.id,
+            "status": status.as_u16(),
+            "error": error,
+        }),
+    }
+}
+
+fn failure(status: StatusCode) -> (StatusCode, Value) {
+    let message = status.canonical_reason().unwrap_or_default();
+    (status, json!({ "message": message }))
+}
+
+async fn create<T: TodoRepository>(session: &Session<T>, payload: CreateTodo) -> CommandResult {
+    if let Err(errors) = payload.validate() {
+        let body = json!(ValidationErrorBody::from(errors));
+        return Err((StatusCode::UNPROCESSABLE_ENTITY, body));
+    }
+    let todo = match create_and_notify(
+        &*session.repository,
+        session.owner,
+        payload.with_limits(session.limits),
+        session.chat.as_ref(),
+        Some(&session.events),
+    )
+    .await
+    {
+        Ok(todo) => todo,
+        Err(e) if is_limit_reached(&e) => {
+            return Err((StatusCode::FORBIDDEN, json!({ "message": e.to_string() })))
+        }
+        Err(e) => return Err(failure(repository_error_status(e))),
+    };
+    Ok((StatusCode::CREATED, todo))
+}
+
+async fn update<T: TodoRepository>(
+    session: &Session<T>,
+    id: i32,
+    payload: UpdateTodo,
+) -> CommandResult {
+    if let Err(errors) = payload.validate() {
+        let body = json!(ValidationErrorBody::from(errors));
+        return Err((StatusCode::UNPROCESSABLE_ENTITY, body));
+    }
+    ensure_owner(&*session.repository, session.owner, id)
+        .await
+        .map_err(|e| failure(repository_error_status(e)))?;
+    if payload.completes()
+        && !blockers_resolved(&*session.repository, session.owner, id, &payload)
+            .await
+            .map_err(failure)?
+    {
+        return Err(failure(StatusCode::CONFLICT));
+    }
+
+    let todo = update_and_notify(
+        &*session.repository,
+        session.owner,
+        id,
+        payload,
+        session.chat.as_ref(),
+        Some(&session.events),
+    )
+    .await
+    .map_err(|e| failure(repository_error_status(e)))?;
+    Ok((StatusCode::OK, todo))
+}
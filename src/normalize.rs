@@ -0,0 +1,122 @@
+use axum::{
+    http::{Request, Uri},
+    response::{IntoResponse, Redirect, Response},
+};
+use std::{
+    future::Future,
+    pin::Pin,
+    str::FromStr,
+    task::{Context, Poll},
+};
+use thiserror::Error;
+use tower::{Layer, Service};
+
+/// How a request whose path is not in canonical form is handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NormalizeMode {
+    /// Route the request as if the canonical path had been sent.
+    #[default]
+    Rewrite,
+    /// Answer with `308 Permanent Redirect` to the canonical path.
+    Redirect,
+}
+
+#[derive(Debug, Error)]
+#[error("unknown path normalization: [{0}], expected one of rewrite, redirect")]
+pub struct ParseNormalizeModeError(String);
+
+impl FromStr for NormalizeMode {
+    type Err = ParseNormalizeModeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rewrite" => Ok(NormalizeMode::Rewrite),
+            "redirect" => Ok(NormalizeMode::Redirect),
+            _ => Err(ParseNormalizeModeError(s.to_string())),
+        }
+    }
+}
+
+/// Collapses duplicate slashes and trims the trailing slash, so `/todos/`,
+/// `//todos` and `/todos` all reach the same route.
+///
+/// Must wrap the whole `Router`: layers added with `Router::layer` run after
+/// the route has already been matched.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NormalizePathLayer {
+    mode: NormalizeMode,
+}
+
+impl NormalizePathLayer {
+    pub fn new(mode: NormalizeMode) -> Self {
+        Self { mode }
+    }
+}
+
+impl<S> Layer<S> for NormalizePathLayer {
+    type Service = NormalizePath<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        NormalizePath {
+            inner,
+            mode: self.mode,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NormalizePath<S> {
+    inner: S,
+    mode: NormalizeMode,
+}
+
+type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send + 'static>>;
+
+impl<S, B> Service<Request<B>> for NormalizePath<S>
+where
+    S: Service<Request<B>, Response = Response>,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = BoxFuture<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request<B>) -> Self::Future {
+        if let Some(uri) = normalize_uri(req.uri()) {
+            match self.mode {
+                NormalizeMode::Rewrite => *req.uri_mut() = uri,
+                NormalizeMode::Redirect => {
+                    let res = Redirect::permanent(uri).into_response();
+                    return Box::pin(async move { Ok(res) });
+                }
+            }
+        }
+
+        Box::pin(self.inner.call(req))
+    }
+}
+
+fn normalize_path(path: &str) -> String {
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    format!("/{}", segments.join("/"))
+}
+
+/// Returns the canonical uri, or `None` if `uri` already is.
+fn normalize_uri(uri: &Uri) -> Option<Uri> {
+    let path = normalize_path(uri.path());
+    if path == uri.path() {
+        return None;
+    }
+
+    let path_and_query = match uri.query() {
+        Some(query) => format!("{}?{}", path, query),
+        None => path,
+    };
+    let mut parts = uri.clone().into_parts();
+    parts.path_and_query = Some(path_and_query.parse().ok()?);
+    Uri::from_parts(parts).ok()
+}
@@ -0,0 +1,136 @@
+use crate::handlers::{
+    FieldError, ListSort, LookupTodos, TodoLookup, ValidationErrorBody, MAX_LOOKUP_IDS,
+};
+use crate::repositories::{
+    CreateLabel, CreateProject, CreateTodo, Label, Priority, Project, SortField, SortOrder, Todo,
+    UpdateLabel, UpdateProject, UpdateTodo, TEXT_MAX_GRAPHEMES,
+};
+use axum::{response::Html, Json};
+use serde_json::{json, Value};
+use utoipa::OpenApi;
+
+/// Swagger UI pointed at `/openapi.json`. The assets come from the
+/// `swagger-ui-dist` package, as the GraphQL playground's do.
+const SWAGGER_UI_PAGE: &str = r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8" />
+  <title>rust_todo API</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@4.15.5/swagger-ui.css" />
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@4.15.5/swagger-ui-bundle.js"></script>
+  <script>
+    window.ui = SwaggerUIBundle({ url: "/openapi.json", dom_id: "#swagger-ui" });
+  </script>
+</body>
+</html>
+"##;
+
+/// The REST API of todos, labels and projects. The constraints on payload
+/// fields come from `FIELD_LIMITS`, as utoipa can not declare them.
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        crate::handlers::create_todo,
+        crate::handlers::all_todo,
+        crate::handlers::find_todo,
+        crate::handlers::update_todo,
+        crate::handlers::delete_todo,
+        crate::handlers::lookup_todos_by_ids,
+        crate::handlers::create_label,
+        crate::handlers::all_labels,
+        crate::handlers::find_label,
+        crate::handlers::update_label,
+        crate::handlers::delete_label,
+        crate::handlers::create_project,
+        crate::handlers::all_projects,
+        crate::handlers::find_project,
+        crate::handlers::update_project,
+        crate::handlers::delete_project
+    ),
+    components(schemas(
+        Todo,
+        CreateTodo,
+        UpdateTodo,
+        Priority,
+        ListSort,
+        SortField,
+        SortOrder,
+        Label,
+        CreateLabel,
+        UpdateLabel,
+        Project,
+        CreateProject,
+        UpdateProject,
+        LookupTodos,
+        TodoLookup,
+        ValidationErrorBody,
+        FieldError
+    )),
+    tags(
+        (name = "todos"),
+        (name = "labels"),
+        (name = "projects")
+    )
+)]
+pub struct ApiDoc;
+
+/// A bound that a payload field's `validate` rules put on it.
+#[derive(Debug, Clone, Copy)]
+pub enum Limit {
+    /// `minLength` and `maxLength` of a string.
+    Length(usize, usize),
+    /// `minItems` and `maxItems` of an array.
+    Items(usize, usize),
+    /// `minimum` and `maximum` of a number.
+    Range(f64, f64),
+}
+
+/// Every validated field of the schemas above and its bound. These mirror
+/// the `validate` rules, which a test holds them to.
+pub const FIELD_LIMITS: [(&str, &str, Limit); 13] = [
+    ("CreateTodo", "text", Limit::Length(1, TEXT_MAX_GRAPHEMES)),
+    ("CreateTodo", "lat", Limit::Range(-90.0, 90.0)),
+    ("CreateTodo", "lon", Limit::Range(-180.0, 180.0)),
+    ("CreateTodo", "place", Limit::Length(1, 100)),
+    ("UpdateTodo", "text", Limit::Length(1, TEXT_MAX_GRAPHEMES)),
+    ("UpdateTodo", "lat", Limit::Range(-90.0, 90.0)),
+    ("UpdateTodo", "lon", Limit::Range(-180.0, 180.0)),
+    ("UpdateTodo", "place", Limit::Length(1, 100)),
+    ("CreateLabel", "name", Limit::Length(1, 30)),
+    ("UpdateLabel", "name", Limit::Length(1, 30)),
+    ("CreateProject", "name", Limit::Length(1, 50)),
+    ("UpdateProject", "name", Limit::Length(1, 50)),
+    ("LookupTodos", "ids", Limit::Items(1, MAX_LOOKUP_IDS)),
+];
+
+/// The generated spec with `FIELD_LIMITS` written into its schemas.
+pub fn api_spec() -> Value {
+    let mut spec = serde_json::to_value(ApiDoc::openapi()).expect("spec is always JSON");
+    for (schema, field, limit) in FIELD_LIMITS {
+        let pointer = format!("/components/schemas/{}/properties/{}", schema, field);
+        let property = spec
+            .pointer_mut(&pointer)
+            .and_then(Value::as_object_mut)
+            .unwrap_or_else(|| panic!("no property {} in the spec", pointer));
+        let (min, max) = match limit {
+            Limit::Length(min, max) => (("minLength", json!(min)), ("maxLength", json!(max))),
+            Limit::Items(min, max) => (("minItems", json!(min)), ("maxItems", json!(max))),
+            Limit::Range(min, max) => (("minimum", json!(min)), ("maximum", json!(max))),
+        };
+        for (keyword, value) in [min, max] {
+            property.insert(keyword.to_string(), value);
+        }
+    }
+    spec
+}
+
+pub async fn openapi_json() -> Json<Value> {
+    Json(api_spec())
+}
+
+pub async fn swagger_ui() -> Html<&'static str> {
+    Html(SWAGGER_UI_PAGE)
+}
@@ -0,0 +1,131 @@
+use crate::chat::{ChatWebhook, TodoEvent};
+use crate::repositories::{Page, Todo, TodoFilter, TodoRepository, TodoSort, UpdateTodo};
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use std::{sync::Arc, time::Duration};
+use tokio::task::JoinHandle;
+
+/// How often the scheduler looks for due reminders by default.
+pub const DEFAULT_REMINDER_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Delivers a reminder about a todo, e.g. by email or webhook.
+#[async_trait]
+pub trait Notifier: Send + Sync + 'static {
+    async fn notify(&self, todo: &Todo) -> anyhow::Result<()>;
+}
+
+/// Only writes reminders to the log.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LogNotifier;
+
+#[async_trait]
+impl Notifier for LogNotifier {
+    async fn notify(&self, todo: &Todo) -> anyhow::Result<()> {
+        tracing::info!("reminder for todo {}: {}", todo.id(), todo.text());
+        Ok(())
+    }
+}
+
+/// Polls the repository for todos whose `remind_at` has passed and hands
+/// them to the notifier. With a webhook it also reports todos that turned
+/// overdue since the previous round.
+#[derive(Clone)]
+pub struct ReminderScheduler {
+    notifier: Arc<dyn Notifier>,
+    interval: Duration,
+    overdue_webhook: Option<ChatWebhook>,
+}
+
+impl ReminderScheduler {
+    pub fn new(notifier: Arc<dyn Notifier>, interval: Duration) -> Self {
+        Self {
+            notifier,
+            interval,
+            overdue_webhook: None,
+        }
+    }
+
+    pub fn with_overdue_webhook(self, webhook: ChatWebhook) -> Self {
+        Self {
+            overdue_webhook: Some(webhook),
+            ..self
+        }
+    }
+
+    /// Runs on a tokio task until the process exits.
+    pub fn spawn<T: TodoRepository>(self, repository: T) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(self.interval);
+            let mut checked_at = Utc::now();
+            loop {
+                ticks.tick().await;
+                let now = Utc::now();
+                if let Err(e) = self.fire_due(&repository, now).await {
+                    tracing::error!("fail fire reminders: {:#}", e);
+                }
+                if let Err(e) = self.report_overdue(&repository, checked_at, now).await {
+                    tracing::error!("fail report overdue todos: {:#}", e);
+                }
+                checked_at = now;
+            }
+        })
+    }
+
+    /// Notifies about every open todo whose reminder is due at `now`, then
+    /// clears the reminder so that it fires once. A reminder the notifier
+    /// fails on is kept for the next round. Returns how many were sent.
+    pub async fn fire_due<T: TodoRepository>(
+        &self,
+        repository: &T,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<usize> {
+        let filter = TodoFilter {
+            reminder_due_at: Some(now),
+            ..TodoFilter::default()
+        };
+        let todos = repository
+            .all(&filter, TodoSort::default(), Page::default())
+            .await?;
+        let mut sent = 0;
+        for todo in todos {
+            if let Err(e) = self.notifier.notify(&todo).await {
+                tracing::warn!("fail notify todo {}: {:#}", todo.id(), e);
+                continue;
+            }
+            repository
+                .update(todo.id(), UpdateTodo::clear_reminder())
+                .await?;
+            sent += 1;
+        }
+
+        Ok(sent)
+    }
+
+    /// Posts every open todo that fell due between `since` and `now` to the
+    /// overdue webhook, if there is one. Returns how many were posted.
+    pub async fn report_overdue<T: TodoRepository>(
+        &self,
+        repository: &T,
+        since: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<usize> {
+        let webhook = match &self.overdue_webhook {
+            Some(webhook) if webhook.forwards(TodoEvent::Overdue) => webhook,
+            _ => return Ok(0),
+        };
+        let filter = TodoFilter {
+            completed: Some(false),
+            due_after: Some(since),
+            due_before: Some(now),
+            ..TodoFilter::default()
+        };
+        let todos = repository
+            .all(&filter, TodoSort::default(), Page::default())
+            .await?;
+        for todo in &todos {
+            webhook.send(TodoEvent::Overdue, todo);
+        }
+
+        Ok(todos.len())
+    }
+}
@@ -0,0 +1,1312 @@
+use super::{
+    check_blocked_by, check_project_deletable, project_todo_ids, reorder_positions, ApiKey,
+    CreateApiKey, CreateLabel, CreateProject, CreateTodo, CreateUser, Label, Nearby, Page,
+    Pomodoro, Project, ReorderTodo, RepositoryError, ShareLink, TimeEntry, Todo, TodoFilter,
+    TodoRepository, TodoSort, UpdateLabel, UpdateProject, UpdateTodo, User, UserRepository,
+    DEFAULT_PROJECT_ID, POMODORO_MINUTES,
+};
+use anyhow::Context;
+use axum::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard},
+    time,
+};
+
+type TodoDatas = HashMap<i32, Todo>;
+
+/// Keeps everything in process memory. Opened with `open`, it also snapshots
+/// the whole state to a JSON file after every mutation, or after a batch of
+/// them `with_write_batching`, so a dev instance survives a restart.
+#[derive(Debug, Clone, Default)]
+pub struct TodoRepositoryForMemory {
+    store: Arc<RwLock<TodoDatas>>,
+    time_entries: Arc<RwLock<Vec<TimeEntry>>>,
+    pomodoros: Arc<RwLock<Vec<Pomodoro>>>,
+    labels: Arc<RwLock<Vec<Label>>>,
+    share_links: Arc<RwLock<Vec<ShareLink>>>,
+    projects: Arc<RwLock<Vec<Project>>>,
+    users: Arc<RwLock<Vec<User>>>,
+    api_keys: Arc<RwLock<Vec<ApiKey>>>,
+    /// Snapshot file; the lock also keeps concurrent snapshots apart.
+    snapshot: Option<Arc<Mutex<PathBuf>>>,
+    /// Unset writes the snapshot on every mutation.
+    batching: Option<WriteBatching>,
+    pending: Arc<Mutex<PendingWrites>>,
+}
+
+/// Coalesces snapshot writes under bursts of mutations. The snapshot is
+/// written once `max_writes` mutations are pending or `max_delay` after the
+/// first of them, whichever comes first, so a crash loses at most that.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WriteBatching {
+    pub max_delay: time::Duration,
+    pub max_writes: usize,
+}
+
+#[derive(Debug, Default)]
+struct PendingWrites {
+    writes: usize,
+    /// Whether a delayed write is on its way.
+    scheduled: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Snapshot {
+    todos: Vec<Todo>,
+    time_entries: Vec<TimeEntry>,
+    pomodoros: Vec<Pomodoro>,
+    #[serde(default)]
+    labels: Vec<Label>,
+    #[serde(default)]
+    share_links: Vec<ShareLink>,
+    #[serde(default)]
+    projects: Vec<Project>,
+    #[serde(default)]
+    users: Vec<User>,
+    #[serde(default)]
+    api_keys: Vec<ApiKey>,
+}
+
+impl TodoRepositoryForMemory {
+    pub fn new() -> Self {
+        Self {
+            projects: Arc::new(RwLock::new(with_default_project(Vec::new()))),
+            ..Self::default()
+        }
+    }
+
+    /// Loads the snapshot at `path`, or starts empty if there is none yet.
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let snapshot: Snapshot = match fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .with_context(|| format!("broken snapshot [{}]", path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Snapshot::default(),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(Self {
+            store: Arc::new(RwLock::new(
+                snapshot
+                    .todos
+                    .into_iter()
+                    .map(|todo| (todo.id, todo))
+                    .collect(),
+            )),
+            time_entries: Arc::new(RwLock::new(snapshot.time_entries)),
+            pomodoros: Arc::new(RwLock::new(snapshot.pomodoros)),
+            labels: Arc::new(RwLock::new(snapshot.labels)),
+            share_links: Arc::new(RwLock::new(snapshot.share_links)),
+            projects: Arc::new(RwLock::new(with_default_project(snapshot.projects))),
+            users: Arc::new(RwLock::new(snapshot.users)),
+            api_keys: Arc::new(RwLock::new(snapshot.api_keys)),
+            snapshot: Some(Arc::new(Mutex::new(path))),
+            ..Self::default()
+        })
+    }
+
+    pub fn with_write_batching(self, batching: WriteBatching) -> Self {
+        Self {
+            batching: Some(batching),
+            ..self
+        }
+    }
+
+    fn write_store_ref(&self) -> RwLockWriteGuard<'_, TodoDatas> {
+        self.store.write().unwrap()
+    }
+
+    fn read_store_ref(&self) -> RwLockReadGuard<'_, TodoDatas> {
+        self.store.read().unwrap()
+    }
+
+    /// Records a mutation in the snapshot file, if any: right away, or with
+    /// batching once the batch is full or its delay is up. A delayed write
+    /// that fails is logged and retried with the next mutation.
+    ///
+    /// Must not be called while holding any of the store locks.
+    async fn persist(&self) -> anyhow::Result<()> {
+        if self.snapshot.is_none() {
+            return Ok(());
+        }
+        let batching = match self.batching {
+            Some(batching) => batching,
+            None => return self.blocking(Self::write_snapshot).await,
+        };
+
+        let schedule = {
+            let mut pending = self.pending.lock().unwrap();
+            pending.writes += 1;
+            if pending.writes < batching.max_writes {
+                Some(!std::mem::replace(&mut pending.scheduled, true))
+            } else {
+                None
+            }
+        };
+        match schedule {
+            None => self.flush().await,
+            Some(true) => {
+                let repository = self.clone();
+                tokio::spawn(async move {
+                    tokio::time::sleep(batching.max_delay).await;
+                    repository.pending.lock().unwrap().scheduled = false;
+                    if let Err(e) = repository.flush().await {
+                        tracing::error!("fail write snapshot: {:#}", e);
+                    }
+                });
+                Ok(())
+            }
+            Some(false) => Ok(()),
+        }
+    }
+
+    /// Writes the mutations still pending in a batch, e.g. before shutdown.
+    pub async fn flush(&self) -> anyhow::Result<()> {
+        self.blocking(|repository| {
+            let mut pending = repository.pending.lock().unwrap();
+            if pending.writes == 0 {
+                return Ok(());
+            }
+            repository.write_snapshot()?;
+            pending.writes = 0;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Runs `f` on the blocking thread pool, as the snapshot file is written
+    /// and synced with plain `std::fs` calls.
+    async fn blocking(&self, f: fn(&Self) -> anyhow::Result<()>) -> anyhow::Result<()> {
+        let repository = self.clone();
+        tokio::task::spawn_blocking(move || f(&repository)).await?
+    }
+
+    /// Writes the current state to the snapshot file, if any. The JSON goes to
+    /// a temporary file that is synced and then renamed over the old
+    /// snapshot, so a crash mid-write never leaves a truncated file behind.
+    fn write_snapshot(&self) -> anyhow::Result<()> {
+        let path = match &self.snapshot {
+            Some(path) => path.lock().unwrap(),
+            None => return Ok(()),
+        };
+        let mut todos: Vec<Todo> = self.read_store_ref().values().cloned().collect();
+        todos.sort_by_key(|todo| todo.id);
+        let snapshot = Snapshot {
+            todos,
+            time_entries: self.time_entries.read().unwrap().clone(),
+            pomodoros: self.pomodoros.read().unwrap().clone(),
+            labels: self.labels.read().unwrap().clone(),
+            share_links: self.share_links.read().unwrap().clone(),
+            projects: self.projects.read().unwrap().clone(),
+            users: self.users.read().unwrap().clone(),
+            api_keys: self.api_keys.read().unwrap().clone(),
+        };
+
+        let tmp_path = path.with_extension("json.tmp");
+        let bytes = serde_json::to_vec_pretty(&snapshot)?;
+        fs::File::create(&tmp_path)
+            .and_then(|mut file| {
+                file.write_all(&bytes)?;
+                file.sync_all()
+            })
+            .with_context(|| format!("failed write snapshot [{}]", tmp_path.display()))?;
+        fs::rename(&tmp_path, &*path)
+            .with_context(|| format!("failed replace snapshot [{}]", path.display()))?;
+
+        Ok(())
+    }
+
+    /// The labels with `ids`, failing on the first that does not exist.
+    fn labels_of(&self, ids: &[i32]) -> anyhow::Result<Vec<Label>> {
+        let labels = self.labels.read().unwrap();
+        ids.iter()
+            .map(|id| {
+                labels
+                    .iter()
+                    .find(|label| label.id == *id)
+                    .cloned()
+                    .ok_or_else(|| RepositoryError::NotFound(*id).into())
+            })
+            .collect()
+    }
+
+    fn check_project(&self, id: i32) -> anyhow::Result<()> {
+        if !self
+            .projects
+            .read()
+            .unwrap()
+            .iter()
+            .any(|project| project.id == id)
+        {
+            return Err(RepositoryError::NotFound(id).into());
+        }
+        Ok(())
+    }
+}
+
+/// The todos in `store` that match `filter`, borrowed and sorted.
+fn matching<'a>(store: &'a TodoDatas, filter: &TodoFilter, sort: TodoSort) -> Vec<&'a Todo> {
+    let mut todos: Vec<&Todo> = store.values().filter(|todo| filter.matches(todo)).collect();
+    todos.sort_by(|a, b| sort.compare(a, b));
+    todos
+}
+
+/// Adds the default project to `projects` unless it is there already, as in
+/// a snapshot written before projects existed.
+fn with_default_project(mut projects: Vec<Project>) -> Vec<Project> {
+    if !projects
+        .iter()
+        .any(|project| project.id == DEFAULT_PROJECT_ID)
+    {
+        projects.insert(0, Project::inbox());
+    }
+    projects
+}
+
+#[async_trait]
+impl TodoRepository for TodoRepositoryForMemory {
+    async fn create(&self, payload: CreateTodo) -> anyhow::Result<Todo> {
+        let labels = self.labels_of(&payload.label_ids)?;
+        self.check_project(payload.project_id)?;
+        let todo = {
+            let mut store = self.write_store_ref();
+            if let Some(parent_id) = payload.parent_id {
+                store
+                    .get(&parent_id)
+                    .ok_or(RepositoryError::NotFound(parent_id))?;
+            }
+            payload
+                .limits
+                .check_todos(payload.project_id, payload.user_id, store.values())?;
+            let id = store.keys().max().unwrap_or(&0) + 1;
+            check_blocked_by(id, &payload.blocked_by, store.values())?;
+            let mut todo = Todo::new(id, payload.text.clone());
+            todo.due_date = payload.due_date;
+            todo.priority = payload.priority;
+            todo.parent_id = payload.parent_id;
+            todo.project_id = payload.project_id;
+            todo.user_id = payload.user_id;
+            todo.recurrence = payload.recurrence.clone();
+            todo.lat = payload.lat;
+            todo.lon = payload.lon;
+            todo.place = payload.place.clone();
+            todo.remind_at = payload.remind_at;
+            todo.set_labels(labels);
+            todo.set_blocked_by(payload.blocked_by.clone());
+            store.insert(id, todo.clone());
+            todo
+        };
+        self.persist().await?;
+
+        Ok(todo)
+    }
+
+    async fn find(&self, id: i32) -> anyhow::Result<Todo> {
+        let store = self.read_store_ref();
+        let todo = store
+            .get(&id)
+            .cloned()
+            .ok_or(RepositoryError::NotFound(id))?;
+
+        Ok(todo)
+    }
+
+    async fn all(
+        &self,
+        filter: &TodoFilter,
+        sort: TodoSort,
+        page: Page,
+    ) -> anyhow::Result<Vec<Todo>> {
+        let store = self.read_store_ref();
+        let todos = matching(&store, filter, sort);
+        Ok(page.apply(todos.into_iter()).cloned().collect())
+    }
+
+    /// Serializes under the read lock, so the page is never cloned.
+    async fn all_json(
+        &self,
+        filter: &TodoFilter,
+        sort: TodoSort,
+        page: Page,
+    ) -> anyhow::Result<Vec<u8>> {
+        let store = self.read_store_ref();
+        let todos = matching(&store, filter, sort);
+        let page: Vec<&Todo> = page.apply(todos.into_iter()).collect();
+        Ok(serde_json::to_vec(&page)?)
+    }
+
+    async fn count(&self, filter: &TodoFilter) -> anyhow::Result<usize> {
+        let store = self.read_store_ref();
+        Ok(store.values().filter(|todo| filter.matches(todo)).count())
+    }
+
+    async fn update(&self, id: i32, payload: UpdateTodo) -> anyhow::Result<Todo> {
+        let labels = payload
+            .label_ids
+            .as_deref()
+            .map(|ids| self.labels_of(ids))
+            .transpose()?;
+        if let Some(project_id) = payload.project_id {
+            self.check_project(project_id)?;
+        }
+        let (todo, was_completed) = {
+            let mut store = self.write_store_ref();
+            let todo = store.get(&id).context(RepositoryError::NotFound(id))?;
+            let was_completed = todo.completed;
+            let text = payload.text.unwrap_or(todo.text.clone());
+            let completed = payload.completed.unwrap_or(todo.completed);
+            let due_date = payload.due_date.unwrap_or(todo.due_date);
+            let priority = payload.priority.unwrap_or(todo.priority);
+            let project_id = payload.project_id.unwrap_or(todo.project_id);
+            let recurrence = payload.recurrence.unwrap_or(todo.recurrence.clone());
+            let mut todo = Todo {
+                text,
+                completed,
+                due_date,
+                priority,
+                project_id,
+                recurrence,
+                lat: payload.lat.unwrap_or(todo.lat),
+                lon: payload.lon.unwrap_or(todo.lon),
+                place: payload.place.unwrap_or(todo.place.clone()),
+                remind_at: payload.remind_at.unwrap_or(todo.remind_at),
+                updated_at: Utc::now(),
+                ..todo.clone()
+            };
+            if let Some(labels) = labels {
+                todo.set_labels(labels);
+            }
+            if let Some(blocked_by) = payload.blocked_by {
+                check_blocked_by(id, &blocked_by, store.values())?;
+                todo.set_blocked_by(blocked_by);
+            }
+            store.insert(id, todo.clone());
+            (todo, was_completed)
+        };
+        self.persist().await?;
+        if todo.completed && !was_completed {
+            if let Some(next) = todo.next_occurrence(Utc::now()) {
+                self.create(next).await?;
+            }
+        }
+
+        Ok(todo)
+    }
+
+    async fn delete(&self, id: i32) -> anyhow::Result<()> {
+        {
+            let mut store = self.write_store_ref();
+            if store.values().any(|todo| todo.parent_id == Some(id)) {
+                return Err(
+                    RepositoryError::Conflict(format!("todo has subtasks, id is {}", id)).into(),
+                );
+            }
+            store.remove(&id).ok_or(RepositoryError::NotFound(id))?;
+            for todo in store.values_mut() {
+                todo.unblock(id);
+            }
+        }
+        // Ids are reused, so anything left behind would pass to the next todo.
+        self.share_links
+            .write()
+            .unwrap()
+            .retain(|link| link.todo_id != id);
+        self.time_entries
+            .write()
+            .unwrap()
+            .retain(|entry| entry.todo_id != id);
+        self.pomodoros
+            .write()
+            .unwrap()
+            .retain(|pomodoro| pomodoro.todo_id != id);
+        self.persist().await?;
+
+        Ok(())
+    }
+
+    async fn snooze(&self, id: i32, until: Option<DateTime<Utc>>) -> anyhow::Result<Todo> {
+        let todo = {
+            let mut store = self.write_store_ref();
+            let todo = store.get_mut(&id).context(RepositoryError::NotFound(id))?;
+            todo.snoozed_until = until;
+            todo.updated_at = Utc::now();
+            todo.clone()
+        };
+        self.persist().await?;
+
+        Ok(todo)
+    }
+
+    async fn start_timer(&self, id: i32) -> anyhow::Result<TimeEntry> {
+        self.find(id).await?;
+        let entry = {
+            let mut entries = self.time_entries.write().unwrap();
+            if entries
+                .iter()
+                .any(|entry| entry.todo_id == id && entry.stopped_at.is_none())
+            {
+                return Err(RepositoryError::Conflict(format!(
+                    "timer already running, id is {}",
+                    id
+                ))
+                .into());
+            }
+            let entry = TimeEntry {
+                id: entries.iter().map(|entry| entry.id).max().unwrap_or(0) + 1,
+                todo_id: id,
+                started_at: Utc::now(),
+                stopped_at: None,
+            };
+            entries.push(entry.clone());
+            entry
+        };
+        self.persist().await?;
+
+        Ok(entry)
+    }
+
+    async fn stop_timer(&self, id: i32) -> anyhow::Result<TimeEntry> {
+        self.find(id).await?;
+        let entry = {
+            let mut entries = self.time_entries.write().unwrap();
+            let entry = entries
+                .iter_mut()
+                .find(|entry| entry.todo_id == id && entry.stopped_at.is_none())
+                .ok_or_else(|| {
+                    RepositoryError::Conflict(format!("no running timer, id is {}", id))
+                })?;
+            entry.stopped_at = Some(Utc::now());
+            entry.clone()
+        };
+        {
+            let mut store = self.write_store_ref();
+            let todo = store.get_mut(&id).context(RepositoryError::NotFound(id))?;
+            todo.time_spent += entry.duration();
+        }
+        self.persist().await?;
+
+        Ok(entry)
+    }
+
+    async fn time_entries(&self, id: i32) -> anyhow::Result<Vec<TimeEntry>> {
+        self.find(id).await?;
+        let entries = self.time_entries.read().unwrap();
+        Ok(entries
+            .iter()
+            .filter(|entry| entry.todo_id == id)
+            .cloned()
+            .collect())
+    }
+
+    async fn start_pomodoro(&self, id: i32) -> anyhow::Result<Pomodoro> {
+        self.find(id).await?;
+        let pomodoro = {
+            let mut pomodoros = self.pomodoros.write().unwrap();
+            if pomodoros
+                .iter()
+                .any(|pomodoro| pomodoro.todo_id == id && pomodoro.finished_at.is_none())
+            {
+                return Err(RepositoryError::Conflict(format!(
+                    "pomodoro already running, id is {}",
+                    id
+                ))
+                .into());
+            }
+            let started_at = Utc::now();
+            let pomodoro = Pomodoro {
+                id: pomodoros
+                    .iter()
+                    .map(|pomodoro| pomodoro.id)
+                    .max()
+                    .unwrap_or(0)
+                    + 1,
+                todo_id: id,
+                started_at,
+                ends_at: started_at + Duration::minutes(POMODORO_MINUTES),
+                interruptions: 0,
+                finished_at: None,
+                completed: false,
+            };
+            pomodoros.push(pomodoro.clone());
+            pomodoro
+        };
+        self.persist().await?;
+
+        Ok(pomodoro)
+    }
+
+    async fn interrupt_pomodoro(&self, pomodoro_id: i32) -> anyhow::Result<Pomodoro> {
+        let pomodoro = {
+            let mut pomodoros = self.pomodoros.write().unwrap();
+            let pomodoro = running_pomodoro(&mut pomodoros, pomodoro_id)?;
+            pomodoro.interruptions += 1;
+            pomodoro.clone()
+        };
+        self.persist().await?;
+
+        Ok(pomodoro)
+    }
+
+    async fn finish_pomodoro(&self, pomodoro_id: i32) -> anyhow::Result<Pomodoro> {
+        let pomodoro = {
+            let mut pomodoros = self.pomodoros.write().unwrap();
+            let pomodoro = running_pomodoro(&mut pomodoros, pomodoro_id)?;
+            let finished_at = Utc::now();
+            pomodoro.finished_at = Some(finished_at);
+            pomodoro.completed = finished_at >= pomodoro.ends_at;
+            pomodoro.clone()
+        };
+
+        if pomodoro.completed {
+            let mut entries = self.time_entries.write().unwrap();
+            let entry = TimeEntry {
+                id: entries.iter().map(|entry| entry.id).max().unwrap_or(0) + 1,
+                todo_id: pomodoro.todo_id,
+                started_at: pomodoro.started_at,
+                stopped_at: Some(pomodoro.ends_at),
+            };
+            entries.push(entry);
+
+            let mut store = self.write_store_ref();
+            if let Some(todo) = store.get_mut(&pomodoro.todo_id) {
+                todo.time_spent += pomodoro.duration();
+            }
+        }
+        self.persist().await?;
+
+        Ok(pomodoro)
+    }
+
+    async fn pomodoro(&self, pomodoro_id: i32) -> anyhow::Result<Pomodoro> {
+        let pomodoros = self.pomodoros.read().unwrap();
+        let pomodoro = pomodoros
+            .iter()
+            .find(|pomodoro| pomodoro.id == pomodoro_id)
+            .cloned()
+            .ok_or(RepositoryError::NotFound(pomodoro_id))?;
+        Ok(pomodoro)
+    }
+
+    async fn pomodoros(&self, id: i32) -> anyhow::Result<Vec<Pomodoro>> {
+        self.find(id).await?;
+        let pomodoros = self.pomodoros.read().unwrap();
+        Ok(pomodoros
+            .iter()
+            .filter(|pomodoro| pomodoro.todo_id == id)
+            .cloned()
+            .collect())
+    }
+
+    async fn completed_pomodoros(&self, user_id: Option<i32>) -> anyhow::Result<Vec<Pomodoro>> {
+        let store = self.read_store_ref();
+        let pomodoros = self.pomodoros.read().unwrap();
+        Ok(pomodoros
+            .iter()
+            .filter(|pomodoro| {
+                pomodoro.completed
+                    && (user_id.is_none()
+                        || store
+                            .get(&pomodoro.todo_id)
+                            .map(|todo| todo.user_id == user_id)
+                            .unwrap_or(false))
+            })
+            .cloned()
+            .collect())
+    }
+
+    async fn create_label(&self, payload: CreateLabel) -> anyhow::Result<Label> {
+        let label = {
+            let mut labels = self.labels.write().unwrap();
+            let id = labels.iter().map(|label| label.id).max().unwrap_or(0) + 1;
+            check_label_name(&labels, id, payload.user_id, &payload.name)?;
+            let label = Label {
+                id,
+                name: payload.name,
+                user_id: payload.user_id,
+            };
+            labels.push(label.clone());
+            label
+        };
+        self.persist().await?;
+
+        Ok(label)
+    }
+
+    async fn find_label(&self, id: i32) -> anyhow::Result<Label> {
+        let label = self
+            .labels
+            .read()
+            .unwrap()
+            .iter()
+            .find(|label| label.id == id)
+            .cloned()
+            .ok_or(RepositoryError::NotFound(id))?;
+
+        Ok(label)
+    }
+
+    async fn all_labels(&self, user_id: Option<i32>) -> anyhow::Result<Vec<Label>> {
+        let mut labels = self.labels.read().unwrap().clone();
+        labels.retain(|label| user_id.is_none() || label.user_id == user_id);
+        labels.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(labels)
+    }
+
+    async fn update_label(&self, id: i32, payload: UpdateLabel) -> anyhow::Result<Label> {
+        let label = {
+            let mut labels = self.labels.write().unwrap();
+            let index = labels
+                .iter()
+                .position(|label| label.id == id)
+                .ok_or(RepositoryError::NotFound(id))?;
+            if let Some(name) = payload.name {
+                check_label_name(&labels, id, labels[index].user_id, &name)?;
+                labels[index].name = name;
+            }
+            labels[index].clone()
+        };
+        for todo in self.write_store_ref().values_mut() {
+            todo.replace_label(id, Some(&label));
+        }
+        self.persist().await?;
+
+        Ok(label)
+    }
+
+    async fn delete_label(&self, id: i32) -> anyhow::Result<()> {
+        {
+            let mut labels = self.labels.write().unwrap();
+            let index = labels
+                .iter()
+                .position(|label| label.id == id)
+                .ok_or(RepositoryError::NotFound(id))?;
+            labels.remove(index);
+        }
+        for todo in self.write_store_ref().values_mut() {
+            todo.replace_label(id, None);
+        }
+        self.persist().await?;
+
+        Ok(())
+    }
+
+    async fn blockers(&self, id: i32) -> anyhow::Result<Vec<Todo>> {
+        let store = self.read_store_ref();
+        let todo = store.get(&id).ok_or(RepositoryError::NotFound(id))?;
+        let blockers = todo
+            .blocked_by
+            .iter()
+            .filter_map(|blocker| store.get(blocker))
+            .filter(|blocker| !blocker.completed)
+            .cloned()
+            .collect();
+        Ok(blockers)
+    }
+
+    async fn nearby(&self, nearby: &Nearby) -> anyhow::Result<Vec<Todo>> {
+        let store = self.read_store_ref();
+        Ok(nearby.select(store.values().cloned()))
+    }
+
+    async fn create_share_link(&self, link: ShareLink) -> anyhow::Result<ShareLink> {
+        self.find(link.todo_id).await?;
+        self.share_links.write().unwrap().push(link.clone());
+        self.persist().await?;
+
+        Ok(link)
+    }
+
+    async fn find_share_link(&self, token: &str) -> anyhow::Result<Option<ShareLink>> {
+        let link = self
+            .share_links
+            .read()
+            .unwrap()
+            .iter()
+            .find(|link| link.token == token)
+            .cloned();
+        Ok(link)
+    }
+
+    async fn delete_share_link(&self, id: i32, token: &str) -> anyhow::Result<()> {
+        {
+            let mut links = self.share_links.write().unwrap();
+            let index = links
+                .iter()
+                .position(|link| link.todo_id == id && link.token == token)
+                .ok_or(RepositoryError::NotFound(id))?;
+            links.remove(index);
+        }
+        self.persist().await?;
+
+        Ok(())
+    }
+
+    async fn reorder(&self, id: i32, payload: ReorderTodo) -> anyhow::Result<Todo> {
+        let todo = {
+            let mut store = self.write_store_ref();
+            for (todo_id, position) in reorder_positions(id, &payload, store.values())? {
+                if let Some(todo) = store.get_mut(&todo_id) {
+                    todo.position = position;
+                }
+            }
+            store
+                .get(&id)
+                .cloned()
+                .ok_or(RepositoryError::NotFound(id))?
+        };
+        self.persist().await?;
+
+        Ok(todo)
+    }
+
+    async fn create_project(&self, payload: CreateProject) -> anyhow::Result<Project> {
+        let project = {
+            let mut projects = self.projects.write().unwrap();
+            let id = projects.iter().map(|project| project.id).max().unwrap_or(0) + 1;
+            let project = Project {
+                id,
+                name: payload.name,
+                user_id: payload.user_id,
+            };
+            projects.push(project.clone());
+            project
+        };
+        self.persist().await?;
+
+        Ok(project)
+    }
+
+    async fn find_project(&self, id: i32) -> anyhow::Result<Project> {
+        let project = self
+            .projects
+            .read()
+            .unwrap()
+            .iter()
+            .find(|project| project.id == id)
+            .cloned()
+            .ok_or(RepositoryError::NotFound(id))?;
+
+        Ok(project)
+    }
+
+    async fn all_projects(&self, user_id: Option<i32>) -> anyhow::Result<Vec<Project>> {
+        let mut projects = self.projects.read().unwrap().clone();
+        projects.retain(|project| project.listed_for(user_id));
+        projects.sort_by_key(|project| project.id);
+        Ok(projects)
+    }
+
+    async fn update_project(&self, id: i32, payload: UpdateProject) -> anyhow::Result<Project> {
+        let project = {
+            let mut projects = self.projects.write().unwrap();
+            let project = projects
+                .iter_mut()
+                .find(|project| project.id == id)
+                .ok_or(RepositoryError::NotFound(id))?;
+            if let Some(name) = payload.name {
+                project.name = name;
+            }
+            project.clone()
+        };
+        self.persist().await?;
+
+        Ok(project)
+    }
+
+    async fn delete_project(&self, id: i32) -> anyhow::Result<()> {
+        check_project_deletable(id)?;
+        let project = self.find_project(id).await?;
+        let todo_ids = project_todo_ids(&project, self.read_store_ref().values())?;
+        for todo_id in todo_ids {
+            self.delete(todo_id).await?;
+        }
+        self.projects
+            .write()
+            .unwrap()
+            .retain(|project| project.id != id);
+        self.persist().await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl UserRepository for TodoRepositoryForMemory {
+    async fn create_user(&self, payload: CreateUser) -> anyhow::Result<User> {
+        let user = {
+            let mut users = self.users.write().unwrap();
+            if users.iter().any(|user| user.name == payload.name) {
+                return Err(RepositoryError::Conflict(format!(
+                    "user already exists, name is {}",
+                    payload.name
+                ))
+                .into());
+            }
+            let id = users.iter().map(|user| user.id).max().unwrap_or(0) + 1;
+            let user = payload.into_user(id);
+            users.push(user.clone());
+            user
+        };
+        self.persist().await?;
+
+        Ok(user)
+    }
+
+    async fn find_user_by_name(&self, name: &str) -> anyhow::Result<Option<User>> {
+        let user = self
+            .users
+            .read()
+            .unwrap()
+            .iter()
+            .find(|user| user.name == name)
+            .cloned();
+        Ok(user)
+    }
+
+    async fn create_api_key(&self, payload: CreateApiKey) -> anyhow::Result<ApiKey> {
+        let key = {
+            let mut api_keys = self.api_keys.write().unwrap();
+            let id = api_keys.iter().map(|key| key.id).max().unwrap_or(0) + 1;
+            let key = payload.into_api_key(id);
+            api_keys.push(key.clone());
+            key
+        };
+        self.persist().await?;
+
+        Ok(key)
+    }
+
+    async fn find_api_key_by_hash(&self, key_hash: &str) -> anyhow::Result<Option<ApiKey>> {
+        let key = self
+            .api_keys
+            .read()
+            .unwrap()
+            .iter()
+            .find(|key| key.key_hash == key_hash)
+            .cloned();
+        Ok(key)
+    }
+
+    async fn api_keys(&self, user_id: i32) -> anyhow::Result<Vec<ApiKey>> {
+        let keys = self
+            .api_keys
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|key| key.user_id == user_id)
+            .cloned()
+            .collect();
+        Ok(keys)
+    }
+
+    async fn delete_api_key(&self, user_id: i32, id: i32) -> anyhow::Result<()> {
+        {
+            let mut api_keys = self.api_keys.write().unwrap();
+            let len = api_keys.len();
+            api_keys.retain(|key| !(key.id == id && key.user_id == user_id));
+            if api_keys.len() == len {
+                return Err(RepositoryError::NotFound(id).into());
+            }
+        }
+        self.persist().await?;
+
+        Ok(())
+    }
+}
+
+/// Fails if a label other than `id` is already called `name`.
+/// Names are unique per user.
+fn check_label_name(
+    labels: &[Label],
+    id: i32,
+    user_id: Option<i32>,
+    name: &str,
+) -> anyhow::Result<()> {
+    if labels
+        .iter()
+        .any(|label| label.id != id && label.user_id == user_id && label.name == name)
+    {
+        return Err(
+            RepositoryError::Conflict(format!("label already exists, name is {}", name)).into(),
+        );
+    }
+    Ok(())
+}
+
+fn running_pomodoro(pomodoros: &mut [Pomodoro], pomodoro_id: i32) -> anyhow::Result<&mut Pomodoro> {
+    let pomodoro = pomodoros
+        .iter_mut()
+        .find(|pomodoro| pomodoro.id == pomodoro_id)
+        .ok_or(RepositoryError::NotFound(pomodoro_id))?;
+    if pomodoro.finished_at.is_some() {
+        return Err(RepositoryError::Conflict(format!(
+            "pomodoro already finished, id is {}",
+            pomodoro_id
+        ))
+        .into());
+    }
+
+    Ok(pomodoro)
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[tokio::test]
+    async fn crud_scenario_memory() {
+        let text = "todo text".to_string();
+        let id = 1;
+        let expected = Todo::new(id, text.clone());
+
+        // create
+        let repository = TodoRepositoryForMemory::new();
+        let todo = repository
+            .create(CreateTodo::new(text))
+            .await
+            .expect("failed store todo");
+        let expected = expected.with_timestamps_of(&todo);
+        assert_eq!(expected, todo);
+
+        // find
+        let todo = repository.find(todo.id).await.unwrap();
+        assert_eq!(expected, todo);
+
+        // all
+        let todo = repository
+            .all(&TodoFilter::default(), TodoSort::default(), Page::default())
+            .await
+            .expect("failed get all");
+        assert_eq!(vec![expected.clone()], todo);
+        let json = repository
+            .all_json(&TodoFilter::default(), TodoSort::default(), Page::default())
+            .await
+            .expect("failed get all as json");
+        assert_eq!(serde_json::to_vec(&todo).unwrap(), json);
+
+        // stale
+        let stale_filter = TodoFilter {
+            stale_before: Some(Utc::now() - Duration::days(30)),
+            ..TodoFilter::default()
+        };
+        let todo = repository
+            .all(&stale_filter, TodoSort::default(), Page::default())
+            .await
+            .expect("failed get all");
+        assert!(todo.is_empty());
+        repository
+            .write_store_ref()
+            .get_mut(&id)
+            .unwrap()
+            .updated_at = Utc::now() - Duration::days(31);
+        let todo = repository
+            .all(&stale_filter, TodoSort::default(), Page::default())
+            .await
+            .expect("failed get all");
+        assert_eq!(todo.len(), 1);
+
+        // update
+        let text = "update todo text".to_string();
+        let todo = repository
+            .update(
+                1,
+                UpdateTodo {
+                    text: Some(text.clone()),
+                    completed: Some(true),
+                    label_ids: None,
+                    due_date: None,
+                    priority: None,
+                    blocked_by: None,
+                    project_id: None,
+                    recurrence: None,
+                    lat: None,
+                    lon: None,
+                    place: None,
+                    remind_at: None,
+                },
+            )
+            .await
+            .expect("failed update todo");
+        assert_eq!(
+            Todo {
+                text,
+                completed: true,
+                ..expected.with_timestamps_of(&todo)
+            },
+            todo
+        );
+        assert!(todo.updated_at > todo.created_at);
+
+        // timer
+        let started = repository
+            .start_timer(id)
+            .await
+            .expect("failed start timer");
+        assert!(repository.start_timer(id).await.is_err());
+        let stopped = repository.stop_timer(id).await.expect("failed stop timer");
+        assert_eq!(started.id, stopped.id);
+        assert!(stopped.stopped_at.is_some());
+        assert!(repository.stop_timer(id).await.is_err());
+        let entries = repository
+            .time_entries(id)
+            .await
+            .expect("failed get time entries");
+        assert_eq!(vec![stopped], entries);
+
+        // pomodoro
+        let pomodoro = repository
+            .start_pomodoro(id)
+            .await
+            .expect("failed start pomodoro");
+        assert!(repository.start_pomodoro(id).await.is_err());
+        let pomodoro = repository
+            .interrupt_pomodoro(pomodoro.id)
+            .await
+            .expect("failed interrupt pomodoro");
+        assert_eq!(pomodoro.interruptions, 1);
+        {
+            let mut pomodoros = repository.pomodoros.write().unwrap();
+            pomodoros[0].started_at = Utc::now() - Duration::minutes(POMODORO_MINUTES + 1);
+            pomodoros[0].ends_at = Utc::now() - Duration::minutes(1);
+        }
+        let pomodoro = repository
+            .finish_pomodoro(pomodoro.id)
+            .await
+            .expect("failed finish pomodoro");
+        assert!(pomodoro.completed);
+        assert!(repository.finish_pomodoro(pomodoro.id).await.is_err());
+        let completed = repository
+            .completed_pomodoros(None)
+            .await
+            .expect("failed get completed pomodoros");
+        assert_eq!(vec![pomodoro.clone()], completed);
+        assert!(repository
+            .completed_pomodoros(Some(1))
+            .await
+            .unwrap()
+            .is_empty());
+        let entries = repository
+            .time_entries(id)
+            .await
+            .expect("failed get time entries");
+        assert_eq!(entries.len(), 2);
+        let todo = repository.find(id).await.unwrap();
+        assert_eq!(
+            todo.time_spent,
+            entries[0].duration() + POMODORO_MINUTES * 60
+        );
+
+        // delete, and the reused id starts over
+        repository.delete(id).await.expect("failed delete todo");
+        let todo = repository
+            .create(CreateTodo::new("next todo".to_string()))
+            .await
+            .expect("failed store todo");
+        assert_eq!(todo.id, id);
+        assert!(repository.time_entries(id).await.unwrap().is_empty());
+        assert!(repository.pomodoros(id).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn user_scenario_memory() {
+        let repository = TodoRepositoryForMemory::new();
+        let user = repository
+            .create_user(CreateUser {
+                name: "alice".to_string(),
+                password_hash: "hash".to_string(),
+            })
+            .await
+            .expect("failed create user");
+        assert_eq!(user.id, 1);
+        assert!(repository
+            .create_user(CreateUser {
+                name: "alice".to_string(),
+                password_hash: "other".to_string(),
+            })
+            .await
+            .is_err());
+        assert_eq!(
+            Some(user.clone()),
+            repository.find_user_by_name("alice").await.unwrap()
+        );
+        assert_eq!(None, repository.find_user_by_name("bob").await.unwrap());
+
+        let key = repository
+            .create_api_key(CreateApiKey {
+                user_id: user.id,
+                name: "backup".to_string(),
+                prefix: "tk_0123".to_string(),
+                key_hash: "hash".to_string(),
+            })
+            .await
+            .expect("failed create api key");
+        assert_eq!(
+            Some(key.clone()),
+            repository.find_api_key_by_hash("hash").await.unwrap()
+        );
+        assert_eq!(
+            vec![key.clone()],
+            repository.api_keys(user.id).await.unwrap()
+        );
+        assert!(repository.api_keys(user.id + 1).await.unwrap().is_empty());
+        assert!(repository
+            .delete_api_key(user.id + 1, key.id)
+            .await
+            .is_err());
+        repository
+            .delete_api_key(user.id, key.id)
+            .await
+            .expect("failed delete api key");
+        assert_eq!(None, repository.find_api_key_by_hash("hash").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn label_scenario_memory() {
+        let repository = TodoRepositoryForMemory::new();
+        let work = repository
+            .create_label(CreateLabel::new("work".to_string()))
+            .await
+            .expect("failed create label");
+        let home = repository
+            .create_label(CreateLabel::new("home".to_string()))
+            .await
+            .expect("failed create label");
+        assert!(repository
+            .create_label(CreateLabel::new("work".to_string()))
+            .await
+            .is_err());
+        assert_eq!(
+            vec![home.clone(), work.clone()],
+            repository.all_labels(None).await.unwrap()
+        );
+
+        let todo = repository
+            .create(CreateTodo::new("labelled".to_string()).with_labels(vec![work.id, home.id]))
+            .await
+            .expect("failed store todo");
+        assert_eq!(vec![home.clone(), work.clone()], *todo.labels);
+        let label_filter = TodoFilter {
+            label: Some("work".to_string()),
+            ..TodoFilter::default()
+        };
+        assert_eq!(repository.count(&label_filter).await.unwrap(), 1);
+
+        let renamed = repository
+            .update_label(
+                work.id,
+                UpdateLabel {
+                    name: Some("office".to_string()),
+                },
+            )
+            .await
+            .expect("failed update label");
+        repository
+            .delete_label(home.id)
+            .await
+            .expect("failed delete label");
+        let todo = repository.find(todo.id).await.unwrap();
+        assert_eq!(vec![renamed], *todo.labels);
+        assert_eq!(repository.count(&label_filter).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn snapshot_survives_reopen() {
+        let path = std::env::temp_dir().join(format!(
+            "rust_todo_snapshot_{}.json",
+            Utc::now().timestamp_millis()
+        ));
+
+        let repository = TodoRepositoryForMemory::open(&path).expect("failed open snapshot");
+        let first = repository
+            .create(CreateTodo::new("first".to_string()))
+            .await
+            .expect("failed store todo");
+        let second = repository
+            .create(CreateTodo::new("second".to_string()))
+            .await
+            .expect("failed store todo");
+        repository
+            .delete(first.id)
+            .await
+            .expect("failed delete todo");
+        repository
+            .start_timer(second.id)
+            .await
+            .expect("failed start timer");
+
+        let reopened = TodoRepositoryForMemory::open(&path).expect("failed reopen snapshot");
+        let _ = fs::remove_file(&path);
+        assert_eq!(
+            vec![second.clone()],
+            reopened
+                .all(&TodoFilter::default(), TodoSort::default(), Page::default())
+                .await
+                .unwrap()
+        );
+        assert_eq!(reopened.time_entries(second.id).await.unwrap().len(), 1);
+        let third = reopened
+            .create(CreateTodo::new("third".to_string()))
+            .await
+            .expect("failed store todo");
+        assert_eq!(third.id, second.id + 1);
+    }
+
+    #[tokio::test]
+    async fn batched_snapshot_writes() {
+        let path = std::env::temp_dir().join(format!(
+            "rust_todo_batched_{}.json",
+            Utc::now().timestamp_millis()
+        ));
+        let todos_on_disk = || {
+            TodoRepositoryForMemory::open(&path)
+                .unwrap()
+                .read_store_ref()
+                .len()
+        };
+
+        let repository = TodoRepositoryForMemory::open(&path)
+            .unwrap()
+            .with_write_batching(WriteBatching {
+                max_delay: time::Duration::from_millis(50),
+                max_writes: 3,
+            });
+        for text in ["first", "second"] {
+            repository
+                .create(CreateTodo::new(text.to_string()))
+                .await
+                .expect("failed store todo");
+        }
+        assert_eq!(todos_on_disk(), 0);
+
+        // a full batch is written right away
+        repository
+            .create(CreateTodo::new("third".to_string()))
+            .await
+            .expect("failed store todo");
+        assert_eq!(todos_on_disk(), 3);
+
+        // the rest once the delay is up
+        repository
+            .create(CreateTodo::new("fourth".to_string()))
+            .await
+            .expect("failed store todo");
+        assert_eq!(todos_on_disk(), 3);
+        tokio::time::sleep(time::Duration::from_millis(200)).await;
+        assert_eq!(todos_on_disk(), 4);
+
+        repository
+            .create(CreateTodo::new("fifth".to_string()))
+            .await
+            .expect("failed store todo");
+        repository.flush().await.expect("failed flush snapshot");
+        assert_eq!(todos_on_disk(), 5);
+        let _ = fs::remove_file(&path);
+    }
+}
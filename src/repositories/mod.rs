@@ -0,0 +1,1633 @@
+use axum::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
+use sqlx::{types::Json, FromRow};
+use std::{
+    borrow::Cow,
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+};
+use thiserror::Error;
+use unicode_segmentation::UnicodeSegmentation;
+use utoipa::ToSchema;
+use validator::{Validate, ValidationError};
+
+use self::recurrence::Recurrence;
+
+mod memory;
+mod postgres;
+mod recurrence;
+mod redis;
+mod sled;
+mod sqlite;
+mod urgency;
+
+pub use self::redis::TodoRepositoryForRedis;
+pub use self::sled::TodoRepositoryForSled;
+pub use memory::{TodoRepositoryForMemory, WriteBatching};
+pub use postgres::TodoRepositoryForDb;
+pub use sqlite::TodoRepositoryForSqlite;
+pub use urgency::all_by_urgency;
+
+/// Maximum todo text length, counted in user-perceived characters
+/// (extended grapheme clusters) rather than bytes or code points.
+pub const TEXT_MAX_GRAPHEMES: usize = 100;
+/// Maximum encoded size of todo text. Checked separately so that a handful of
+/// huge grapheme clusters (e.g. stacked combining marks) cannot slip through.
+pub const TEXT_MAX_BYTES: usize = 4096;
+/// Length of a single pomodoro session.
+pub const POMODORO_MINUTES: i64 = 25;
+/// Random bytes in a share link token, which is their hex encoding.
+const SHARE_TOKEN_BYTES: usize = 32;
+/// The project every backend starts with. Todos created without a project
+/// land here, and it can not be deleted.
+pub const DEFAULT_PROJECT_ID: i32 = 1;
+const DEFAULT_PROJECT_NAME: &str = "Inbox";
+
+#[derive(Debug, Error)]
+pub enum RepositoryError {
+    #[error("Unexpected Error: [{0}]")]
+    Unexpected(String),
+    #[error("NotFound, id is {0}")]
+    NotFound(i32),
+    #[error("Conflict: [{0}]")]
+    Conflict(String),
+    #[error("Todo limit reached: [{usage} of {limit}]")]
+    TodoLimitReached { limit: usize, usage: usize },
+    #[error("Todo limit of project {project_id} reached: [{usage} of {limit}]")]
+    ProjectTodoLimitReached {
+        project_id: i32,
+        limit: usize,
+        usage: usize,
+    },
+}
+
+/// Upper bounds on the todos each user stores, configured per deployment.
+/// Todos without a user count together. Carried by `CreateTodo` and checked
+/// by each backend in the same step as the insert, so concurrent creates
+/// can not overshoot them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TodoLimits {
+    pub max_todos: Option<usize>,
+    /// Applies to every project separately.
+    pub max_project_todos: Option<usize>,
+}
+
+impl TodoLimits {
+    fn is_unlimited(&self) -> bool {
+        self.max_todos.is_none() && self.max_project_todos.is_none()
+    }
+
+    /// Fails with `TodoLimitReached` or `ProjectTodoLimitReached` unless one
+    /// more todo fits next to the `usage` stored by its user, `project_usage`
+    /// of them in project `project_id`.
+    fn check(&self, project_id: i32, usage: usize, project_usage: usize) -> anyhow::Result<()> {
+        if let Some(limit) = self.max_todos.filter(|limit| usage >= *limit) {
+            return Err(RepositoryError::TodoLimitReached { limit, usage }.into());
+        }
+        if let Some(limit) = self
+            .max_project_todos
+            .filter(|limit| project_usage >= *limit)
+        {
+            return Err(RepositoryError::ProjectTodoLimitReached {
+                project_id,
+                limit,
+                usage: project_usage,
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    /// `check` against the stored `todos` of user `user_id`.
+    fn check_todos<'a>(
+        &self,
+        project_id: i32,
+        user_id: Option<i32>,
+        todos: impl IntoIterator<Item = &'a Todo>,
+    ) -> anyhow::Result<()> {
+        let (usage, project_usage) = todos
+            .into_iter()
+            .filter(|todo| todo.user_id == user_id)
+            .fold((0, 0), |(usage, in_project), todo| {
+                (
+                    usage + 1,
+                    in_project + usize::from(todo.project_id == project_id),
+                )
+            });
+        self.check(project_id, usage, project_usage)
+    }
+}
+
+#[async_trait]
+pub trait TodoRepository: Clone + std::marker::Send + std::marker::Sync + 'static {
+    async fn create(&self, payload: CreateTodo) -> anyhow::Result<Todo>;
+    async fn find(&self, id: i32) -> anyhow::Result<Todo>;
+    async fn all(
+        &self,
+        filter: &TodoFilter,
+        sort: TodoSort,
+        page: Page,
+    ) -> anyhow::Result<Vec<Todo>>;
+    /// `all` as a JSON array. Backends holding their todos in memory
+    /// serialize them in place rather than copy them out first.
+    async fn all_json(
+        &self,
+        filter: &TodoFilter,
+        sort: TodoSort,
+        page: Page,
+    ) -> anyhow::Result<Vec<u8>> {
+        let todos = self.all(filter, sort, page).await?;
+        Ok(serde_json::to_vec(&todos)?)
+    }
+    async fn count(&self, filter: &TodoFilter) -> anyhow::Result<usize>;
+    async fn update(&self, id: i32, payload: UpdateTodo) -> anyhow::Result<Todo>;
+    async fn delete(&self, id: i32) -> anyhow::Result<()>;
+    async fn snooze(&self, id: i32, until: Option<DateTime<Utc>>) -> anyhow::Result<Todo>;
+    async fn start_timer(&self, id: i32) -> anyhow::Result<TimeEntry>;
+    async fn stop_timer(&self, id: i32) -> anyhow::Result<TimeEntry>;
+    async fn time_entries(&self, id: i32) -> anyhow::Result<Vec<TimeEntry>>;
+    async fn start_pomodoro(&self, id: i32) -> anyhow::Result<Pomodoro>;
+    async fn interrupt_pomodoro(&self, pomodoro_id: i32) -> anyhow::Result<Pomodoro>;
+    async fn finish_pomodoro(&self, pomodoro_id: i32) -> anyhow::Result<Pomodoro>;
+    async fn pomodoro(&self, pomodoro_id: i32) -> anyhow::Result<Pomodoro>;
+    async fn pomodoros(&self, id: i32) -> anyhow::Result<Vec<Pomodoro>>;
+    /// The completed pomodoros on todos of user `user_id`, or on every todo
+    /// without one.
+    async fn completed_pomodoros(&self, user_id: Option<i32>) -> anyhow::Result<Vec<Pomodoro>>;
+    async fn create_label(&self, payload: CreateLabel) -> anyhow::Result<Label>;
+    async fn find_label(&self, id: i32) -> anyhow::Result<Label>;
+    /// The labels of user `user_id`, or every label without one, by name.
+    async fn all_labels(&self, user_id: Option<i32>) -> anyhow::Result<Vec<Label>>;
+    async fn update_label(&self, id: i32, payload: UpdateLabel) -> anyhow::Result<Label>;
+    /// Deletes the label and detaches it from every todo.
+    async fn delete_label(&self, id: i32) -> anyhow::Result<()>;
+    /// The unfinished todos that todo `id` is blocked by, by id.
+    async fn blockers(&self, id: i32) -> anyhow::Result<Vec<Todo>>;
+    /// The open todos located within `nearby`, closest first.
+    async fn nearby(&self, nearby: &Nearby) -> anyhow::Result<Vec<Todo>>;
+    async fn create_share_link(&self, link: ShareLink) -> anyhow::Result<ShareLink>;
+    /// The link with `token`, whether expired or not.
+    async fn find_share_link(&self, token: &str) -> anyhow::Result<Option<ShareLink>>;
+    /// Revokes the link with `token` to todo `id`.
+    async fn delete_share_link(&self, id: i32, token: &str) -> anyhow::Result<()>;
+    async fn create_project(&self, payload: CreateProject) -> anyhow::Result<Project>;
+    async fn find_project(&self, id: i32) -> anyhow::Result<Project>;
+    /// The projects of user `user_id` and the default project they share, or
+    /// every project without one, by id.
+    async fn all_projects(&self, user_id: Option<i32>) -> anyhow::Result<Vec<Project>>;
+    async fn update_project(&self, id: i32, payload: UpdateProject) -> anyhow::Result<Project>;
+    /// Moves todo `id` within the manual order, then numbers every todo from
+    /// 0 so that positions stay unique.
+    async fn reorder(&self, id: i32, payload: ReorderTodo) -> anyhow::Result<Todo>;
+    /// Deletes the project together with its todos. Fails with `Conflict` for
+    /// the default project, if a todo elsewhere is a subtask of one of them,
+    /// or if one of them belongs to someone other than the project's owner.
+    async fn delete_project(&self, id: i32) -> anyhow::Result<()>;
+}
+
+/// The accounts that may log in and their API keys. Every backend keeps them
+/// next to its todos.
+#[async_trait]
+pub trait UserRepository: Clone + std::marker::Send + std::marker::Sync + 'static {
+    /// Fails with `Conflict` if the name is taken.
+    async fn create_user(&self, payload: CreateUser) -> anyhow::Result<User>;
+    async fn find_user_by_name(&self, name: &str) -> anyhow::Result<Option<User>>;
+    async fn create_api_key(&self, payload: CreateApiKey) -> anyhow::Result<ApiKey>;
+    async fn find_api_key_by_hash(&self, key_hash: &str) -> anyhow::Result<Option<ApiKey>>;
+    /// The keys of user `user_id`, oldest first.
+    async fn api_keys(&self, user_id: i32) -> anyhow::Result<Vec<ApiKey>>;
+    /// Fails with `NotFound` unless key `id` belongs to user `user_id`.
+    async fn delete_api_key(&self, user_id: i32, id: i32) -> anyhow::Result<()>;
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, FromRow, ToSchema)]
+pub struct Todo {
+    id: i32,
+    text: String,
+    completed: bool,
+    /// Accumulated seconds of all stopped time entries.
+    time_spent: i64,
+    /// Hidden from default listings until this time has passed.
+    snoozed_until: Option<DateTime<Utc>>,
+    due_date: Option<DateTime<Utc>>,
+    #[serde(default)]
+    priority: Priority,
+    /// The todo this is a subtask of.
+    parent_id: Option<i32>,
+    #[serde(default = "default_project_id")]
+    project_id: i32,
+    /// The user who created it. Unset for todos from while authentication
+    /// was off, which no user sees once it is on.
+    #[serde(default)]
+    user_id: Option<i32>,
+    /// Place in the manual order, lowest first. New todos go last.
+    #[serde(default)]
+    position: i64,
+    /// iCalendar RRULE, e.g. `FREQ=WEEKLY;COUNT=4`. Completing the todo
+    /// creates the next occurrence.
+    #[serde(default)]
+    recurrence: Option<String>,
+    /// Where the todo is to be done, in degrees. Both or neither are set.
+    lat: Option<f64>,
+    lon: Option<f64>,
+    /// A name for the location, e.g. the shop to go to.
+    place: Option<String>,
+    /// When to send a reminder. Cleared once it has been sent.
+    remind_at: Option<DateTime<Utc>>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+    /// Attached labels, by name. The SQL backends keep them in `todo_labels`
+    /// and fill this in after loading the row.
+    #[serde(default)]
+    #[sqlx(default)]
+    #[schema(value_type = Vec<Label>)]
+    labels: Json<Vec<Label>>,
+    /// Ids of the todos this one waits for, ascending. The SQL backends keep
+    /// them in `todo_dependencies` like labels.
+    #[serde(default)]
+    #[sqlx(default)]
+    #[schema(value_type = Vec<i32>)]
+    blocked_by: Json<Vec<i32>>,
+}
+
+/// Stored as its rank, so the SQL backends can sort on it.
+#[derive(
+    Serialize,
+    Deserialize,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Clone,
+    Copy,
+    sqlx::Type,
+    ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+#[repr(i32)]
+pub enum Priority {
+    Low = 0,
+    #[default]
+    Medium = 1,
+    High = 2,
+    Urgent = 3,
+}
+
+fn default_project_id() -> i32 {
+    DEFAULT_PROJECT_ID
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, FromRow, ToSchema)]
+pub struct Project {
+    id: i32,
+    name: String,
+    /// The user who created it. Unset for the default project, which every
+    /// user shares, and for projects from while authentication was off.
+    #[serde(default)]
+    user_id: Option<i32>,
+}
+
+impl Project {
+    /// The project with `DEFAULT_PROJECT_ID`, as every backend creates it.
+    fn inbox() -> Self {
+        Self {
+            id: DEFAULT_PROJECT_ID,
+            name: DEFAULT_PROJECT_NAME.to_string(),
+            user_id: None,
+        }
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn user_id(&self) -> Option<i32> {
+        self.user_id
+    }
+
+    /// Whether it is in the listing of user `user_id`; see
+    /// `TodoRepository::all_projects`.
+    fn listed_for(&self, user_id: Option<i32>) -> bool {
+        user_id.is_none() || self.user_id == user_id || self.id == DEFAULT_PROJECT_ID
+    }
+}
+
+/// Serialized with its password hash, for the backends that store JSON; the
+/// API answers with `Account` instead.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, FromRow)]
+pub struct User {
+    id: i32,
+    name: String,
+    /// PHC string of the password, salt and parameters included.
+    password_hash: String,
+    created_at: DateTime<Utc>,
+}
+
+impl User {
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn password_hash(&self) -> &str {
+        &self.password_hash
+    }
+
+    pub fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+}
+
+/// A new user whose password has already been hashed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreateUser {
+    pub name: String,
+    pub password_hash: String,
+}
+
+impl CreateUser {
+    fn into_user(self, id: i32) -> User {
+        User {
+            id,
+            name: self.name,
+            password_hash: self.password_hash,
+            created_at: Utc::now(),
+        }
+    }
+}
+
+/// A long-lived credential standing in for a user's access token. Only the
+/// SHA-256 of the key is kept; the key itself is shown once, on creation.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, FromRow)]
+pub struct ApiKey {
+    id: i32,
+    user_id: i32,
+    name: String,
+    /// The start of the key, to tell keys apart without revealing them.
+    prefix: String,
+    /// Hex of the SHA-256 of the key.
+    key_hash: String,
+    created_at: DateTime<Utc>,
+}
+
+impl ApiKey {
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn user_id(&self) -> i32 {
+        self.user_id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    pub fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+}
+
+/// A new API key whose key has already been hashed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreateApiKey {
+    pub user_id: i32,
+    pub name: String,
+    pub prefix: String,
+    pub key_hash: String,
+}
+
+impl CreateApiKey {
+    fn into_api_key(self, id: i32) -> ApiKey {
+        ApiKey {
+            id,
+            user_id: self.user_id,
+            name: self.name,
+            prefix: self.prefix,
+            key_hash: self.key_hash,
+            created_at: Utc::now(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, FromRow, ToSchema)]
+pub struct Label {
+    id: i32,
+    name: String,
+    /// The user who created it. Unset for labels from while authentication
+    /// was off, which no user sees once it is on.
+    #[serde(default)]
+    user_id: Option<i32>,
+}
+
+impl Label {
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn user_id(&self) -> Option<i32> {
+        self.user_id
+    }
+}
+
+/// What a label name is claimed under, for the backends that keep names
+/// unique themselves. Each user has names of their own. A label without an
+/// owner keeps its bare name, so that names claimed before owners still
+/// count; the prefix of the others is never valid UTF-8.
+fn label_name_key(user_id: Option<i32>, name: &str) -> Vec<u8> {
+    match user_id {
+        Some(user_id) => [&[0xff][..], &user_id.to_be_bytes(), name.as_bytes()].concat(),
+        None => name.as_bytes().to_vec(),
+    }
+}
+
+/// A label attached to a todo, as joined from `todo_labels`.
+#[derive(Debug, FromRow)]
+struct LabelLink {
+    todo_id: i32,
+    id: i32,
+    name: String,
+    user_id: Option<i32>,
+}
+
+/// Hands `links` to the todos they belong to.
+fn attach_labels(todos: &mut [Todo], links: Vec<LabelLink>) {
+    let mut labels: HashMap<i32, Vec<Label>> = HashMap::new();
+    for link in links {
+        labels.entry(link.todo_id).or_default().push(Label {
+            id: link.id,
+            name: link.name,
+            user_id: link.user_id,
+        });
+    }
+    for todo in todos {
+        todo.set_labels(labels.remove(&todo.id).unwrap_or_default());
+    }
+}
+
+/// A dependency of a todo, as stored in `todo_dependencies`.
+#[derive(Debug, FromRow)]
+struct DependencyLink {
+    todo_id: i32,
+    blocker_id: i32,
+}
+
+/// Hands `links` to the todos that are blocked by them.
+fn attach_blockers(todos: &mut [Todo], links: Vec<DependencyLink>) {
+    let mut blocked_by: HashMap<i32, Vec<i32>> = HashMap::new();
+    for link in links {
+        blocked_by
+            .entry(link.todo_id)
+            .or_default()
+            .push(link.blocker_id);
+    }
+    for todo in todos {
+        todo.set_blocked_by(blocked_by.remove(&todo.id).unwrap_or_default());
+    }
+}
+
+/// Fails with `NotFound` if a todo in `blocked_by` is not among `todos`, or
+/// with `Conflict` if todo `id` would end up waiting for itself.
+fn check_blocked_by<'a>(
+    id: i32,
+    blocked_by: &[i32],
+    todos: impl IntoIterator<Item = &'a Todo>,
+) -> anyhow::Result<()> {
+    let edges: HashMap<i32, &[i32]> = todos
+        .into_iter()
+        .map(|todo| (todo.id, todo.blocked_by.as_slice()))
+        .collect();
+    if let Some(missing) = blocked_by.iter().find(|id| !edges.contains_key(id)) {
+        return Err(RepositoryError::NotFound(*missing).into());
+    }
+
+    let mut pending = blocked_by.to_vec();
+    let mut seen = HashSet::new();
+    while let Some(blocker) = pending.pop() {
+        if blocker == id {
+            return Err(
+                RepositoryError::Conflict(format!("dependency cycle, id is {}", id)).into(),
+            );
+        }
+        if seen.insert(blocker) {
+            pending.extend_from_slice(edges.get(&blocker).copied().unwrap_or_default());
+        }
+    }
+    Ok(())
+}
+
+/// Fails with `Conflict` for the default project, which must always exist.
+fn check_project_deletable(id: i32) -> anyhow::Result<()> {
+    if id == DEFAULT_PROJECT_ID {
+        return Err(RepositoryError::Conflict(format!(
+            "default project can not be deleted, id is {}",
+            id
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+/// The ids of the todos in `project` among `todos`, subtasks before their
+/// parents so that they can be deleted one by one. Fails with `Conflict` if a
+/// todo in another project is a subtask of one of them, or if one of them
+/// belongs to someone other than the project's owner.
+fn project_todo_ids<'a>(
+    project: &Project,
+    todos: impl IntoIterator<Item = &'a Todo>,
+) -> anyhow::Result<Vec<i32>> {
+    let id = project.id;
+    let (inside, outside): (Vec<&Todo>, Vec<&Todo>) =
+        todos.into_iter().partition(|todo| todo.project_id == id);
+    if inside.iter().any(|todo| todo.user_id != project.user_id) {
+        return Err(foreign_project_todos(id).into());
+    }
+    let mut ids: Vec<i32> = inside.iter().map(|todo| todo.id).collect();
+    if outside
+        .iter()
+        .filter_map(|todo| todo.parent_id)
+        .any(|parent_id| ids.contains(&parent_id))
+    {
+        return Err(RepositoryError::Conflict(format!(
+            "project todos have subtasks elsewhere, id is {}",
+            id
+        ))
+        .into());
+    }
+    // A subtask is always created after its parent, so it has the larger id.
+    ids.sort_unstable_by(|a, b| b.cmp(a));
+    Ok(ids)
+}
+
+fn foreign_project_todos(id: i32) -> RepositoryError {
+    RepositoryError::Conflict(format!("project holds todos of other users, id is {}", id))
+}
+
+/// Narrows down `TodoRepository::all`. The default matches every todo.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TodoFilter {
+    /// Only open todos that have not been updated since this time.
+    pub stale_before: Option<DateTime<Utc>>,
+    /// Only todos that are not snoozed at this time.
+    pub awake_at: Option<DateTime<Utc>>,
+    /// Only todos with a smaller id. Listings are newest first, so this is
+    /// the keyset that resumes a listing after a given todo.
+    pub before_id: Option<i32>,
+    /// Only todos in this completion state.
+    pub completed: Option<bool>,
+    /// Only todos whose text contains this, ignoring case.
+    pub text_contains: Option<String>,
+    /// Only todos with the label of this name.
+    pub label: Option<String>,
+    /// Only todos due before this time.
+    pub due_before: Option<DateTime<Utc>>,
+    /// Only todos due after this time.
+    pub due_after: Option<DateTime<Utc>>,
+    /// Only open todos that are past due at this time.
+    pub overdue_at: Option<DateTime<Utc>>,
+    pub priority: Option<Priority>,
+    /// Only direct subtasks of this todo.
+    pub parent_id: Option<i32>,
+    pub project_id: Option<i32>,
+    /// Only todos updated after this time.
+    pub updated_after: Option<DateTime<Utc>>,
+    /// Only open todos whose reminder is due at this time.
+    pub reminder_due_at: Option<DateTime<Utc>>,
+    /// Only todos with one of these ids.
+    pub ids: Option<Vec<i32>>,
+    /// Only todos of this user.
+    pub user_id: Option<i32>,
+}
+
+impl TodoFilter {
+    pub fn matches(&self, todo: &Todo) -> bool {
+        let stale = self
+            .stale_before
+            .map(|stale_before| !todo.completed && todo.updated_at < stale_before)
+            .unwrap_or(true);
+        let awake = self
+            .awake_at
+            .map(|awake_at| !todo.is_snoozed(awake_at))
+            .unwrap_or(true);
+        let before = self.before_id.map(|id| todo.id < id).unwrap_or(true);
+        let completed = self
+            .completed
+            .map(|completed| todo.completed == completed)
+            .unwrap_or(true);
+        let text = self
+            .text_contains
+            .as_ref()
+            .map(|query| todo.text.to_lowercase().contains(&query.to_lowercase()))
+            .unwrap_or(true);
+        let label = self
+            .label
+            .as_ref()
+            .map(|name| todo.labels.iter().any(|label| &label.name == name))
+            .unwrap_or(true);
+        let due_before = self
+            .due_before
+            .map(|due_before| todo.due_date.is_some_and(|due_date| due_date < due_before))
+            .unwrap_or(true);
+        let due_after = self
+            .due_after
+            .map(|due_after| todo.due_date.is_some_and(|due_date| due_date > due_after))
+            .unwrap_or(true);
+        let overdue = self
+            .overdue_at
+            .map(|now| !todo.completed && todo.due_date.is_some_and(|due_date| due_date < now))
+            .unwrap_or(true);
+        let priority = self
+            .priority
+            .map(|priority| todo.priority == priority)
+            .unwrap_or(true);
+        let parent = self
+            .parent_id
+            .map(|parent_id| todo.parent_id == Some(parent_id))
+            .unwrap_or(true);
+        let project = self
+            .project_id
+            .map(|project_id| todo.project_id == project_id)
+            .unwrap_or(true);
+        let updated = self
+            .updated_after
+            .map(|updated_after| todo.updated_at > updated_after)
+            .unwrap_or(true);
+        let reminder = self
+            .reminder_due_at
+            .map(|now| !todo.completed && todo.remind_at.is_some_and(|remind_at| remind_at <= now))
+            .unwrap_or(true);
+        let id = self
+            .ids
+            .as_ref()
+            .map(|ids| ids.contains(&todo.id))
+            .unwrap_or(true);
+        let user = self
+            .user_id
+            .map(|user_id| todo.user_id == Some(user_id))
+            .unwrap_or(true);
+        stale
+            && awake
+            && before
+            && completed
+            && text
+            && label
+            && due_before
+            && due_after
+            && overdue
+            && priority
+            && parent
+            && project
+            && updated
+            && reminder
+            && id
+            && user
+    }
+
+    /// `text_contains` as a `LIKE` pattern, with the wildcards in it escaped
+    /// by `\`.
+    fn text_pattern(&self) -> Option<String> {
+        self.text_contains.as_ref().map(|query| {
+            let escaped = query
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_");
+            format!("%{}%", escaped)
+        })
+    }
+
+    /// `ids` as a JSON array, for SQLite to unpack with `json_each`.
+    fn ids_json(&self) -> Option<String> {
+        self.ids
+            .as_ref()
+            .map(|ids| serde_json::to_string(ids).expect("ids serialize"))
+    }
+}
+
+/// Mean radius of the earth, for distances between todo locations.
+const EARTH_RADIUS_METRES: f64 = 6_371_000.0;
+
+/// A circle of `radius` metres around `lat`/`lon`, as searched by
+/// `TodoRepository::nearby`.
+#[derive(Debug, Clone, Copy, Deserialize, Validate)]
+pub struct Nearby {
+    #[validate(range(min = -90.0, max = 90.0, message = "Out of latitude range"))]
+    lat: f64,
+    #[validate(range(min = -180.0, max = 180.0, message = "Out of longitude range"))]
+    lon: f64,
+    #[validate(range(min = 1.0, max = 100000.0, message = "Out of radius range"))]
+    radius: f64,
+}
+
+impl Nearby {
+    /// Great-circle distance from the centre to `todo` by the haversine
+    /// formula, if it has a location.
+    fn distance_to(&self, todo: &Todo) -> Option<f64> {
+        let (lat, lon) = (todo.lat?, todo.lon?);
+        let d_lat = (lat - self.lat).to_radians();
+        let d_lon = (lon - self.lon).to_radians();
+        let a = (d_lat / 2.0).sin().powi(2)
+            + self.lat.to_radians().cos() * lat.to_radians().cos() * (d_lon / 2.0).sin().powi(2);
+        Some(2.0 * EARTH_RADIUS_METRES * a.sqrt().min(1.0).asin())
+    }
+
+    /// Latitude and longitude ranges that enclose the circle, for the SQL
+    /// backends to narrow down on before `select`. Every longitude is taken
+    /// once the circle reaches a pole or the antimeridian.
+    fn bounds(&self) -> ((f64, f64), (f64, f64)) {
+        let d_lat = (self.radius / EARTH_RADIUS_METRES).to_degrees();
+        let lat = (self.lat - d_lat, self.lat + d_lat);
+        let widest = lat.0.abs().max(lat.1.abs());
+        if widest >= 90.0 {
+            return (lat, (-180.0, 180.0));
+        }
+        let d_lon = d_lat / widest.to_radians().cos();
+        let lon = (self.lon - d_lon, self.lon + d_lon);
+        if lon.0 < -180.0 || lon.1 > 180.0 {
+            return (lat, (-180.0, 180.0));
+        }
+        (lat, lon)
+    }
+
+    /// The open todos of `todos` inside the circle, closest first.
+    fn select(&self, todos: impl IntoIterator<Item = Todo>) -> Vec<Todo> {
+        let mut found: Vec<(f64, Todo)> = todos
+            .into_iter()
+            .filter(|todo| !todo.completed)
+            .filter_map(|todo| {
+                let distance = self.distance_to(&todo)?;
+                (distance <= self.radius).then_some((distance, todo))
+            })
+            .collect();
+        found.sort_by(|(a, x), (b, y)| a.total_cmp(b).then(x.id.cmp(&y.id)));
+        found.into_iter().map(|(_, todo)| todo).collect()
+    }
+}
+
+/// Order of `TodoRepository::all`. The default is newest first.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TodoSort {
+    pub field: SortField,
+    pub order: SortOrder,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SortField {
+    #[default]
+    Id,
+    CreatedAt,
+    Text,
+    Priority,
+    /// The manual order.
+    Position,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+impl TodoSort {
+    /// Ties on `field` are broken by id, in the same direction.
+    fn compare(&self, a: &Todo, b: &Todo) -> Ordering {
+        let ordering = match self.field {
+            SortField::Id => a.id.cmp(&b.id),
+            SortField::CreatedAt => a.created_at.cmp(&b.created_at),
+            SortField::Text => a.text.cmp(&b.text),
+            SortField::Priority => a.priority.cmp(&b.priority),
+            SortField::Position => a.position.cmp(&b.position),
+        }
+        .then(a.id.cmp(&b.id));
+        match self.order {
+            SortOrder::Asc => ordering,
+            SortOrder::Desc => ordering.reverse(),
+        }
+    }
+
+    /// The SQL `order by` clause, built only from known column names.
+    fn order_by(&self) -> String {
+        let column = match self.field {
+            SortField::Id => "id",
+            SortField::CreatedAt => "created_at",
+            SortField::Text => "text",
+            SortField::Priority => "priority",
+            SortField::Position => "position",
+        };
+        let order = match self.order {
+            SortOrder::Asc => "asc",
+            SortOrder::Desc => "desc",
+        };
+        format!("{0} {1}, id {1}", column, order)
+    }
+}
+
+/// A slice of a listing. The default is the whole listing.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Page {
+    pub limit: Option<usize>,
+    pub offset: usize,
+}
+
+impl Page {
+    fn apply<I: Iterator>(&self, items: I) -> impl Iterator<Item = I::Item> {
+        items
+            .skip(self.offset)
+            .take(self.limit.unwrap_or(usize::MAX))
+    }
+}
+
+impl Todo {
+    pub fn new(id: i32, text: String) -> Self {
+        let now = Utc::now();
+        Self {
+            id,
+            text,
+            completed: false,
+            time_spent: 0,
+            snoozed_until: None,
+            due_date: None,
+            priority: Priority::default(),
+            parent_id: None,
+            project_id: DEFAULT_PROJECT_ID,
+            user_id: None,
+            // Ids only grow past every position handed out so far, since
+            // reordering numbers the todos from 0.
+            position: id.into(),
+            recurrence: None,
+            lat: None,
+            lon: None,
+            place: None,
+            remind_at: None,
+            created_at: now,
+            updated_at: now,
+            labels: Json(Vec::new()),
+            blocked_by: Json(Vec::new()),
+        }
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn due_date(&self) -> Option<DateTime<Utc>> {
+        self.due_date
+    }
+
+    pub fn parent_id(&self) -> Option<i32> {
+        self.parent_id
+    }
+
+    pub fn user_id(&self) -> Option<i32> {
+        self.user_id
+    }
+
+    pub fn project_id(&self) -> i32 {
+        self.project_id
+    }
+
+    pub fn is_completed(&self) -> bool {
+        self.completed
+    }
+
+    pub fn time_spent(&self) -> i64 {
+        self.time_spent
+    }
+
+    pub fn snoozed_until(&self) -> Option<DateTime<Utc>> {
+        self.snoozed_until
+    }
+
+    pub fn priority(&self) -> Priority {
+        self.priority
+    }
+
+    pub fn position(&self) -> i64 {
+        self.position
+    }
+
+    pub fn recurrence(&self) -> Option<&str> {
+        self.recurrence.as_deref()
+    }
+
+    pub fn location(&self) -> Option<(f64, f64)> {
+        self.lat.zip(self.lon)
+    }
+
+    pub fn place(&self) -> Option<&str> {
+        self.place.as_deref()
+    }
+
+    pub fn remind_at(&self) -> Option<DateTime<Utc>> {
+        self.remind_at
+    }
+
+    pub fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    pub fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at
+    }
+
+    pub fn labels(&self) -> &[Label] {
+        &self.labels
+    }
+
+    pub fn blocked_by(&self) -> &[i32] {
+        &self.blocked_by
+    }
+
+    pub fn is_snoozed(&self, now: DateTime<Utc>) -> bool {
+        self.snoozed_until
+            .map(|snoozed_until| snoozed_until > now)
+            .unwrap_or(false)
+    }
+
+    fn set_labels(&mut self, mut labels: Vec<Label>) {
+        labels.sort_by(|a, b| a.name.cmp(&b.name).then(a.id.cmp(&b.id)));
+        labels.dedup_by_key(|label| label.id);
+        self.labels = Json(labels);
+    }
+
+    fn set_blocked_by(&mut self, mut blocked_by: Vec<i32>) {
+        blocked_by.sort_unstable();
+        blocked_by.dedup();
+        self.blocked_by = Json(blocked_by);
+    }
+
+    /// The todo to create when this one gets completed: a copy due at the
+    /// next occurrence after its due date, or after `now` if it has none,
+    /// with a reminder just as long before it. `None` unless it recurs and
+    /// its rule has occurrences left.
+    fn next_occurrence(&self, now: DateTime<Utc>) -> Option<CreateTodo> {
+        let recurrence: Recurrence = self.recurrence.as_deref()?.parse().ok()?;
+        let (due_date, recurrence) = recurrence.next(self.due_date.unwrap_or(now))?;
+        Some(CreateTodo {
+            text: self.text.clone(),
+            label_ids: self.labels.iter().map(|label| label.id).collect(),
+            due_date: Some(due_date),
+            priority: self.priority,
+            parent_id: self.parent_id,
+            blocked_by: Vec::new(),
+            project_id: self.project_id,
+            user_id: self.user_id,
+            limits: TodoLimits::default(),
+            recurrence: Some(recurrence.to_string()),
+            lat: self.lat,
+            lon: self.lon,
+            place: self.place.clone(),
+            remind_at: self
+                .remind_at
+                .zip(self.due_date)
+                .map(|(remind_at, old_due_date)| due_date - (old_due_date - remind_at)),
+        })
+    }
+
+    /// Drops todo `id` from the blockers. Returns whether it was one.
+    fn unblock(&mut self, id: i32) -> bool {
+        let blocked = self.blocked_by.contains(&id);
+        self.blocked_by.retain(|blocker| *blocker != id);
+        blocked
+    }
+
+    /// Brings the copy of label `id` up to date, where a deleted label is
+    /// `None`. Returns whether this todo carries the label at all.
+    fn replace_label(&mut self, id: i32, label: Option<&Label>) -> bool {
+        if !self.labels.iter().any(|label| label.id == id) {
+            return false;
+        }
+        let labels = self
+            .labels
+            .iter()
+            .filter(|label| label.id != id)
+            .cloned()
+            .chain(label.cloned())
+            .collect();
+        self.set_labels(labels);
+        true
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, FromRow)]
+pub struct TimeEntry {
+    id: i32,
+    todo_id: i32,
+    started_at: DateTime<Utc>,
+    stopped_at: Option<DateTime<Utc>>,
+}
+
+/// A fixed-length focus session on a todo. Finishing it after `ends_at`
+/// marks it completed and logs it as a time entry; finishing it earlier
+/// abandons it without logging time.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, FromRow)]
+pub struct Pomodoro {
+    id: i32,
+    todo_id: i32,
+    started_at: DateTime<Utc>,
+    ends_at: DateTime<Utc>,
+    interruptions: i32,
+    finished_at: Option<DateTime<Utc>>,
+    completed: bool,
+}
+
+/// Read-only access to a todo for anyone who knows `token`.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, FromRow)]
+pub struct ShareLink {
+    token: String,
+    todo_id: i32,
+    /// Never expires when absent.
+    expires_at: Option<DateTime<Utc>>,
+    created_at: DateTime<Utc>,
+}
+
+impl ShareLink {
+    /// A link with a fresh random token.
+    pub fn new(todo_id: i32, expires_at: Option<DateTime<Utc>>) -> Self {
+        let token: [u8; SHARE_TOKEN_BYTES] = rand::random();
+        Self {
+            token: hex::encode(token),
+            todo_id,
+            expires_at,
+            created_at: Utc::now(),
+        }
+    }
+
+    pub fn todo_id(&self) -> i32 {
+        self.todo_id
+    }
+
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at
+            .map(|expires_at| expires_at <= now)
+            .unwrap_or(false)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Validate, ToSchema)]
+#[validate(schema(function = "validate_create_location"))]
+pub struct CreateTodo {
+    #[validate(length(min = 1, message = "Can not be empty"))]
+    #[validate(custom = "validate_text_length")]
+    text: String,
+    /// Ids of the labels to attach.
+    #[serde(default)]
+    label_ids: Vec<i32>,
+    /// RFC 3339, like every other timestamp.
+    due_date: Option<DateTime<Utc>>,
+    #[serde(default)]
+    priority: Priority,
+    /// Makes this a subtask. Fixed once created, so the hierarchy can not
+    /// form a cycle.
+    parent_id: Option<i32>,
+    /// Ids of the todos that must be completed first.
+    #[serde(default)]
+    blocked_by: Vec<i32>,
+    #[serde(default = "default_project_id")]
+    project_id: i32,
+    /// Set from the authenticated user, never by the client.
+    #[serde(skip)]
+    user_id: Option<i32>,
+    /// Set from the deployment, never by the client. Todos created by
+    /// recurrence are not limited.
+    #[serde(skip)]
+    limits: TodoLimits,
+    #[validate(custom = "validate_recurrence")]
+    recurrence: Option<String>,
+    #[validate(range(min = -90.0, max = 90.0, message = "Out of latitude range"))]
+    lat: Option<f64>,
+    #[validate(range(min = -180.0, max = 180.0, message = "Out of longitude range"))]
+    lon: Option<f64>,
+    #[validate(length(min = 1, message = "Can not be empty"))]
+    #[validate(length(max = 100, message = "Over place length"))]
+    place: Option<String>,
+    remind_at: Option<DateTime<Utc>>,
+}
+
+impl CreateTodo {
+    pub fn new(text: String) -> Self {
+        Self {
+            text,
+            label_ids: Vec::new(),
+            due_date: None,
+            priority: Priority::default(),
+            parent_id: None,
+            blocked_by: Vec::new(),
+            project_id: DEFAULT_PROJECT_ID,
+            user_id: None,
+            limits: TodoLimits::default(),
+            recurrence: None,
+            lat: None,
+            lon: None,
+            place: None,
+            remind_at: None,
+        }
+    }
+
+    pub fn with_user(self, user_id: Option<i32>) -> Self {
+        Self { user_id, ..self }
+    }
+
+    pub fn with_limits(self, limits: TodoLimits) -> Self {
+        Self { limits, ..self }
+    }
+
+    pub fn project_id(&self) -> i32 {
+        self.project_id
+    }
+
+    pub fn label_ids(&self) -> &[i32] {
+        &self.label_ids
+    }
+
+    /// The todos this one points at, as parent or blocker.
+    pub fn linked_ids(&self) -> impl Iterator<Item = i32> + '_ {
+        self.parent_id
+            .into_iter()
+            .chain(self.blocked_by.iter().copied())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Validate, ToSchema)]
+#[validate(schema(function = "validate_update_location"))]
+pub struct UpdateTodo {
+    #[validate(length(min = 1, message = "Can not be empty"))]
+    #[validate(custom = "validate_text_length")]
+    text: Option<String>,
+    completed: Option<bool>,
+    /// Replaces all attached labels when given.
+    label_ids: Option<Vec<i32>>,
+    /// `null` clears the due date; leaving it out keeps it.
+    #[serde(default, deserialize_with = "present")]
+    #[schema(value_type = Option<DateTime<Utc>>)]
+    due_date: Option<Option<DateTime<Utc>>>,
+    priority: Option<Priority>,
+    /// Replaces all blockers when given.
+    blocked_by: Option<Vec<i32>>,
+    /// Moves the todo to this project.
+    project_id: Option<i32>,
+    /// `null` stops the todo from recurring; leaving it out keeps it.
+    #[serde(default, deserialize_with = "present")]
+    #[validate(custom = "validate_recurrence")]
+    #[schema(value_type = Option<String>)]
+    recurrence: Option<Option<String>>,
+    /// `null` for both clears the location; leaving them out keeps it.
+    #[serde(default, deserialize_with = "present")]
+    #[validate(range(min = -90.0, max = 90.0, message = "Out of latitude range"))]
+    #[schema(value_type = Option<f64>)]
+    lat: Option<Option<f64>>,
+    #[serde(default, deserialize_with = "present")]
+    #[validate(range(min = -180.0, max = 180.0, message = "Out of longitude range"))]
+    #[schema(value_type = Option<f64>)]
+    lon: Option<Option<f64>>,
+    #[serde(default, deserialize_with = "present")]
+    #[validate(length(min = 1, message = "Can not be empty"))]
+    #[validate(length(max = 100, message = "Over place length"))]
+    #[schema(value_type = Option<String>)]
+    place: Option<Option<String>>,
+    /// `null` cancels the reminder; leaving it out keeps it.
+    #[serde(default, deserialize_with = "present")]
+    #[schema(value_type = Option<DateTime<Utc>>)]
+    remind_at: Option<Option<DateTime<Utc>>>,
+}
+
+impl UpdateTodo {
+    /// An update that only clears the reminder, once it has been sent.
+    pub fn clear_reminder() -> Self {
+        Self {
+            text: None,
+            completed: None,
+            label_ids: None,
+            due_date: None,
+            priority: None,
+            blocked_by: None,
+            project_id: None,
+            recurrence: None,
+            lat: None,
+            lon: None,
+            place: None,
+            remind_at: Some(None),
+        }
+    }
+
+    pub fn completes(&self) -> bool {
+        self.completed == Some(true)
+    }
+
+    pub fn blocked_by(&self) -> Option<&[i32]> {
+        self.blocked_by.as_deref()
+    }
+
+    pub fn project_id(&self) -> Option<i32> {
+        self.project_id
+    }
+
+    pub fn label_ids(&self) -> Option<&[i32]> {
+        self.label_ids.as_deref()
+    }
+}
+
+/// Wraps whatever is given in `Some`, so that together with
+/// `#[serde(default)]` an explicit `null` is told apart from a missing field.
+fn present<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+/// Files an inbox todo in one go: the project is required, priority and due
+/// date are set only when given.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Validate)]
+pub struct TriageTodo {
+    project_id: i32,
+    priority: Option<Priority>,
+    /// `null` clears the due date; leaving it out keeps it.
+    #[serde(default, deserialize_with = "present")]
+    due_date: Option<Option<DateTime<Utc>>>,
+}
+
+impl From<TriageTodo> for UpdateTodo {
+    fn from(triage: TriageTodo) -> Self {
+        Self {
+            text: None,
+            completed: None,
+            label_ids: None,
+            due_date: triage.due_date,
+            priority: triage.priority,
+            blocked_by: None,
+            project_id: Some(triage.project_id),
+            recurrence: None,
+            lat: None,
+            lon: None,
+            place: None,
+            remind_at: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Validate, ToSchema)]
+pub struct CreateLabel {
+    #[validate(length(min = 1, message = "Can not be empty"))]
+    #[validate(length(max = 30, message = "Over name length"))]
+    name: String,
+    /// Set from the authenticated user, never by the client.
+    #[serde(skip)]
+    user_id: Option<i32>,
+}
+
+impl CreateLabel {
+    pub fn with_user(self, user_id: Option<i32>) -> Self {
+        Self { user_id, ..self }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Validate, ToSchema)]
+pub struct UpdateLabel {
+    #[validate(length(min = 1, message = "Can not be empty"))]
+    #[validate(length(max = 30, message = "Over name length"))]
+    name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Validate, ToSchema)]
+pub struct CreateProject {
+    #[validate(length(min = 1, message = "Can not be empty"))]
+    #[validate(length(max = 50, message = "Over name length"))]
+    name: String,
+    /// Set from the authenticated user, never by the client.
+    #[serde(skip)]
+    user_id: Option<i32>,
+}
+
+impl CreateProject {
+    pub fn with_user(self, user_id: Option<i32>) -> Self {
+        Self { user_id, ..self }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Validate, ToSchema)]
+pub struct UpdateProject {
+    #[validate(length(min = 1, message = "Can not be empty"))]
+    #[validate(length(max = 50, message = "Over name length"))]
+    name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Validate)]
+pub struct CreateShareLink {
+    /// Never expires when absent.
+    #[validate(range(min = 1, max = 8760, message = "Out of expiry range"))]
+    expires_in_hours: Option<i64>,
+}
+
+impl CreateShareLink {
+    pub fn expires_at(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.expires_in_hours
+            .map(|hours| now + Duration::hours(hours))
+    }
+}
+
+/// Either a target `index` in the manual order or the id of the todo to
+/// follow, exactly one of them.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Validate)]
+#[validate(schema(function = "validate_reorder"))]
+pub struct ReorderTodo {
+    /// Counted from 0; past the end means last.
+    index: Option<usize>,
+    after_id: Option<i32>,
+}
+
+fn validate_reorder(reorder: &ReorderTodo) -> Result<(), ValidationError> {
+    match (reorder.index, reorder.after_id) {
+        (Some(_), None) | (None, Some(_)) => Ok(()),
+        _ => {
+            let mut error = ValidationError::new("reorder");
+            error.message = Some(Cow::from("Specify exactly one of index or after_id"));
+            Err(error)
+        }
+    }
+}
+
+/// The new positions after moving todo `id` as `payload` says, for just the
+/// todos among `todos` whose position changes. Each user has an order of
+/// their own, so only the todos of the one whose todo moves take part.
+fn reorder_positions<'a>(
+    id: i32,
+    payload: &ReorderTodo,
+    todos: impl IntoIterator<Item = &'a Todo>,
+) -> anyhow::Result<Vec<(i32, i64)>> {
+    let mut todos: Vec<&Todo> = todos.into_iter().collect();
+    let user_id = todos
+        .iter()
+        .find(|todo| todo.id == id)
+        .ok_or(RepositoryError::NotFound(id))?
+        .user_id;
+    todos.retain(|todo| todo.user_id == user_id);
+    todos.sort_by_key(|todo| (todo.position, todo.id));
+    let from = todos
+        .iter()
+        .position(|todo| todo.id == id)
+        .ok_or(RepositoryError::NotFound(id))?;
+    let todo = todos.remove(from);
+    let to = match (payload.index, payload.after_id) {
+        (_, Some(after_id)) if after_id == id => from,
+        (_, Some(after_id)) => {
+            todos
+                .iter()
+                .position(|todo| todo.id == after_id)
+                .ok_or(RepositoryError::NotFound(after_id))?
+                + 1
+        }
+        (Some(index), None) => index.min(todos.len()),
+        (None, None) => from,
+    };
+    todos.insert(to, todo);
+
+    Ok(todos
+        .iter()
+        .zip(0..)
+        .filter(|(todo, position)| todo.position != *position)
+        .map(|(todo, position)| (todo.id, position))
+        .collect())
+}
+
+/// Either a relative `minutes` or an absolute `until`, exactly one of them.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Validate)]
+#[validate(schema(function = "validate_snooze"))]
+pub struct SnoozeTodo {
+    #[validate(range(min = 1, max = 525600, message = "Out of snooze range"))]
+    minutes: Option<i64>,
+    until: Option<DateTime<Utc>>,
+}
+
+impl SnoozeTodo {
+    pub fn until(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        match (self.minutes, self.until) {
+            (Some(minutes), _) => now + Duration::minutes(minutes),
+            (None, Some(until)) => until,
+            (None, None) => now,
+        }
+    }
+}
+
+fn validate_snooze(snooze: &SnoozeTodo) -> Result<(), ValidationError> {
+    match (snooze.minutes, snooze.until) {
+        (Some(_), None) => Ok(()),
+        (None, Some(until)) if until > Utc::now() => Ok(()),
+        (None, Some(_)) => {
+            let mut error = ValidationError::new("until");
+            error.message = Some(Cow::from("Must be in the future"));
+            Err(error)
+        }
+        _ => {
+            let mut error = ValidationError::new("snooze");
+            error.message = Some(Cow::from("Specify exactly one of minutes or until"));
+            Err(error)
+        }
+    }
+}
+
+fn validate_text_length(text: &str) -> Result<(), ValidationError> {
+    if text.graphemes(true).count() > TEXT_MAX_GRAPHEMES {
+        let mut error = ValidationError::new("length");
+        error.message = Some(Cow::from("Over text length"));
+        error.add_param(Cow::from("max"), &TEXT_MAX_GRAPHEMES);
+        return Err(error);
+    }
+    if text.len() > TEXT_MAX_BYTES {
+        let mut error = ValidationError::new("bytes");
+        error.message = Some(Cow::from("Over text byte length"));
+        error.add_param(Cow::from("max"), &TEXT_MAX_BYTES);
+        return Err(error);
+    }
+
+    Ok(())
+}
+
+fn location_error() -> ValidationError {
+    let mut error = ValidationError::new("location");
+    error.message = Some(Cow::from("Specify both or neither of lat and lon"));
+    error
+}
+
+fn validate_create_location(todo: &CreateTodo) -> Result<(), ValidationError> {
+    if todo.lat.is_some() != todo.lon.is_some() {
+        return Err(location_error());
+    }
+    Ok(())
+}
+
+fn validate_update_location(todo: &UpdateTodo) -> Result<(), ValidationError> {
+    match (todo.lat, todo.lon) {
+        (None, None) | (Some(None), Some(None)) | (Some(Some(_)), Some(Some(_))) => Ok(()),
+        _ => Err(location_error()),
+    }
+}
+
+fn validate_recurrence(rule: &str) -> Result<(), ValidationError> {
+    rule.parse::<Recurrence>().map(|_| ()).map_err(|reason| {
+        let mut error = ValidationError::new("recurrence");
+        error.message = Some(Cow::from(reason));
+        error
+    })
+}
+
+impl TimeEntry {
+    /// Length of a stopped entry in whole seconds; zero while still running.
+    fn duration(&self) -> i64 {
+        self.stopped_at
+            .map(|stopped_at| (stopped_at - self.started_at).num_seconds())
+            .unwrap_or(0)
+    }
+}
+
+impl Pomodoro {
+    pub fn todo_id(&self) -> i32 {
+        self.todo_id
+    }
+
+    pub fn finished_at(&self) -> Option<DateTime<Utc>> {
+        self.finished_at
+    }
+
+    /// Planned length of the session in whole seconds.
+    fn duration(&self) -> i64 {
+        (self.ends_at - self.started_at).num_seconds()
+    }
+}
+
+#[cfg(test)]
+pub mod test_utils {
+    use super::*;
+
+    impl Todo {
+        /// Copies the timestamps assigned by the repository from `other`, so an
+        /// expected todo can be compared with one that was actually stored.
+        pub fn with_timestamps_of(self, other: &Todo) -> Self {
+            Self {
+                created_at: other.created_at,
+                updated_at: other.updated_at,
+                ..self
+            }
+        }
+    }
+
+    impl UpdateTodo {
+        pub fn new(text: Option<String>, completed: Option<bool>) -> Self {
+            Self {
+                text,
+                completed,
+                label_ids: None,
+                due_date: None,
+                priority: None,
+                blocked_by: None,
+                project_id: None,
+                recurrence: None,
+                lat: None,
+                lon: None,
+                place: None,
+                remind_at: None,
+            }
+        }
+    }
+
+    impl CreateTodo {
+        pub fn with_labels(self, label_ids: Vec<i32>) -> Self {
+            Self { label_ids, ..self }
+        }
+
+        pub fn with_blockers(self, blocked_by: Vec<i32>) -> Self {
+            Self { blocked_by, ..self }
+        }
+
+        pub fn with_parent(self, parent_id: i32) -> Self {
+            Self {
+                parent_id: Some(parent_id),
+                ..self
+            }
+        }
+
+        pub fn with_project(self, project_id: i32) -> Self {
+            Self { project_id, ..self }
+        }
+
+        pub fn with_location(self, lat: f64, lon: f64) -> Self {
+            Self {
+                lat: Some(lat),
+                lon: Some(lon),
+                ..self
+            }
+        }
+
+        pub fn with_reminder(self, remind_at: DateTime<Utc>) -> Self {
+            Self {
+                remind_at: Some(remind_at),
+                ..self
+            }
+        }
+
+        pub fn with_recurrence(self, rule: &str) -> Self {
+            Self {
+                recurrence: Some(rule.to_string()),
+                ..self
+            }
+        }
+    }
+
+    impl ReorderTodo {
+        pub fn to_index(index: usize) -> Self {
+            Self {
+                index: Some(index),
+                after_id: None,
+            }
+        }
+    }
+
+    impl CreateProject {
+        pub fn new(name: String) -> Self {
+            Self {
+                name,
+                user_id: None,
+            }
+        }
+    }
+
+    impl CreateLabel {
+        pub fn new(name: String) -> Self {
+            Self {
+                name,
+                user_id: None,
+            }
+        }
+    }
+
+    impl ShareLink {
+        pub fn token(&self) -> &str {
+            &self.token
+        }
+    }
+}
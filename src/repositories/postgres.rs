@@ -0,0 +1,1324 @@
+use super::{
+    attach_blockers, attach_labels, check_project_deletable, foreign_project_todos,
+    reorder_positions, ApiKey, CreateApiKey, CreateLabel, CreateProject, CreateTodo, CreateUser,
+    DependencyLink, Label, LabelLink, Nearby, Page, Pomodoro, Project, ReorderTodo,
+    RepositoryError, ShareLink, TimeEntry, Todo, TodoFilter, TodoRepository, TodoSort, UpdateLabel,
+    UpdateProject, UpdateTodo, User, UserRepository, DEFAULT_PROJECT_ID, POMODORO_MINUTES,
+};
+use axum::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use std::slice;
+
+const UNIQUE_VIOLATION: &str = "23505";
+const FOREIGN_KEY_VIOLATION: &str = "23503";
+/// Advisory lock key that serializes changes to `todo_dependencies`.
+const DEPENDENCY_LOCK: i64 = 0x0074_6f64_6f64_6570;
+/// Advisory lock key that serializes limited todo creation.
+const LIMIT_LOCK: i64 = 0x0074_6f64_6f6c_696d;
+
+/// Binds the parameters of the todo listing, for the listing itself and
+/// for its plan.
+macro_rules! bind_listing {
+    ($query:expr, $filter:expr, $page:expr) => {
+        $query
+            .bind($filter.stale_before)
+            .bind($filter.awake_at)
+            .bind($filter.before_id)
+            .bind($filter.completed)
+            .bind($filter.text_pattern())
+            .bind($filter.label.as_deref())
+            .bind($filter.due_before)
+            .bind($filter.due_after)
+            .bind($filter.overdue_at)
+            .bind($filter.priority)
+            .bind($filter.parent_id)
+            .bind($filter.project_id)
+            .bind($filter.updated_after)
+            .bind($filter.reminder_due_at)
+            .bind($filter.ids.as_deref())
+            .bind($filter.user_id)
+            .bind($page.limit.map(|limit| limit as i64))
+            .bind($page.offset as i64)
+    };
+}
+
+#[derive(Debug, Clone)]
+pub struct TodoRepositoryForDb {
+    pool: PgPool,
+    /// Logs the plan of every todo listing.
+    explain: bool,
+}
+
+impl TodoRepositoryForDb {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+            explain: false,
+        }
+    }
+
+    /// Logs the query plan of every todo listing at debug level, to find
+    /// out why one is slow. They only show with `RUST_LOG=debug`.
+    pub fn with_query_plans(self) -> Self {
+        Self {
+            explain: true,
+            ..self
+        }
+    }
+
+    /// The plan Postgres picks for `query`, one node per line.
+    async fn listing_plan(
+        &self,
+        query: &str,
+        filter: &TodoFilter,
+        page: Page,
+    ) -> anyhow::Result<Vec<String>> {
+        let plan = bind_listing!(
+            sqlx::query_scalar::<_, String>(&format!("explain {}", query)),
+            filter,
+            page
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(plan)
+    }
+}
+
+/// The todo listing in `sort` order. One statement is prepared and cached
+/// per order.
+fn listing_query(sort: TodoSort) -> String {
+    format!(
+        r#"
+            select * from todos
+            where ($1::timestamptz is null or (completed=false and updated_at<$1))
+            and ($2::timestamptz is null or snoozed_until is null or snoozed_until<=$2)
+            and ($3::int4 is null or id<$3)
+            and ($4::bool is null or completed=$4)
+            and ($5::text is null or text ilike $5)
+            and ($6::text is null or exists (
+                select 1 from todo_labels join labels on labels.id=todo_labels.label_id
+                where todo_labels.todo_id=todos.id and labels.name=$6
+            ))
+            and ($7::timestamptz is null or due_date<$7)
+            and ($8::timestamptz is null or due_date>$8)
+            and ($9::timestamptz is null or (completed=false and due_date<$9))
+            and ($10::int4 is null or priority=$10)
+            and ($11::int4 is null or parent_id=$11)
+            and ($12::int4 is null or project_id=$12)
+            and ($13::timestamptz is null or updated_at>$13)
+            and ($14::timestamptz is null or (completed=false and remind_at<=$14))
+            and ($15::int4[] is null or id=any($15))
+            and ($16::int4 is null or user_id=$16)
+            order by {}
+            limit $17 offset $18;
+        "#,
+        sort.order_by()
+    )
+}
+
+#[async_trait]
+impl TodoRepository for TodoRepositoryForDb {
+    async fn create(&self, payload: CreateTodo) -> anyhow::Result<Todo> {
+        self.find_project(payload.project_id).await?;
+        let mut tx = self.pool.begin().await?;
+        if !payload.limits.is_unlimited() {
+            sqlx::query(
+                r#"
+                    select pg_advisory_xact_lock($1)
+                "#,
+            )
+            .bind(LIMIT_LOCK)
+            .execute(&mut tx)
+            .await?;
+            let (usage, project_usage) = sqlx::query_as::<_, (i64, i64)>(
+                r#"
+                    select count(*), count(*) filter (where project_id = $1) from todos
+                    where user_id is not distinct from $2
+                "#,
+            )
+            .bind(payload.project_id)
+            .bind(payload.user_id)
+            .fetch_one(&mut tx)
+            .await?;
+            payload
+                .limits
+                .check(payload.project_id, usage as usize, project_usage as usize)?;
+        }
+        let mut todo = sqlx::query_as::<_, Todo>(
+            r#"
+                insert into todos (
+                    text, completed, due_date, priority, parent_id, project_id, user_id,
+                    recurrence, lat, lon, place, remind_at, position
+                )
+                values (
+                    $1, false, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11,
+                    (select coalesce(max(position), -1) + 1 from todos)
+                )
+                returning *
+            "#,
+        )
+        .bind(payload.text.clone())
+        .bind(payload.due_date)
+        .bind(payload.priority)
+        .bind(payload.parent_id)
+        .bind(payload.project_id)
+        .bind(payload.user_id)
+        .bind(payload.recurrence.clone())
+        .bind(payload.lat)
+        .bind(payload.lon)
+        .bind(payload.place.clone())
+        .bind(payload.remind_at)
+        .fetch_one(&mut tx)
+        .await
+        .map_err(|e| match (e, payload.parent_id) {
+            (sqlx::Error::Database(ref db), Some(parent_id))
+                if db.code().as_deref() == Some(FOREIGN_KEY_VIOLATION) =>
+            {
+                RepositoryError::NotFound(parent_id)
+            }
+            (e, _) => RepositoryError::Unexpected(e.to_string()),
+        })?;
+        set_labels(&mut tx, todo.id, &payload.label_ids).await?;
+        set_blockers(&mut tx, todo.id, &payload.blocked_by).await?;
+        tx.commit().await?;
+        self.load_relations(slice::from_mut(&mut todo)).await?;
+
+        Ok(todo)
+    }
+
+    async fn find(&self, id: i32) -> anyhow::Result<Todo> {
+        let mut todo = sqlx::query_as::<_, Todo>(
+            r#"
+                select * from todos where id=$1
+            "#,
+        )
+        .bind(id)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => RepositoryError::NotFound(id),
+            _ => RepositoryError::Unexpected(e.to_string()),
+        })?;
+        self.load_relations(slice::from_mut(&mut todo)).await?;
+
+        Ok(todo)
+    }
+
+    async fn all(
+        &self,
+        filter: &TodoFilter,
+        sort: TodoSort,
+        page: Page,
+    ) -> anyhow::Result<Vec<Todo>> {
+        let query = listing_query(sort);
+        if self.explain {
+            match self.listing_plan(&query, filter, page).await {
+                Ok(plan) => tracing::debug!("plan of todo listing:\n{}", plan.join("\n")),
+                Err(e) => tracing::warn!("fail explain todo listing: {:#}", e),
+            }
+        }
+        let mut todos = bind_listing!(sqlx::query_as::<_, Todo>(&query), filter, page)
+            .fetch_all(&self.pool)
+            .await?;
+        self.load_relations(&mut todos).await?;
+
+        Ok(todos)
+    }
+
+    async fn count(&self, filter: &TodoFilter) -> anyhow::Result<usize> {
+        let count = sqlx::query_scalar::<_, i64>(
+            r#"
+                select count(*) from todos
+                where ($1::timestamptz is null or (completed=false and updated_at<$1))
+                and ($2::timestamptz is null or snoozed_until is null or snoozed_until<=$2)
+                and ($3::int4 is null or id<$3)
+                and ($4::bool is null or completed=$4)
+                and ($5::text is null or text ilike $5)
+                and ($6::text is null or exists (
+                    select 1 from todo_labels join labels on labels.id=todo_labels.label_id
+                    where todo_labels.todo_id=todos.id and labels.name=$6
+                ))
+                and ($7::timestamptz is null or due_date<$7)
+                and ($8::timestamptz is null or due_date>$8)
+                and ($9::timestamptz is null or (completed=false and due_date<$9))
+                and ($10::int4 is null or priority=$10)
+                and ($11::int4 is null or parent_id=$11)
+                and ($12::int4 is null or project_id=$12)
+                and ($13::timestamptz is null or updated_at>$13)
+                and ($14::timestamptz is null or (completed=false and remind_at<=$14))
+                and ($15::int4[] is null or id=any($15))
+                and ($16::int4 is null or user_id=$16)
+            "#,
+        )
+        .bind(filter.stale_before)
+        .bind(filter.awake_at)
+        .bind(filter.before_id)
+        .bind(filter.completed)
+        .bind(filter.text_pattern())
+        .bind(filter.label.as_deref())
+        .bind(filter.due_before)
+        .bind(filter.due_after)
+        .bind(filter.overdue_at)
+        .bind(filter.priority)
+        .bind(filter.parent_id)
+        .bind(filter.project_id)
+        .bind(filter.updated_after)
+        .bind(filter.reminder_due_at)
+        .bind(filter.ids.as_deref())
+        .bind(filter.user_id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(count as usize)
+    }
+
+    async fn update(&self, id: i32, payload: UpdateTodo) -> anyhow::Result<Todo> {
+        let old_todo = self.find(id).await?;
+        if let Some(project_id) = payload.project_id {
+            self.find_project(project_id).await?;
+        }
+        let mut tx = self.pool.begin().await?;
+        let mut todo = sqlx::query_as::<_, Todo>(
+            r#"
+                update todos
+                set text=$1, completed=$2, due_date=$3, priority=$4, project_id=$5,
+                    recurrence=$6, lat=$7, lon=$8, place=$9, remind_at=$10, updated_at=now()
+                where id=$11
+                returning *
+            "#,
+        )
+        .bind(payload.text.unwrap_or(old_todo.text))
+        .bind(payload.completed.unwrap_or(old_todo.completed))
+        .bind(payload.due_date.unwrap_or(old_todo.due_date))
+        .bind(payload.priority.unwrap_or(old_todo.priority))
+        .bind(payload.project_id.unwrap_or(old_todo.project_id))
+        .bind(payload.recurrence.unwrap_or(old_todo.recurrence))
+        .bind(payload.lat.unwrap_or(old_todo.lat))
+        .bind(payload.lon.unwrap_or(old_todo.lon))
+        .bind(payload.place.unwrap_or(old_todo.place))
+        .bind(payload.remind_at.unwrap_or(old_todo.remind_at))
+        .bind(id)
+        .fetch_one(&mut tx)
+        .await?;
+        if let Some(label_ids) = &payload.label_ids {
+            set_labels(&mut tx, id, label_ids).await?;
+        }
+        if let Some(blocked_by) = &payload.blocked_by {
+            set_blockers(&mut tx, id, blocked_by).await?;
+        }
+        tx.commit().await?;
+        self.load_relations(slice::from_mut(&mut todo)).await?;
+        if todo.completed && !old_todo.completed {
+            if let Some(next) = todo.next_occurrence(Utc::now()) {
+                self.create(next).await?;
+            }
+        }
+
+        Ok(todo)
+    }
+
+    async fn delete(&self, id: i32) -> anyhow::Result<()> {
+        let result = sqlx::query(
+            r#"
+                delete from todos where id=$1
+            "#,
+        )
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(ref db)
+                if db.code().as_deref() == Some(FOREIGN_KEY_VIOLATION) =>
+            {
+                RepositoryError::Conflict(format!("todo has subtasks, id is {}", id))
+            }
+            _ => RepositoryError::Unexpected(e.to_string()),
+        })?;
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound(id).into());
+        }
+
+        Ok(())
+    }
+
+    async fn snooze(&self, id: i32, until: Option<DateTime<Utc>>) -> anyhow::Result<Todo> {
+        let mut todo = sqlx::query_as::<_, Todo>(
+            r#"
+                update todos set snoozed_until=$1, updated_at=now()
+                where id=$2
+                returning *
+            "#,
+        )
+        .bind(until)
+        .bind(id)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => RepositoryError::NotFound(id),
+            _ => RepositoryError::Unexpected(e.to_string()),
+        })?;
+        self.load_relations(slice::from_mut(&mut todo)).await?;
+
+        Ok(todo)
+    }
+
+    async fn start_timer(&self, id: i32) -> anyhow::Result<TimeEntry> {
+        self.find(id).await?;
+        let entry = sqlx::query_as::<_, TimeEntry>(
+            r#"
+                insert into time_entries (todo_id)
+                values ($1)
+                returning *
+            "#,
+        )
+        .bind(id)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(ref db) if db.code().as_deref() == Some(UNIQUE_VIOLATION) => {
+                RepositoryError::Conflict(format!("timer already running, id is {}", id))
+            }
+            _ => RepositoryError::Unexpected(e.to_string()),
+        })?;
+
+        Ok(entry)
+    }
+
+    async fn stop_timer(&self, id: i32) -> anyhow::Result<TimeEntry> {
+        self.find(id).await?;
+        let mut tx = self.pool.begin().await?;
+        let entry = sqlx::query_as::<_, TimeEntry>(
+            r#"
+                update time_entries set stopped_at=now()
+                where todo_id=$1 and stopped_at is null
+                returning *
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(&mut tx)
+        .await?
+        .ok_or_else(|| RepositoryError::Conflict(format!("no running timer, id is {}", id)))?;
+
+        sqlx::query(
+            r#"
+                update todos set time_spent=time_spent+$1
+                where id=$2
+            "#,
+        )
+        .bind(entry.duration())
+        .bind(id)
+        .execute(&mut tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(entry)
+    }
+
+    async fn time_entries(&self, id: i32) -> anyhow::Result<Vec<TimeEntry>> {
+        self.find(id).await?;
+        let entries = sqlx::query_as::<_, TimeEntry>(
+            r#"
+                select * from time_entries
+                where todo_id=$1
+                order by id
+            "#,
+        )
+        .bind(id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(entries)
+    }
+
+    async fn start_pomodoro(&self, id: i32) -> anyhow::Result<Pomodoro> {
+        self.find(id).await?;
+        let started_at = Utc::now();
+        let pomodoro = sqlx::query_as::<_, Pomodoro>(
+            r#"
+                insert into pomodoros (todo_id, started_at, ends_at)
+                values ($1, $2, $3)
+                returning *
+            "#,
+        )
+        .bind(id)
+        .bind(started_at)
+        .bind(started_at + Duration::minutes(POMODORO_MINUTES))
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(ref db) if db.code().as_deref() == Some(UNIQUE_VIOLATION) => {
+                RepositoryError::Conflict(format!("pomodoro already running, id is {}", id))
+            }
+            _ => RepositoryError::Unexpected(e.to_string()),
+        })?;
+
+        Ok(pomodoro)
+    }
+
+    async fn interrupt_pomodoro(&self, pomodoro_id: i32) -> anyhow::Result<Pomodoro> {
+        self.find_pomodoro(pomodoro_id).await?;
+        let pomodoro = sqlx::query_as::<_, Pomodoro>(
+            r#"
+                update pomodoros set interruptions=interruptions+1
+                where id=$1 and finished_at is null
+                returning *
+            "#,
+        )
+        .bind(pomodoro_id)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| {
+            RepositoryError::Conflict(format!("pomodoro already finished, id is {}", pomodoro_id))
+        })?;
+
+        Ok(pomodoro)
+    }
+
+    async fn finish_pomodoro(&self, pomodoro_id: i32) -> anyhow::Result<Pomodoro> {
+        let pomodoro = self.find_pomodoro(pomodoro_id).await?;
+        let finished_at = Utc::now();
+        let completed = finished_at >= pomodoro.ends_at;
+
+        let mut tx = self.pool.begin().await?;
+        let pomodoro = sqlx::query_as::<_, Pomodoro>(
+            r#"
+                update pomodoros set finished_at=$2, completed=$3
+                where id=$1 and finished_at is null
+                returning *
+            "#,
+        )
+        .bind(pomodoro_id)
+        .bind(finished_at)
+        .bind(completed)
+        .fetch_optional(&mut tx)
+        .await?
+        .ok_or_else(|| {
+            RepositoryError::Conflict(format!("pomodoro already finished, id is {}", pomodoro_id))
+        })?;
+
+        if completed {
+            sqlx::query(
+                r#"
+                    insert into time_entries (todo_id, started_at, stopped_at)
+                    values ($1, $2, $3)
+                "#,
+            )
+            .bind(pomodoro.todo_id)
+            .bind(pomodoro.started_at)
+            .bind(pomodoro.ends_at)
+            .execute(&mut tx)
+            .await?;
+            sqlx::query(
+                r#"
+                    update todos set time_spent=time_spent+$1
+                    where id=$2
+                "#,
+            )
+            .bind(pomodoro.duration())
+            .bind(pomodoro.todo_id)
+            .execute(&mut tx)
+            .await?;
+        }
+        tx.commit().await?;
+
+        Ok(pomodoro)
+    }
+
+    async fn pomodoro(&self, pomodoro_id: i32) -> anyhow::Result<Pomodoro> {
+        self.find_pomodoro(pomodoro_id).await
+    }
+
+    async fn pomodoros(&self, id: i32) -> anyhow::Result<Vec<Pomodoro>> {
+        self.find(id).await?;
+        let pomodoros = sqlx::query_as::<_, Pomodoro>(
+            r#"
+                select * from pomodoros
+                where todo_id=$1
+                order by id
+            "#,
+        )
+        .bind(id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(pomodoros)
+    }
+
+    async fn completed_pomodoros(&self, user_id: Option<i32>) -> anyhow::Result<Vec<Pomodoro>> {
+        let pomodoros = sqlx::query_as::<_, Pomodoro>(
+            r#"
+                select pomodoros.* from pomodoros
+                join todos on todos.id=pomodoros.todo_id
+                where pomodoros.completed and ($1::int4 is null or todos.user_id=$1)
+                order by pomodoros.id
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(pomodoros)
+    }
+
+    async fn create_label(&self, payload: CreateLabel) -> anyhow::Result<Label> {
+        let label = sqlx::query_as::<_, Label>(
+            r#"
+                insert into labels (name, user_id)
+                values ($1, $2)
+                returning *
+            "#,
+        )
+        .bind(payload.name.clone())
+        .bind(payload.user_id)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(ref db) if db.code().as_deref() == Some(UNIQUE_VIOLATION) => {
+                RepositoryError::Conflict(format!("label already exists, name is {}", payload.name))
+            }
+            _ => RepositoryError::Unexpected(e.to_string()),
+        })?;
+
+        Ok(label)
+    }
+
+    async fn find_label(&self, id: i32) -> anyhow::Result<Label> {
+        let label = sqlx::query_as::<_, Label>(
+            r#"
+                select * from labels where id=$1
+            "#,
+        )
+        .bind(id)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => RepositoryError::NotFound(id),
+            _ => RepositoryError::Unexpected(e.to_string()),
+        })?;
+
+        Ok(label)
+    }
+
+    async fn all_labels(&self, user_id: Option<i32>) -> anyhow::Result<Vec<Label>> {
+        let labels = sqlx::query_as::<_, Label>(
+            r#"
+                select * from labels
+                where $1::int4 is null or user_id=$1
+                order by name
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(labels)
+    }
+
+    async fn update_label(&self, id: i32, payload: UpdateLabel) -> anyhow::Result<Label> {
+        let label = sqlx::query_as::<_, Label>(
+            r#"
+                update labels set name=coalesce($1, name)
+                where id=$2
+                returning *
+            "#,
+        )
+        .bind(payload.name.clone())
+        .bind(id)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => RepositoryError::NotFound(id),
+            sqlx::Error::Database(ref db) if db.code().as_deref() == Some(UNIQUE_VIOLATION) => {
+                RepositoryError::Conflict(format!(
+                    "label already exists, name is {}",
+                    payload.name.unwrap_or_default()
+                ))
+            }
+            _ => RepositoryError::Unexpected(e.to_string()),
+        })?;
+
+        Ok(label)
+    }
+
+    async fn delete_label(&self, id: i32) -> anyhow::Result<()> {
+        let result = sqlx::query(
+            r#"
+                delete from labels where id=$1
+            "#,
+        )
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| RepositoryError::Unexpected(e.to_string()))?;
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound(id).into());
+        }
+
+        Ok(())
+    }
+
+    async fn blockers(&self, id: i32) -> anyhow::Result<Vec<Todo>> {
+        self.find(id).await?;
+        let mut todos = sqlx::query_as::<_, Todo>(
+            r#"
+                select todos.* from todos
+                join todo_dependencies on todos.id=todo_dependencies.blocker_id
+                where todo_dependencies.todo_id=$1 and todos.completed=false
+                order by todos.id
+            "#,
+        )
+        .bind(id)
+        .fetch_all(&self.pool)
+        .await?;
+        self.load_relations(&mut todos).await?;
+
+        Ok(todos)
+    }
+
+    async fn nearby(&self, nearby: &Nearby) -> anyhow::Result<Vec<Todo>> {
+        let ((min_lat, max_lat), (min_lon, max_lon)) = nearby.bounds();
+        let mut todos = sqlx::query_as::<_, Todo>(
+            r#"
+                select * from todos
+                where completed=false
+                and lat between $1 and $2 and lon between $3 and $4
+            "#,
+        )
+        .bind(min_lat)
+        .bind(max_lat)
+        .bind(min_lon)
+        .bind(max_lon)
+        .fetch_all(&self.pool)
+        .await?;
+        self.load_relations(&mut todos).await?;
+
+        Ok(nearby.select(todos))
+    }
+
+    async fn create_share_link(&self, link: ShareLink) -> anyhow::Result<ShareLink> {
+        let link = sqlx::query_as::<_, ShareLink>(
+            r#"
+                insert into share_links (token, todo_id, expires_at, created_at)
+                values ($1, $2, $3, $4)
+                returning *
+            "#,
+        )
+        .bind(&link.token)
+        .bind(link.todo_id)
+        .bind(link.expires_at)
+        .bind(link.created_at)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(ref db)
+                if db.code().as_deref() == Some(FOREIGN_KEY_VIOLATION) =>
+            {
+                RepositoryError::NotFound(link.todo_id)
+            }
+            _ => RepositoryError::Unexpected(e.to_string()),
+        })?;
+
+        Ok(link)
+    }
+
+    async fn find_share_link(&self, token: &str) -> anyhow::Result<Option<ShareLink>> {
+        let link = sqlx::query_as::<_, ShareLink>(
+            r#"
+                select * from share_links where token=$1
+            "#,
+        )
+        .bind(token)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(link)
+    }
+
+    async fn delete_share_link(&self, id: i32, token: &str) -> anyhow::Result<()> {
+        let result = sqlx::query(
+            r#"
+                delete from share_links where todo_id=$1 and token=$2
+            "#,
+        )
+        .bind(id)
+        .bind(token)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| RepositoryError::Unexpected(e.to_string()))?;
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound(id).into());
+        }
+
+        Ok(())
+    }
+
+    async fn reorder(&self, id: i32, payload: ReorderTodo) -> anyhow::Result<Todo> {
+        let mut tx = self.pool.begin().await?;
+        // Locking every row of the user makes their concurrent reorders take
+        // turns.
+        let todos = sqlx::query_as::<_, Todo>(
+            r#"
+                select * from todos
+                where user_id is not distinct from (select user_id from todos where id=$1)
+                for update
+            "#,
+        )
+        .bind(id)
+        .fetch_all(&mut tx)
+        .await?;
+        for (todo_id, position) in reorder_positions(id, &payload, &todos)? {
+            sqlx::query(
+                r#"
+                    update todos set position=$1 where id=$2
+                "#,
+            )
+            .bind(position)
+            .bind(todo_id)
+            .execute(&mut tx)
+            .await?;
+        }
+        tx.commit().await?;
+
+        self.find(id).await
+    }
+
+    async fn create_project(&self, payload: CreateProject) -> anyhow::Result<Project> {
+        let project = sqlx::query_as::<_, Project>(
+            r#"
+                insert into projects (name, user_id)
+                values ($1, $2)
+                returning *
+            "#,
+        )
+        .bind(payload.name)
+        .bind(payload.user_id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(project)
+    }
+
+    async fn find_project(&self, id: i32) -> anyhow::Result<Project> {
+        let project = sqlx::query_as::<_, Project>(
+            r#"
+                select * from projects where id=$1
+            "#,
+        )
+        .bind(id)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => RepositoryError::NotFound(id),
+            _ => RepositoryError::Unexpected(e.to_string()),
+        })?;
+
+        Ok(project)
+    }
+
+    async fn all_projects(&self, user_id: Option<i32>) -> anyhow::Result<Vec<Project>> {
+        let projects = sqlx::query_as::<_, Project>(
+            r#"
+                select * from projects
+                where $1::int4 is null or user_id=$1 or id=$2
+                order by id
+            "#,
+        )
+        .bind(user_id)
+        .bind(DEFAULT_PROJECT_ID)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(projects)
+    }
+
+    async fn update_project(&self, id: i32, payload: UpdateProject) -> anyhow::Result<Project> {
+        let project = sqlx::query_as::<_, Project>(
+            r#"
+                update projects set name=coalesce($1, name)
+                where id=$2
+                returning *
+            "#,
+        )
+        .bind(payload.name)
+        .bind(id)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => RepositoryError::NotFound(id),
+            _ => RepositoryError::Unexpected(e.to_string()),
+        })?;
+
+        Ok(project)
+    }
+
+    async fn delete_project(&self, id: i32) -> anyhow::Result<()> {
+        check_project_deletable(id)?;
+        let mut tx = self.pool.begin().await?;
+        let foreign = sqlx::query_scalar::<_, i64>(
+            r#"
+                select count(*) from todos
+                where project_id=$1
+                and user_id is distinct from (select user_id from projects where id=$1)
+            "#,
+        )
+        .bind(id)
+        .fetch_one(&mut tx)
+        .await?;
+        if foreign > 0 {
+            return Err(foreign_project_todos(id).into());
+        }
+        // Its todos go with it through `on delete cascade`, unless a todo
+        // elsewhere is a subtask of one of them.
+        let result = sqlx::query(
+            r#"
+                delete from projects where id=$1
+            "#,
+        )
+        .bind(id)
+        .execute(&mut tx)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(ref db)
+                if db.code().as_deref() == Some(FOREIGN_KEY_VIOLATION) =>
+            {
+                RepositoryError::Conflict(format!(
+                    "project todos have subtasks elsewhere, id is {}",
+                    id
+                ))
+            }
+            _ => RepositoryError::Unexpected(e.to_string()),
+        })?;
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound(id).into());
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
+
+impl TodoRepositoryForDb {
+    /// Fills in the labels and blockers of `todos`, which `select * from todos`
+    /// leaves empty.
+    async fn load_relations(&self, todos: &mut [Todo]) -> anyhow::Result<()> {
+        let ids: Vec<i32> = todos.iter().map(|todo| todo.id).collect();
+        let links = sqlx::query_as::<_, LabelLink>(
+            r#"
+                select todo_labels.todo_id, labels.id, labels.name, labels.user_id
+                from todo_labels join labels on labels.id=todo_labels.label_id
+                where todo_labels.todo_id=any($1)
+            "#,
+        )
+        .bind(&ids)
+        .fetch_all(&self.pool)
+        .await?;
+        attach_labels(todos, links);
+        let links = sqlx::query_as::<_, DependencyLink>(
+            r#"
+                select todo_id, blocker_id from todo_dependencies
+                where todo_id=any($1)
+            "#,
+        )
+        .bind(&ids)
+        .fetch_all(&self.pool)
+        .await?;
+        attach_blockers(todos, links);
+
+        Ok(())
+    }
+
+    async fn find_pomodoro(&self, pomodoro_id: i32) -> anyhow::Result<Pomodoro> {
+        let pomodoro = sqlx::query_as::<_, Pomodoro>(
+            r#"
+                select * from pomodoros where id=$1
+            "#,
+        )
+        .bind(pomodoro_id)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => RepositoryError::NotFound(pomodoro_id),
+            _ => RepositoryError::Unexpected(e.to_string()),
+        })?;
+
+        Ok(pomodoro)
+    }
+}
+
+/// Replaces the labels attached to todo `id`.
+async fn set_labels(
+    tx: &mut Transaction<'_, Postgres>,
+    id: i32,
+    label_ids: &[i32],
+) -> anyhow::Result<()> {
+    sqlx::query(
+        r#"
+            delete from todo_labels where todo_id=$1
+        "#,
+    )
+    .bind(id)
+    .execute(&mut *tx)
+    .await?;
+    for label_id in label_ids {
+        sqlx::query(
+            r#"
+                insert into todo_labels (todo_id, label_id)
+                values ($1, $2)
+                on conflict do nothing
+            "#,
+        )
+        .bind(id)
+        .bind(label_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(ref db)
+                if db.code().as_deref() == Some(FOREIGN_KEY_VIOLATION) =>
+            {
+                RepositoryError::NotFound(*label_id)
+            }
+            _ => RepositoryError::Unexpected(e.to_string()),
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Replaces the todos that todo `id` is blocked by, refusing any that would
+/// close a cycle. Adding blockers takes an advisory lock held until `tx`
+/// ends, so two transactions can not each pass the cycle check and then
+/// close a cycle together.
+async fn set_blockers(
+    tx: &mut Transaction<'_, Postgres>,
+    id: i32,
+    blocked_by: &[i32],
+) -> anyhow::Result<()> {
+    if !blocked_by.is_empty() {
+        sqlx::query(
+            r#"
+                select pg_advisory_xact_lock($1)
+            "#,
+        )
+        .bind(DEPENDENCY_LOCK)
+        .execute(&mut *tx)
+        .await?;
+    }
+    let (cycle,) = sqlx::query_as::<_, (bool,)>(
+        r#"
+            with recursive reachable (id) as (
+                select unnest($1::int4[])
+                union
+                select todo_dependencies.blocker_id
+                from todo_dependencies join reachable on todo_dependencies.todo_id=reachable.id
+            )
+            select exists(select 1 from reachable where id=$2)
+        "#,
+    )
+    .bind(blocked_by)
+    .bind(id)
+    .fetch_one(&mut *tx)
+    .await?;
+    if cycle {
+        return Err(RepositoryError::Conflict(format!("dependency cycle, id is {}", id)).into());
+    }
+
+    sqlx::query(
+        r#"
+            delete from todo_dependencies where todo_id=$1
+        "#,
+    )
+    .bind(id)
+    .execute(&mut *tx)
+    .await?;
+    for blocker_id in blocked_by {
+        sqlx::query(
+            r#"
+                insert into todo_dependencies (todo_id, blocker_id)
+                values ($1, $2)
+                on conflict do nothing
+            "#,
+        )
+        .bind(id)
+        .bind(blocker_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(ref db)
+                if db.code().as_deref() == Some(FOREIGN_KEY_VIOLATION) =>
+            {
+                RepositoryError::NotFound(*blocker_id)
+            }
+            _ => RepositoryError::Unexpected(e.to_string()),
+        })?;
+    }
+
+    Ok(())
+}
+
+#[async_trait]
+impl UserRepository for TodoRepositoryForDb {
+    async fn create_user(&self, payload: CreateUser) -> anyhow::Result<User> {
+        let user = sqlx::query_as::<_, User>(
+            r#"
+                insert into users (name, password_hash, created_at)
+                values ($1, $2, $3)
+                returning *
+            "#,
+        )
+        .bind(payload.name.clone())
+        .bind(payload.password_hash.clone())
+        .bind(Utc::now())
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(ref db) if db.code().as_deref() == Some(UNIQUE_VIOLATION) => {
+                RepositoryError::Conflict(format!("user already exists, name is {}", payload.name))
+            }
+            _ => RepositoryError::Unexpected(e.to_string()),
+        })?;
+
+        Ok(user)
+    }
+
+    async fn find_user_by_name(&self, name: &str) -> anyhow::Result<Option<User>> {
+        let user = sqlx::query_as::<_, User>(
+            r#"
+                select * from users where name=$1
+            "#,
+        )
+        .bind(name)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(user)
+    }
+    async fn create_api_key(&self, payload: CreateApiKey) -> anyhow::Result<ApiKey> {
+        let key = sqlx::query_as::<_, ApiKey>(
+            r#"
+                insert into api_keys (user_id, name, prefix, key_hash, created_at)
+                values ($1, $2, $3, $4, $5)
+                returning *
+            "#,
+        )
+        .bind(payload.user_id)
+        .bind(payload.name)
+        .bind(payload.prefix)
+        .bind(payload.key_hash)
+        .bind(Utc::now())
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(key)
+    }
+
+    async fn find_api_key_by_hash(&self, key_hash: &str) -> anyhow::Result<Option<ApiKey>> {
+        let key = sqlx::query_as::<_, ApiKey>(
+            r#"
+                select * from api_keys where key_hash=$1
+            "#,
+        )
+        .bind(key_hash)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(key)
+    }
+
+    async fn api_keys(&self, user_id: i32) -> anyhow::Result<Vec<ApiKey>> {
+        let keys = sqlx::query_as::<_, ApiKey>(
+            r#"
+                select * from api_keys where user_id=$1 order by id asc
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(keys)
+    }
+
+    async fn delete_api_key(&self, user_id: i32, id: i32) -> anyhow::Result<()> {
+        let result = sqlx::query(
+            r#"
+                delete from api_keys where id=$1 and user_id=$2
+            "#,
+        )
+        .bind(id)
+        .bind(user_id)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| RepositoryError::Unexpected(e.to_string()))?;
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound(id).into());
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[cfg(feature = "database-test")]
+    #[tokio::test]
+    async fn crud_scenario_db() {
+        use crate::repositories::TodoLimits;
+        use dotenv::dotenv;
+        use std::env;
+
+        dotenv().ok();
+        let database_url = &env::var("DATABASE_URL").expect("undefined env variable $DATABASE_URL");
+        let pool = PgPool::connect(database_url)
+            .await
+            .unwrap_or_else(|_| panic!("failed connect database, url is [{}]", database_url));
+
+        let repository = TodoRepositoryForDb::new(pool.clone());
+        let todo_text = "[crud_scenario] text";
+
+        // create
+        let created = repository
+            .create(CreateTodo::new(todo_text.to_string()))
+            .await
+            .expect("[create] returned Err");
+        assert_eq!(created.text, todo_text);
+        assert!(!created.completed);
+
+        // find
+        let todo = repository
+            .find(created.id)
+            .await
+            .expect("[find] returned Err");
+        assert_eq!(created, todo);
+
+        // all
+        let todos = repository
+            .all(&TodoFilter::default(), TodoSort::default(), Page::default())
+            .await
+            .expect("[all] returned Err");
+        let todo = todos.first().unwrap();
+        assert_eq!(created, *todo);
+
+        // update
+        let updated_text = "[crud_scenario] updated text";
+        let todo = repository
+            .update(
+                todo.id,
+                UpdateTodo {
+                    text: Some(updated_text.to_string()),
+                    completed: Some(true),
+                    label_ids: None,
+                    due_date: None,
+                    priority: None,
+                    blocked_by: None,
+                    project_id: None,
+                    recurrence: None,
+                    lat: None,
+                    lon: None,
+                    place: None,
+                    remind_at: None,
+                },
+            )
+            .await
+            .expect("[update] returned Err");
+        assert_eq!(created.id, todo.id);
+        assert_eq!(todo.text, updated_text);
+
+        // blockers
+        let blocked = repository
+            .create(CreateTodo::new(todo_text.to_string()).with_blockers(vec![todo.id]))
+            .await
+            .expect("[blockers] returned Err");
+        let res = repository
+            .update(
+                todo.id,
+                UpdateTodo {
+                    blocked_by: Some(vec![blocked.id]),
+                    ..UpdateTodo::new(None, None)
+                },
+            )
+            .await;
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::Conflict(_))
+        ));
+        repository
+            .delete(blocked.id)
+            .await
+            .expect("[blockers] returned Err");
+
+        // limits
+        let project = repository
+            .create_project(CreateProject::new("[crud_scenario] limited".to_string()))
+            .await
+            .expect("[limits] returned Err");
+        let limits = TodoLimits {
+            max_project_todos: Some(1),
+            ..TodoLimits::default()
+        };
+        repository
+            .create(
+                CreateTodo::new(todo_text.to_string())
+                    .with_project(project.id)
+                    .with_limits(limits),
+            )
+            .await
+            .expect("[limits] returned Err");
+        let res = repository
+            .create(
+                CreateTodo::new(todo_text.to_string())
+                    .with_project(project.id)
+                    .with_limits(limits),
+            )
+            .await;
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::ProjectTodoLimitReached {
+                limit: 1,
+                usage: 1,
+                ..
+            })
+        ));
+        repository
+            .delete_project(project.id)
+            .await
+            .expect("[limits] returned Err");
+
+        // pomodoro, abandoned when finished early
+        let pomodoro = repository
+            .start_pomodoro(todo.id)
+            .await
+            .expect("[start_pomodoro] returned Err");
+        let pomodoro = repository
+            .finish_pomodoro(pomodoro.id)
+            .await
+            .expect("[finish_pomodoro] returned Err");
+        assert!(!pomodoro.completed);
+        let completed = repository
+            .completed_pomodoros(None)
+            .await
+            .expect("[completed_pomodoros] returned Err");
+        assert!(!completed.contains(&pomodoro));
+
+        // delete
+        repository
+            .delete(todo.id)
+            .await
+            .expect("[delete] returned Err");
+        let res = repository.find(created.id).await;
+        assert!(res.is_err());
+        let res = repository.delete(created.id).await;
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::NotFound(_))
+        ));
+
+        let todo_rows = sqlx::query("select * from todos where id=$1")
+            .bind(todo.id)
+            .fetch_all(&pool)
+            .await
+            .expect("[delete] todo_labels fetch error");
+        assert_eq!(todo_rows.len(), 0);
+    }
+}
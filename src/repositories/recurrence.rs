@@ -0,0 +1,190 @@
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use std::{fmt, str::FromStr};
+
+const UNTIL_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// The part of an iCalendar RRULE (RFC 5545) that todos repeat by: `FREQ`
+/// with an optional `INTERVAL` and at most one of `COUNT` and `UNTIL`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Recurrence {
+    frequency: Frequency,
+    interval: u32,
+    /// Occurrences left, counting the current one.
+    count: Option<u32>,
+    until: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+impl Recurrence {
+    /// The first occurrence after `due_date`, together with the rule that
+    /// continues from there. `None` once `COUNT` or `UNTIL` runs out.
+    pub fn next(&self, due_date: DateTime<Utc>) -> Option<(DateTime<Utc>, Recurrence)> {
+        if self.count == Some(1) {
+            return None;
+        }
+        let next = match self.frequency {
+            Frequency::Daily => due_date.checked_add_signed(Duration::days(self.interval.into())),
+            Frequency::Weekly => due_date.checked_add_signed(Duration::weeks(self.interval.into())),
+            Frequency::Monthly => add_months(due_date, self.interval.into()),
+            Frequency::Yearly => add_months(due_date, i64::from(self.interval) * 12),
+        }?;
+        if self.until.map(|until| next > until).unwrap_or(false) {
+            return None;
+        }
+        let rest = Recurrence {
+            count: self.count.map(|count| count - 1),
+            ..*self
+        };
+        Some((next, rest))
+    }
+}
+
+/// Steps `months` at a time until the day of the month exists, so that like
+/// RFC 5545 a rule on the 31st skips shorter months instead of clamping.
+fn add_months(date: DateTime<Utc>, months: i64) -> Option<DateTime<Utc>> {
+    (1..=12).find_map(|step| {
+        let month0 = i64::from(date.month0()) + months * step;
+        let year = i32::try_from(i64::from(date.year()) + month0 / 12).ok()?;
+        let day = NaiveDate::from_ymd_opt(year, (month0 % 12) as u32 + 1, date.day())?;
+        Some(Utc.from_utc_datetime(&day.and_time(date.time())))
+    })
+}
+
+/// Accepts a UTC date-time, or a bare date that then lasts the whole day.
+fn parse_until(value: &str) -> Option<DateTime<Utc>> {
+    if let Ok(until) = NaiveDateTime::parse_from_str(value, UNTIL_FORMAT) {
+        return Some(Utc.from_utc_datetime(&until));
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y%m%d").ok()?;
+    Some(Utc.from_utc_datetime(&date.and_hms_opt(23, 59, 59)?))
+}
+
+fn parse_positive(name: &str, value: &str) -> Result<u32, String> {
+    value
+        .parse()
+        .ok()
+        .filter(|value| *value > 0)
+        .ok_or_else(|| format!("Invalid {} {}", name, value))
+}
+
+impl FromStr for Recurrence {
+    type Err = String;
+
+    fn from_str(rule: &str) -> Result<Self, Self::Err> {
+        let rule = rule.strip_prefix("RRULE:").unwrap_or(rule);
+        let mut frequency = None;
+        let mut interval = 1;
+        let mut count = None;
+        let mut until = None;
+        for part in rule.split(';') {
+            let (name, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("Malformed rule part {}", part))?;
+            match name.to_ascii_uppercase().as_str() {
+                "FREQ" => {
+                    frequency = Some(match value.to_ascii_uppercase().as_str() {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        "YEARLY" => Frequency::Yearly,
+                        _ => return Err(format!("Unsupported frequency {}", value)),
+                    })
+                }
+                "INTERVAL" => interval = parse_positive("INTERVAL", value)?,
+                "COUNT" => count = Some(parse_positive("COUNT", value)?),
+                "UNTIL" => {
+                    until =
+                        Some(parse_until(value).ok_or_else(|| format!("Invalid UNTIL {}", value))?)
+                }
+                _ => return Err(format!("Unsupported rule part {}", name)),
+            }
+        }
+        if count.is_some() && until.is_some() {
+            return Err("COUNT and UNTIL can not be combined".to_string());
+        }
+        let frequency = frequency.ok_or_else(|| "Missing FREQ".to_string())?;
+
+        Ok(Recurrence {
+            frequency,
+            interval,
+            count,
+            until,
+        })
+    }
+}
+
+impl fmt::Display for Recurrence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let frequency = match self.frequency {
+            Frequency::Daily => "DAILY",
+            Frequency::Weekly => "WEEKLY",
+            Frequency::Monthly => "MONTHLY",
+            Frequency::Yearly => "YEARLY",
+        };
+        write!(f, "FREQ={}", frequency)?;
+        if self.interval != 1 {
+            write!(f, ";INTERVAL={}", self.interval)?;
+        }
+        if let Some(count) = self.count {
+            write!(f, ";COUNT={}", count)?;
+        }
+        if let Some(until) = self.until {
+            write!(f, ";UNTIL={}", until.format(UNTIL_FORMAT))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    fn at(value: &str) -> DateTime<Utc> {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn should_parse_and_render_rules() {
+        let rule: Recurrence = "RRULE:freq=weekly;INTERVAL=2;COUNT=3".parse().unwrap();
+        assert_eq!(rule.to_string(), "FREQ=WEEKLY;INTERVAL=2;COUNT=3");
+        let rule: Recurrence = "FREQ=DAILY;UNTIL=20261231".parse().unwrap();
+        assert_eq!(rule.to_string(), "FREQ=DAILY;UNTIL=20261231T235959Z");
+
+        for rule in [
+            "INTERVAL=2",
+            "FREQ=HOURLY",
+            "FREQ=DAILY;INTERVAL=0",
+            "FREQ=DAILY;BYDAY=MO",
+            "FREQ=DAILY;COUNT=2;UNTIL=20261231",
+            "FREQ",
+        ] {
+            assert!(rule.parse::<Recurrence>().is_err(), "{}", rule);
+        }
+    }
+
+    #[test]
+    fn should_step_to_next_occurrence() {
+        let rule: Recurrence = "FREQ=MONTHLY;COUNT=2".parse().unwrap();
+        let (next, rest) = rule.next(at("2026-01-31T09:00:00Z")).unwrap();
+        assert_eq!(next, at("2026-03-31T09:00:00Z"));
+        assert_eq!(rest.to_string(), "FREQ=MONTHLY;COUNT=1");
+        assert_eq!(rest.next(next), None);
+
+        let rule: Recurrence = "FREQ=YEARLY".parse().unwrap();
+        let (next, _) = rule.next(at("2028-02-29T00:00:00Z")).unwrap();
+        assert_eq!(next, at("2032-02-29T00:00:00Z"));
+
+        let rule: Recurrence = "FREQ=WEEKLY;UNTIL=20261020".parse().unwrap();
+        let (next, _) = rule.next(at("2026-10-13T12:00:00Z")).unwrap();
+        assert_eq!(next, at("2026-10-20T12:00:00Z"));
+        assert_eq!(rule.next(next), None);
+    }
+}
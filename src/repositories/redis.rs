@@ -0,0 +1,986 @@
+use super::{
+    check_blocked_by, check_project_deletable, label_name_key, project_todo_ids, reorder_positions,
+    ApiKey, CreateApiKey, CreateLabel, CreateProject, CreateTodo, CreateUser, Label, Nearby, Page,
+    Pomodoro, Project, ReorderTodo, RepositoryError, ShareLink, TimeEntry, Todo, TodoFilter,
+    TodoRepository, TodoSort, UpdateLabel, UpdateProject, UpdateTodo, User, UserRepository,
+    DEFAULT_PROJECT_ID, POMODORO_MINUTES,
+};
+use axum::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use redis::{aio::ConnectionManager, AsyncCommands};
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::HashMap;
+
+const DEFAULT_NAMESPACE: &str = "rust_todo";
+
+const TODOS: &str = "todos";
+const TIME_ENTRIES: &str = "time_entries";
+const POMODOROS: &str = "pomodoros";
+/// todo id -> id of its running time entry.
+const RUNNING_TIMERS: &str = "running_timers";
+/// todo id -> id of its running pomodoro.
+const RUNNING_POMODOROS: &str = "running_pomodoros";
+const LABELS: &str = "labels";
+/// label name, per user -> label id, claimed with `HSETNX` to keep names
+/// unique; see `label_name_key`.
+const LABEL_NAMES: &str = "label_names";
+/// Keyed by token rather than id.
+const SHARE_LINKS: &str = "share_links";
+const PROJECTS: &str = "projects";
+const USERS: &str = "users";
+/// user name -> user id, claimed like label names.
+const USER_NAMES: &str = "user_names";
+const API_KEYS: &str = "api_keys";
+/// key hash -> api key id, for looking keys up as they are presented.
+const API_KEY_HASHES: &str = "api_key_hashes";
+
+/// Stores todo `ARGV[1]` as `ARGV[2]` in hash `KEYS[1]` unless that would go
+/// past `ARGV[4]` todos of user `ARGV[6]`, or `ARGV[5]` of theirs in project
+/// `ARGV[3]`; an empty limit is none, and an empty user the todos without
+/// one. Redis runs one script at a time, so counting and storing can not
+/// interleave with another create. Answers whether it stored the todo and
+/// the usage it counted.
+const LIMITED_CREATE: &str = r#"
+    local project_id = tonumber(ARGV[3])
+    local user_id = tonumber(ARGV[6])
+    local usage = 0
+    local project_usage = 0
+    for _, json in ipairs(redis.call('HVALS', KEYS[1])) do
+        local todo = cjson.decode(json)
+        local owner = todo.user_id
+        if owner == cjson.null then
+            owner = nil
+        end
+        if owner == user_id then
+            usage = usage + 1
+            if todo.project_id == project_id then
+                project_usage = project_usage + 1
+            end
+        end
+    end
+    local max_todos = tonumber(ARGV[4])
+    local max_project_todos = tonumber(ARGV[5])
+    if (max_todos and usage >= max_todos)
+        or (max_project_todos and project_usage >= max_project_todos) then
+        return {0, usage, project_usage}
+    end
+    redis.call('HSET', KEYS[1], ARGV[1], ARGV[2])
+    return {1, usage, project_usage}
+"#;
+
+/// Keeps every record as JSON in one hash per kind, keyed by id, so several
+/// app instances can share state. Keys never expire.
+///
+/// Starting and stopping a timer or pomodoro is claimed atomically with
+/// `HSETNX` / `HDEL`, so two instances cannot run one twice for a todo.
+/// Other updates read, modify and write back a record; the last write wins.
+///
+/// Todos carry copies of their labels, which are rewritten whenever a label
+/// is renamed or deleted.
+///
+/// Reordering rewrites the position of every todo it shifts. Two reorders at
+/// once may leave todos sharing a position, which then sort by id.
+#[derive(Clone)]
+pub struct TodoRepositoryForRedis {
+    connection: ConnectionManager,
+    namespace: String,
+}
+
+impl TodoRepositoryForRedis {
+    pub async fn new(client: redis::Client) -> anyhow::Result<Self> {
+        Self::with_namespace(client, DEFAULT_NAMESPACE).await
+    }
+
+    /// Prefixes every key with `namespace`, so instances that must not see
+    /// each other's todos can share one Redis database.
+    pub async fn with_namespace(client: redis::Client, namespace: &str) -> anyhow::Result<Self> {
+        let connection = ConnectionManager::new(client).await?;
+        let repository = Self {
+            connection,
+            namespace: namespace.to_string(),
+        };
+        repository.create_default_project().await?;
+        Ok(repository)
+    }
+
+    /// Creates the default project unless an earlier start already did, and
+    /// moves the id counter past it.
+    async fn create_default_project(&self) -> anyhow::Result<()> {
+        let json = serde_json::to_string(&Project::inbox())?;
+        let _: bool = self
+            .connection()
+            .hset_nx(self.key(PROJECTS), DEFAULT_PROJECT_ID, json)
+            .await?;
+        let _: bool = self
+            .connection()
+            .set_nx(
+                self.key(&format!("{}:next_id", PROJECTS)),
+                DEFAULT_PROJECT_ID,
+            )
+            .await?;
+        Ok(())
+    }
+
+    fn key(&self, name: &str) -> String {
+        format!("{}:{}", self.namespace, name)
+    }
+
+    fn connection(&self) -> ConnectionManager {
+        self.connection.clone()
+    }
+
+    async fn next_id(&self, name: &str) -> anyhow::Result<i32> {
+        let id: i32 = self
+            .connection()
+            .incr(self.key(&format!("{}:next_id", name)), 1)
+            .await?;
+        Ok(id)
+    }
+
+    async fn get<V: DeserializeOwned>(&self, name: &str, id: i32) -> anyhow::Result<Option<V>> {
+        let json: Option<String> = self.connection().hget(self.key(name), id).await?;
+        let value = json.map(|json| serde_json::from_str(&json)).transpose()?;
+        Ok(value)
+    }
+
+    async fn put<V: Serialize>(&self, name: &str, id: i32, value: &V) -> anyhow::Result<()> {
+        let json = serde_json::to_string(value)?;
+        let _: () = self.connection().hset(self.key(name), id, json).await?;
+        Ok(())
+    }
+
+    async fn values<V: DeserializeOwned>(&self, name: &str) -> anyhow::Result<Vec<V>> {
+        let jsons: Vec<String> = self.connection().hvals(self.key(name)).await?;
+        let values = jsons
+            .iter()
+            .map(|json| serde_json::from_str(json))
+            .collect::<Result<_, _>>()?;
+        Ok(values)
+    }
+
+    async fn remove(&self, name: &str, ids: &[i32]) -> anyhow::Result<usize> {
+        if ids.is_empty() {
+            return Ok(0);
+        }
+        let removed: usize = self.connection().hdel(self.key(name), ids).await?;
+        Ok(removed)
+    }
+
+    async fn find_pomodoro(&self, pomodoro_id: i32) -> anyhow::Result<Pomodoro> {
+        let pomodoro = self
+            .get(POMODOROS, pomodoro_id)
+            .await?
+            .ok_or(RepositoryError::NotFound(pomodoro_id))?;
+        Ok(pomodoro)
+    }
+
+    /// The labels with `ids`, failing on the first that does not exist.
+    async fn labels_of(&self, ids: &[i32]) -> anyhow::Result<Vec<Label>> {
+        let mut labels = Vec::with_capacity(ids.len());
+        for id in ids {
+            let label = self
+                .get(LABELS, *id)
+                .await?
+                .ok_or(RepositoryError::NotFound(*id))?;
+            labels.push(label);
+        }
+        Ok(labels)
+    }
+
+    async fn claim_label_name(
+        &self,
+        user_id: Option<i32>,
+        name: &str,
+        id: i32,
+    ) -> anyhow::Result<()> {
+        let claimed: bool = self
+            .connection()
+            .hset_nx(self.key(LABEL_NAMES), label_name_key(user_id, name), id)
+            .await?;
+        if !claimed {
+            return Err(RepositoryError::Conflict(format!(
+                "label already exists, name is {}",
+                name
+            ))
+            .into());
+        }
+        Ok(())
+    }
+
+    async fn release_label_name(&self, label: &Label) -> anyhow::Result<()> {
+        let _: usize = self
+            .connection()
+            .hdel(
+                self.key(LABEL_NAMES),
+                label_name_key(label.user_id, &label.name),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Rewrites the todos that carry label `id`; see `Todo::replace_label`.
+    async fn replace_label(&self, id: i32, label: Option<&Label>) -> anyhow::Result<()> {
+        for mut todo in self.values::<Todo>(TODOS).await? {
+            if todo.replace_label(id, label) {
+                self.put(TODOS, todo.id, &todo).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn add_time_spent(&self, id: i32, seconds: i64) -> anyhow::Result<()> {
+        if let Some(mut todo) = self.get::<Todo>(TODOS, id).await? {
+            todo.time_spent += seconds;
+            self.put(TODOS, id, &todo).await?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl TodoRepository for TodoRepositoryForRedis {
+    async fn create(&self, payload: CreateTodo) -> anyhow::Result<Todo> {
+        let labels = self.labels_of(&payload.label_ids).await?;
+        self.find_project(payload.project_id).await?;
+        if let Some(parent_id) = payload.parent_id {
+            self.find(parent_id).await?;
+        }
+        if !payload.blocked_by.is_empty() {
+            // A new todo has no dependents yet, so 0 stands in for its id.
+            let todos: Vec<Todo> = self.values(TODOS).await?;
+            check_blocked_by(0, &payload.blocked_by, &todos)?;
+        }
+        let id = self.next_id(TODOS).await?;
+        let mut todo = Todo::new(id, payload.text);
+        todo.due_date = payload.due_date;
+        todo.priority = payload.priority;
+        todo.parent_id = payload.parent_id;
+        todo.project_id = payload.project_id;
+        todo.user_id = payload.user_id;
+        todo.recurrence = payload.recurrence;
+        todo.lat = payload.lat;
+        todo.lon = payload.lon;
+        todo.place = payload.place;
+        todo.remind_at = payload.remind_at;
+        todo.set_labels(labels);
+        todo.set_blocked_by(payload.blocked_by);
+        let limits = payload.limits;
+        if limits.is_unlimited() {
+            self.put(TODOS, id, &todo).await?;
+        } else {
+            let (stored, usage, project_usage): (bool, usize, usize) =
+                redis::Script::new(LIMITED_CREATE)
+                    .key(self.key(TODOS))
+                    .arg(id)
+                    .arg(serde_json::to_string(&todo)?)
+                    .arg(todo.project_id)
+                    .arg(
+                        limits
+                            .max_todos
+                            .map(|max| max.to_string())
+                            .unwrap_or_default(),
+                    )
+                    .arg(
+                        limits
+                            .max_project_todos
+                            .map(|max| max.to_string())
+                            .unwrap_or_default(),
+                    )
+                    .arg(
+                        todo.user_id
+                            .map(|user_id| user_id.to_string())
+                            .unwrap_or_default(),
+                    )
+                    .invoke_async(&mut self.connection())
+                    .await?;
+            if !stored {
+                limits.check(todo.project_id, usage, project_usage)?;
+            }
+        }
+
+        Ok(todo)
+    }
+
+    async fn find(&self, id: i32) -> anyhow::Result<Todo> {
+        let todo = self
+            .get(TODOS, id)
+            .await?
+            .ok_or(RepositoryError::NotFound(id))?;
+        Ok(todo)
+    }
+
+    async fn all(
+        &self,
+        filter: &TodoFilter,
+        sort: TodoSort,
+        page: Page,
+    ) -> anyhow::Result<Vec<Todo>> {
+        let mut todos: Vec<Todo> = self
+            .values(TODOS)
+            .await?
+            .into_iter()
+            .filter(|todo| filter.matches(todo))
+            .collect();
+        todos.sort_by(|a, b| sort.compare(a, b));
+
+        Ok(page.apply(todos.into_iter()).collect())
+    }
+
+    async fn count(&self, filter: &TodoFilter) -> anyhow::Result<usize> {
+        if *filter == TodoFilter::default() {
+            let count: usize = self.connection().hlen(self.key(TODOS)).await?;
+            return Ok(count);
+        }
+        let todos: Vec<Todo> = self.values(TODOS).await?;
+        Ok(todos.iter().filter(|todo| filter.matches(todo)).count())
+    }
+
+    async fn update(&self, id: i32, payload: UpdateTodo) -> anyhow::Result<Todo> {
+        let old_todo = self.find(id).await?;
+        if let Some(project_id) = payload.project_id {
+            self.find_project(project_id).await?;
+        }
+        let was_completed = old_todo.completed;
+        let mut todo = Todo {
+            text: payload.text.unwrap_or(old_todo.text),
+            completed: payload.completed.unwrap_or(old_todo.completed),
+            due_date: payload.due_date.unwrap_or(old_todo.due_date),
+            priority: payload.priority.unwrap_or(old_todo.priority),
+            project_id: payload.project_id.unwrap_or(old_todo.project_id),
+            recurrence: payload.recurrence.unwrap_or(old_todo.recurrence),
+            lat: payload.lat.unwrap_or(old_todo.lat),
+            lon: payload.lon.unwrap_or(old_todo.lon),
+            place: payload.place.unwrap_or(old_todo.place),
+            remind_at: payload.remind_at.unwrap_or(old_todo.remind_at),
+            updated_at: Utc::now(),
+            ..old_todo
+        };
+        if let Some(label_ids) = &payload.label_ids {
+            todo.set_labels(self.labels_of(label_ids).await?);
+        }
+        if let Some(blocked_by) = payload.blocked_by {
+            let todos: Vec<Todo> = self.values(TODOS).await?;
+            check_blocked_by(id, &blocked_by, &todos)?;
+            todo.set_blocked_by(blocked_by);
+        }
+        self.put(TODOS, id, &todo).await?;
+        if todo.completed && !was_completed {
+            if let Some(next) = todo.next_occurrence(Utc::now()) {
+                self.create(next).await?;
+            }
+        }
+
+        Ok(todo)
+    }
+
+    async fn delete(&self, id: i32) -> anyhow::Result<()> {
+        let todos: Vec<Todo> = self.values(TODOS).await?;
+        if todos.iter().any(|todo| todo.parent_id == Some(id)) {
+            return Err(
+                RepositoryError::Conflict(format!("todo has subtasks, id is {}", id)).into(),
+            );
+        }
+        if self.remove(TODOS, &[id]).await? == 0 {
+            return Err(RepositoryError::NotFound(id).into());
+        }
+        for mut todo in todos {
+            if todo.unblock(id) {
+                self.put(TODOS, todo.id, &todo).await?;
+            }
+        }
+
+        let entry_ids: Vec<i32> = self
+            .values::<TimeEntry>(TIME_ENTRIES)
+            .await?
+            .iter()
+            .filter(|entry| entry.todo_id == id)
+            .map(|entry| entry.id)
+            .collect();
+        self.remove(TIME_ENTRIES, &entry_ids).await?;
+        self.remove(RUNNING_TIMERS, &[id]).await?;
+
+        let pomodoro_ids: Vec<i32> = self
+            .values::<Pomodoro>(POMODOROS)
+            .await?
+            .iter()
+            .filter(|pomodoro| pomodoro.todo_id == id)
+            .map(|pomodoro| pomodoro.id)
+            .collect();
+        self.remove(POMODOROS, &pomodoro_ids).await?;
+        self.remove(RUNNING_POMODOROS, &[id]).await?;
+
+        let tokens: Vec<String> = self
+            .values::<ShareLink>(SHARE_LINKS)
+            .await?
+            .into_iter()
+            .filter(|link| link.todo_id == id)
+            .map(|link| link.token)
+            .collect();
+        if !tokens.is_empty() {
+            let _: usize = self
+                .connection()
+                .hdel(self.key(SHARE_LINKS), tokens)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn snooze(&self, id: i32, until: Option<DateTime<Utc>>) -> anyhow::Result<Todo> {
+        let todo = Todo {
+            snoozed_until: until,
+            updated_at: Utc::now(),
+            ..self.find(id).await?
+        };
+        self.put(TODOS, id, &todo).await?;
+
+        Ok(todo)
+    }
+
+    async fn start_timer(&self, id: i32) -> anyhow::Result<TimeEntry> {
+        self.find(id).await?;
+        let entry_id = self.next_id(TIME_ENTRIES).await?;
+        let claimed: bool = self
+            .connection()
+            .hset_nx(self.key(RUNNING_TIMERS), id, entry_id)
+            .await?;
+        if !claimed {
+            return Err(
+                RepositoryError::Conflict(format!("timer already running, id is {}", id)).into(),
+            );
+        }
+        let entry = TimeEntry {
+            id: entry_id,
+            todo_id: id,
+            started_at: Utc::now(),
+            stopped_at: None,
+        };
+        self.put(TIME_ENTRIES, entry_id, &entry).await?;
+
+        Ok(entry)
+    }
+
+    async fn stop_timer(&self, id: i32) -> anyhow::Result<TimeEntry> {
+        self.find(id).await?;
+        let no_running_timer =
+            || RepositoryError::Conflict(format!("no running timer, id is {}", id));
+        let entry_id: Option<i32> = self.connection().hget(self.key(RUNNING_TIMERS), id).await?;
+        let entry_id = entry_id.ok_or_else(no_running_timer)?;
+        if self.remove(RUNNING_TIMERS, &[id]).await? == 0 {
+            return Err(no_running_timer().into());
+        }
+
+        let entry = TimeEntry {
+            stopped_at: Some(Utc::now()),
+            ..self.get(TIME_ENTRIES, entry_id).await?.ok_or_else(|| {
+                RepositoryError::Unexpected(format!("lost time entry {}", entry_id))
+            })?
+        };
+        self.put(TIME_ENTRIES, entry_id, &entry).await?;
+        self.add_time_spent(id, entry.duration()).await?;
+
+        Ok(entry)
+    }
+
+    async fn time_entries(&self, id: i32) -> anyhow::Result<Vec<TimeEntry>> {
+        self.find(id).await?;
+        let mut entries: Vec<TimeEntry> = self
+            .values(TIME_ENTRIES)
+            .await?
+            .into_iter()
+            .filter(|entry: &TimeEntry| entry.todo_id == id)
+            .collect();
+        entries.sort_by_key(|entry| entry.id);
+
+        Ok(entries)
+    }
+
+    async fn start_pomodoro(&self, id: i32) -> anyhow::Result<Pomodoro> {
+        self.find(id).await?;
+        let pomodoro_id = self.next_id(POMODOROS).await?;
+        let claimed: bool = self
+            .connection()
+            .hset_nx(self.key(RUNNING_POMODOROS), id, pomodoro_id)
+            .await?;
+        if !claimed {
+            return Err(RepositoryError::Conflict(format!(
+                "pomodoro already running, id is {}",
+                id
+            ))
+            .into());
+        }
+        let started_at = Utc::now();
+        let pomodoro = Pomodoro {
+            id: pomodoro_id,
+            todo_id: id,
+            started_at,
+            ends_at: started_at + Duration::minutes(POMODORO_MINUTES),
+            interruptions: 0,
+            finished_at: None,
+            completed: false,
+        };
+        self.put(POMODOROS, pomodoro_id, &pomodoro).await?;
+
+        Ok(pomodoro)
+    }
+
+    async fn interrupt_pomodoro(&self, pomodoro_id: i32) -> anyhow::Result<Pomodoro> {
+        let mut pomodoro = self.find_pomodoro(pomodoro_id).await?;
+        if pomodoro.finished_at.is_some() {
+            return Err(RepositoryError::Conflict(format!(
+                "pomodoro already finished, id is {}",
+                pomodoro_id
+            ))
+            .into());
+        }
+        pomodoro.interruptions += 1;
+        self.put(POMODOROS, pomodoro_id, &pomodoro).await?;
+
+        Ok(pomodoro)
+    }
+
+    async fn finish_pomodoro(&self, pomodoro_id: i32) -> anyhow::Result<Pomodoro> {
+        let mut pomodoro = self.find_pomodoro(pomodoro_id).await?;
+        if pomodoro.finished_at.is_some()
+            || self.remove(RUNNING_POMODOROS, &[pomodoro.todo_id]).await? == 0
+        {
+            return Err(RepositoryError::Conflict(format!(
+                "pomodoro already finished, id is {}",
+                pomodoro_id
+            ))
+            .into());
+        }
+        let finished_at = Utc::now();
+        pomodoro.finished_at = Some(finished_at);
+        pomodoro.completed = finished_at >= pomodoro.ends_at;
+        self.put(POMODOROS, pomodoro_id, &pomodoro).await?;
+
+        if pomodoro.completed {
+            let entry_id = self.next_id(TIME_ENTRIES).await?;
+            let entry = TimeEntry {
+                id: entry_id,
+                todo_id: pomodoro.todo_id,
+                started_at: pomodoro.started_at,
+                stopped_at: Some(pomodoro.ends_at),
+            };
+            self.put(TIME_ENTRIES, entry_id, &entry).await?;
+            self.add_time_spent(pomodoro.todo_id, pomodoro.duration())
+                .await?;
+        }
+
+        Ok(pomodoro)
+    }
+
+    async fn pomodoro(&self, pomodoro_id: i32) -> anyhow::Result<Pomodoro> {
+        self.find_pomodoro(pomodoro_id).await
+    }
+
+    async fn pomodoros(&self, id: i32) -> anyhow::Result<Vec<Pomodoro>> {
+        self.find(id).await?;
+        let mut pomodoros: Vec<Pomodoro> = self
+            .values(POMODOROS)
+            .await?
+            .into_iter()
+            .filter(|pomodoro: &Pomodoro| pomodoro.todo_id == id)
+            .collect();
+        pomodoros.sort_by_key(|pomodoro| pomodoro.id);
+
+        Ok(pomodoros)
+    }
+
+    async fn completed_pomodoros(&self, user_id: Option<i32>) -> anyhow::Result<Vec<Pomodoro>> {
+        let todos: HashMap<i32, Option<i32>> = self
+            .values(TODOS)
+            .await?
+            .into_iter()
+            .map(|todo: Todo| (todo.id, todo.user_id))
+            .collect();
+        let mut pomodoros: Vec<Pomodoro> = self
+            .values(POMODOROS)
+            .await?
+            .into_iter()
+            .filter(|pomodoro: &Pomodoro| {
+                pomodoro.completed
+                    && (user_id.is_none() || todos.get(&pomodoro.todo_id) == Some(&user_id))
+            })
+            .collect();
+        pomodoros.sort_by_key(|pomodoro| pomodoro.id);
+
+        Ok(pomodoros)
+    }
+
+    async fn create_label(&self, payload: CreateLabel) -> anyhow::Result<Label> {
+        let id = self.next_id(LABELS).await?;
+        self.claim_label_name(payload.user_id, &payload.name, id)
+            .await?;
+        let label = Label {
+            id,
+            name: payload.name,
+            user_id: payload.user_id,
+        };
+        self.put(LABELS, id, &label).await?;
+
+        Ok(label)
+    }
+
+    async fn find_label(&self, id: i32) -> anyhow::Result<Label> {
+        let label = self
+            .get(LABELS, id)
+            .await?
+            .ok_or(RepositoryError::NotFound(id))?;
+        Ok(label)
+    }
+
+    async fn all_labels(&self, user_id: Option<i32>) -> anyhow::Result<Vec<Label>> {
+        let mut labels: Vec<Label> = self.values(LABELS).await?;
+        labels.retain(|label| user_id.is_none() || label.user_id == user_id);
+        labels.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(labels)
+    }
+
+    async fn update_label(&self, id: i32, payload: UpdateLabel) -> anyhow::Result<Label> {
+        let mut label = self.find_label(id).await?;
+        match payload.name {
+            Some(name) if name != label.name => {
+                self.claim_label_name(label.user_id, &name, id).await?;
+                self.release_label_name(&label).await?;
+                label.name = name;
+            }
+            _ => return Ok(label),
+        }
+        self.put(LABELS, id, &label).await?;
+        self.replace_label(id, Some(&label)).await?;
+
+        Ok(label)
+    }
+
+    async fn delete_label(&self, id: i32) -> anyhow::Result<()> {
+        let label = self.find_label(id).await?;
+        if self.remove(LABELS, &[id]).await? == 0 {
+            return Err(RepositoryError::NotFound(id).into());
+        }
+        self.release_label_name(&label).await?;
+        self.replace_label(id, None).await?;
+
+        Ok(())
+    }
+
+    async fn blockers(&self, id: i32) -> anyhow::Result<Vec<Todo>> {
+        let todo = self.find(id).await?;
+        let mut blockers = Vec::new();
+        for blocker in todo.blocked_by.iter() {
+            match self.get::<Todo>(TODOS, *blocker).await? {
+                Some(blocker) if !blocker.completed => blockers.push(blocker),
+                _ => {}
+            }
+        }
+        Ok(blockers)
+    }
+
+    async fn nearby(&self, nearby: &Nearby) -> anyhow::Result<Vec<Todo>> {
+        let todos: Vec<Todo> = self.values(TODOS).await?;
+        Ok(nearby.select(todos))
+    }
+
+    async fn create_share_link(&self, link: ShareLink) -> anyhow::Result<ShareLink> {
+        self.find(link.todo_id).await?;
+        let json = serde_json::to_string(&link)?;
+        let _: () = self
+            .connection()
+            .hset(self.key(SHARE_LINKS), &link.token, json)
+            .await?;
+
+        Ok(link)
+    }
+
+    async fn find_share_link(&self, token: &str) -> anyhow::Result<Option<ShareLink>> {
+        let json: Option<String> = self.connection().hget(self.key(SHARE_LINKS), token).await?;
+        let link = json.map(|json| serde_json::from_str(&json)).transpose()?;
+        Ok(link)
+    }
+
+    async fn delete_share_link(&self, id: i32, token: &str) -> anyhow::Result<()> {
+        match self.find_share_link(token).await? {
+            Some(link) if link.todo_id == id => {}
+            _ => return Err(RepositoryError::NotFound(id).into()),
+        }
+        let removed: usize = self.connection().hdel(self.key(SHARE_LINKS), token).await?;
+        if removed == 0 {
+            return Err(RepositoryError::NotFound(id).into());
+        }
+
+        Ok(())
+    }
+
+    async fn reorder(&self, id: i32, payload: ReorderTodo) -> anyhow::Result<Todo> {
+        let todos: Vec<Todo> = self.values(TODOS).await?;
+        let positions: HashMap<i32, i64> = reorder_positions(id, &payload, &todos)?
+            .into_iter()
+            .collect();
+        for mut todo in todos {
+            if let Some(position) = positions.get(&todo.id) {
+                todo.position = *position;
+                self.put(TODOS, todo.id, &todo).await?;
+            }
+        }
+        self.find(id).await
+    }
+
+    async fn create_project(&self, payload: CreateProject) -> anyhow::Result<Project> {
+        let project = Project {
+            id: self.next_id(PROJECTS).await?,
+            name: payload.name,
+            user_id: payload.user_id,
+        };
+        self.put(PROJECTS, project.id, &project).await?;
+
+        Ok(project)
+    }
+
+    async fn find_project(&self, id: i32) -> anyhow::Result<Project> {
+        let project = self
+            .get(PROJECTS, id)
+            .await?
+            .ok_or(RepositoryError::NotFound(id))?;
+        Ok(project)
+    }
+
+    async fn all_projects(&self, user_id: Option<i32>) -> anyhow::Result<Vec<Project>> {
+        let mut projects: Vec<Project> = self.values(PROJECTS).await?;
+        projects.retain(|project| project.listed_for(user_id));
+        projects.sort_by_key(|project| project.id);
+        Ok(projects)
+    }
+
+    async fn update_project(&self, id: i32, payload: UpdateProject) -> anyhow::Result<Project> {
+        let mut project = self.find_project(id).await?;
+        if let Some(name) = payload.name {
+            project.name = name;
+        }
+        self.put(PROJECTS, id, &project).await?;
+
+        Ok(project)
+    }
+
+    async fn delete_project(&self, id: i32) -> anyhow::Result<()> {
+        check_project_deletable(id)?;
+        let project = self.find_project(id).await?;
+        let todos: Vec<Todo> = self.values(TODOS).await?;
+        for todo_id in project_todo_ids(&project, &todos)? {
+            self.delete(todo_id).await?;
+        }
+        self.remove(PROJECTS, &[id]).await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl UserRepository for TodoRepositoryForRedis {
+    async fn create_user(&self, payload: CreateUser) -> anyhow::Result<User> {
+        let id = self.next_id(USERS).await?;
+        let claimed: bool = self
+            .connection()
+            .hset_nx(self.key(USER_NAMES), &payload.name, id)
+            .await?;
+        if !claimed {
+            return Err(RepositoryError::Conflict(format!(
+                "user already exists, name is {}",
+                payload.name
+            ))
+            .into());
+        }
+        let user = payload.into_user(id);
+        self.put(USERS, id, &user).await?;
+
+        Ok(user)
+    }
+
+    async fn find_user_by_name(&self, name: &str) -> anyhow::Result<Option<User>> {
+        let id: Option<i32> = self.connection().hget(self.key(USER_NAMES), name).await?;
+        match id {
+            Some(id) => self.get(USERS, id).await,
+            None => Ok(None),
+        }
+    }
+
+    async fn create_api_key(&self, payload: CreateApiKey) -> anyhow::Result<ApiKey> {
+        let id = self.next_id(API_KEYS).await?;
+        let key = payload.into_api_key(id);
+        self.put(API_KEYS, id, &key).await?;
+        let _: () = self
+            .connection()
+            .hset(self.key(API_KEY_HASHES), &key.key_hash, id)
+            .await?;
+
+        Ok(key)
+    }
+
+    async fn find_api_key_by_hash(&self, key_hash: &str) -> anyhow::Result<Option<ApiKey>> {
+        let id: Option<i32> = self
+            .connection()
+            .hget(self.key(API_KEY_HASHES), key_hash)
+            .await?;
+        match id {
+            Some(id) => self.get(API_KEYS, id).await,
+            None => Ok(None),
+        }
+    }
+
+    async fn api_keys(&self, user_id: i32) -> anyhow::Result<Vec<ApiKey>> {
+        let mut keys: Vec<ApiKey> = self.values(API_KEYS).await?;
+        keys.retain(|key| key.user_id == user_id);
+        keys.sort_by_key(|key| key.id);
+        Ok(keys)
+    }
+
+    async fn delete_api_key(&self, user_id: i32, id: i32) -> anyhow::Result<()> {
+        let key = self
+            .get::<ApiKey>(API_KEYS, id)
+            .await?
+            .filter(|key| key.user_id == user_id)
+            .ok_or(RepositoryError::NotFound(id))?;
+        let _: usize = self
+            .connection()
+            .hdel(self.key(API_KEY_HASHES), &key.key_hash)
+            .await?;
+        self.remove(API_KEYS, &[id]).await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "redis-test"))]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn crud_scenario_redis() {
+        use dotenv::dotenv;
+        use std::env;
+
+        dotenv().ok();
+        let redis_url = &env::var("REDIS_URL").expect("undefined env variable $REDIS_URL");
+        let client = redis::Client::open(redis_url.as_str())
+            .unwrap_or_else(|_| panic!("invalid redis url [{}]", redis_url));
+        let namespace = format!("rust_todo_test:{}", Utc::now().timestamp_millis());
+        let repository = TodoRepositoryForRedis::with_namespace(client, &namespace)
+            .await
+            .unwrap_or_else(|_| panic!("failed connect redis, url is [{}]", redis_url));
+        let todo_text = "[crud_scenario] text";
+
+        // create
+        let created = repository
+            .create(CreateTodo::new(todo_text.to_string()))
+            .await
+            .expect("[create] returned Err");
+        assert_eq!(created.text, todo_text);
+        assert!(!created.completed);
+
+        // find
+        let todo = repository
+            .find(created.id)
+            .await
+            .expect("[find] returned Err");
+        assert_eq!(created, todo);
+
+        // all
+        let todos = repository
+            .all(&TodoFilter::default(), TodoSort::default(), Page::default())
+            .await
+            .expect("[all] returned Err");
+        assert_eq!(vec![created.clone()], todos);
+        assert_eq!(
+            repository
+                .count(&TodoFilter::default())
+                .await
+                .expect("[count] returned Err"),
+            1
+        );
+
+        // update
+        let updated_text = "[crud_scenario] updated text";
+        let todo = repository
+            .update(
+                created.id,
+                UpdateTodo {
+                    text: Some(updated_text.to_string()),
+                    completed: Some(true),
+                    label_ids: None,
+                    due_date: None,
+                    priority: None,
+                    blocked_by: None,
+                    project_id: None,
+                    recurrence: None,
+                    lat: None,
+                    lon: None,
+                    place: None,
+                    remind_at: None,
+                },
+            )
+            .await
+            .expect("[update] returned Err");
+        assert_eq!(created.id, todo.id);
+        assert_eq!(todo.text, updated_text);
+        assert!(todo.completed);
+
+        // timer
+        let entry = repository
+            .start_timer(created.id)
+            .await
+            .expect("[start_timer] returned Err");
+        let res = repository.start_timer(created.id).await;
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::Conflict(_))
+        ));
+        let stopped = repository
+            .stop_timer(created.id)
+            .await
+            .expect("[stop_timer] returned Err");
+        assert_eq!(entry.id, stopped.id);
+        assert!(stopped.stopped_at.is_some());
+        let entries = repository
+            .time_entries(created.id)
+            .await
+            .expect("[time_entries] returned Err");
+        assert_eq!(vec![stopped], entries);
+
+        // pomodoro
+        let pomodoro = repository
+            .start_pomodoro(created.id)
+            .await
+            .expect("[start_pomodoro] returned Err");
+        let pomodoro = repository
+            .interrupt_pomodoro(pomodoro.id)
+            .await
+            .expect("[interrupt_pomodoro] returned Err");
+        assert_eq!(pomodoro.interruptions, 1);
+        let pomodoro = repository
+            .finish_pomodoro(pomodoro.id)
+            .await
+            .expect("[finish_pomodoro] returned Err");
+        assert!(!pomodoro.completed);
+        let res = repository.finish_pomodoro(pomodoro.id).await;
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::Conflict(_))
+        ));
+
+        // delete
+        repository
+            .delete(created.id)
+            .await
+            .expect("[delete] returned Err");
+        let res = repository.find(created.id).await;
+        assert!(res.is_err());
+        let res = repository.delete(created.id).await;
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::NotFound(_))
+        ));
+        let pomodoros: Vec<Pomodoro> = repository.values(POMODOROS).await.unwrap();
+        assert!(pomodoros.is_empty());
+    }
+}
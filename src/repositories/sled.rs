@@ -0,0 +1,987 @@
+use super::{
+    check_blocked_by, check_project_deletable, label_name_key, project_todo_ids, reorder_positions,
+    ApiKey, CreateApiKey, CreateLabel, CreateProject, CreateTodo, CreateUser, Label, Nearby, Page,
+    Pomodoro, Project, ReorderTodo, RepositoryError, ShareLink, TimeEntry, Todo, TodoFilter,
+    TodoRepository, TodoSort, UpdateLabel, UpdateProject, UpdateTodo, User, UserRepository,
+    DEFAULT_PROJECT_ID, POMODORO_MINUTES,
+};
+use axum::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use serde::{de::DeserializeOwned, Serialize};
+use sled::{Db, Tree};
+use std::sync::{Arc, Mutex};
+
+const COUNTERS: &str = "counters";
+const TODOS: &str = "todos";
+const TIME_ENTRIES: &str = "time_entries";
+const POMODOROS: &str = "pomodoros";
+/// todo id -> id of its running time entry.
+const RUNNING_TIMERS: &str = "running_timers";
+/// todo id -> id of its running pomodoro.
+const RUNNING_POMODOROS: &str = "running_pomodoros";
+const LABELS: &str = "labels";
+/// label name, per user -> label id, claimed with compare-and-swap to keep
+/// names unique; see `label_name_key`.
+const LABEL_NAMES: &str = "label_names";
+/// Keyed by token rather than id.
+const SHARE_LINKS: &str = "share_links";
+const PROJECTS: &str = "projects";
+const USERS: &str = "users";
+/// user name -> user id, claimed like label names.
+const USER_NAMES: &str = "user_names";
+const API_KEYS: &str = "api_keys";
+/// key hash -> api key id, for looking keys up as they are presented.
+const API_KEY_HASHES: &str = "api_key_hashes";
+
+/// Embedded storage in a sled database directory. Each record kind lives in
+/// its own tree as JSON, keyed by big-endian id so that iteration follows id
+/// order. Every mutation is flushed before it returns.
+///
+/// Todos carry copies of their labels, which are rewritten whenever a label
+/// is renamed or deleted.
+#[derive(Debug, Clone)]
+pub struct TodoRepositoryForSled {
+    db: Db,
+    todos: Tree,
+    time_entries: Tree,
+    pomodoros: Tree,
+    running_timers: Tree,
+    running_pomodoros: Tree,
+    labels: Tree,
+    label_names: Tree,
+    share_links: Tree,
+    projects: Tree,
+    users: Tree,
+    user_names: Tree,
+    api_keys: Tree,
+    api_key_hashes: Tree,
+    /// A sled database belongs to one process, so holding this while
+    /// reordering keeps two reorders from interleaving.
+    reorder_lock: Arc<Mutex<()>>,
+    /// Held from counting the todos until the new one is stored, so that
+    /// concurrent creates can not overshoot the limits.
+    create_lock: Arc<Mutex<()>>,
+}
+
+impl TodoRepositoryForSled {
+    pub fn new(db: Db) -> anyhow::Result<Self> {
+        let repository = Self {
+            todos: db.open_tree(TODOS)?,
+            time_entries: db.open_tree(TIME_ENTRIES)?,
+            pomodoros: db.open_tree(POMODOROS)?,
+            running_timers: db.open_tree(RUNNING_TIMERS)?,
+            running_pomodoros: db.open_tree(RUNNING_POMODOROS)?,
+            labels: db.open_tree(LABELS)?,
+            label_names: db.open_tree(LABEL_NAMES)?,
+            share_links: db.open_tree(SHARE_LINKS)?,
+            projects: db.open_tree(PROJECTS)?,
+            users: db.open_tree(USERS)?,
+            user_names: db.open_tree(USER_NAMES)?,
+            api_keys: db.open_tree(API_KEYS)?,
+            api_key_hashes: db.open_tree(API_KEY_HASHES)?,
+            reorder_lock: Arc::default(),
+            create_lock: Arc::default(),
+            db,
+        };
+        repository.create_default_project()?;
+        Ok(repository)
+    }
+
+    /// Creates the default project unless an earlier start already did, and
+    /// moves the id counter past it.
+    fn create_default_project(&self) -> anyhow::Result<()> {
+        let id = DEFAULT_PROJECT_ID.to_be_bytes();
+        // Either swap fails only because the value is already there.
+        let _ = self.projects.compare_and_swap(
+            id,
+            None as Option<&[u8]>,
+            Some(serde_json::to_vec(&Project::inbox())?),
+        )?;
+        let _ = self.db.open_tree(COUNTERS)?.compare_and_swap(
+            PROJECTS,
+            None as Option<&[u8]>,
+            Some(&id[..]),
+        )?;
+        Ok(())
+    }
+
+    async fn flush(&self) -> anyhow::Result<()> {
+        self.db.flush_async().await?;
+        Ok(())
+    }
+
+    fn next_id(&self, name: &str) -> anyhow::Result<i32> {
+        let counter = self
+            .db
+            .open_tree(COUNTERS)?
+            .update_and_fetch(name, |old| {
+                let id = old.map(decode_id).unwrap_or(0) + 1;
+                Some(id.to_be_bytes().to_vec())
+            })?
+            .expect("counter is always set by update");
+        Ok(decode_id(&counter))
+    }
+
+    fn find_pomodoro(&self, pomodoro_id: i32) -> anyhow::Result<Pomodoro> {
+        let pomodoro =
+            get(&self.pomodoros, pomodoro_id)?.ok_or(RepositoryError::NotFound(pomodoro_id))?;
+        Ok(pomodoro)
+    }
+
+    /// The labels with `ids`, failing on the first that does not exist.
+    fn labels_of(&self, ids: &[i32]) -> anyhow::Result<Vec<Label>> {
+        ids.iter()
+            .map(|id| get(&self.labels, *id)?.ok_or_else(|| RepositoryError::NotFound(*id).into()))
+            .collect()
+    }
+
+    fn claim_label_name(&self, user_id: Option<i32>, name: &str, id: i32) -> anyhow::Result<()> {
+        self.label_names
+            .compare_and_swap(
+                label_name_key(user_id, name),
+                None as Option<&[u8]>,
+                Some(&id.to_be_bytes()[..]),
+            )?
+            .map_err(|_| {
+                RepositoryError::Conflict(format!("label already exists, name is {}", name))
+            })?;
+        Ok(())
+    }
+
+    /// Rewrites the todos that carry label `id`; see `Todo::replace_label`.
+    fn replace_label(&self, id: i32, label: Option<&Label>) -> anyhow::Result<()> {
+        for entry in self.todos.iter() {
+            let (key, bytes) = entry?;
+            let todo_id = decode_id(&key);
+            let carries = serde_json::from_slice::<Todo>(&bytes)?
+                .labels
+                .iter()
+                .any(|label| label.id == id);
+            if carries {
+                modify(&self.todos, todo_id, |todo: &mut Todo| {
+                    todo.replace_label(id, label);
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// See `check_blocked_by`; the check only loads the todos when needed.
+    fn check_blocked_by(&self, id: i32, blocked_by: &[i32]) -> anyhow::Result<()> {
+        if blocked_by.is_empty() {
+            return Ok(());
+        }
+        let todos = values::<Todo>(&self.todos).collect::<anyhow::Result<Vec<_>>>()?;
+        check_blocked_by(id, blocked_by, &todos)
+    }
+
+    /// Drops todo `id` from the blockers of the todos it was blocking.
+    fn unblock(&self, id: i32) -> anyhow::Result<()> {
+        for entry in self.todos.iter() {
+            let (key, bytes) = entry?;
+            let todo_id = decode_id(&key);
+            if serde_json::from_slice::<Todo>(&bytes)?
+                .blocked_by
+                .contains(&id)
+            {
+                modify(&self.todos, todo_id, |todo: &mut Todo| {
+                    todo.unblock(id);
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    fn add_time_spent(&self, id: i32, seconds: i64) -> anyhow::Result<()> {
+        modify(&self.todos, id, |todo: &mut Todo| {
+            todo.time_spent += seconds
+        })?;
+        Ok(())
+    }
+}
+
+fn decode_id(bytes: &[u8]) -> i32 {
+    let mut id = [0; 4];
+    id.copy_from_slice(&bytes[..4]);
+    i32::from_be_bytes(id)
+}
+
+fn get<V: DeserializeOwned>(tree: &Tree, id: i32) -> anyhow::Result<Option<V>> {
+    let value = tree
+        .get(id.to_be_bytes())?
+        .map(|bytes| serde_json::from_slice(&bytes))
+        .transpose()?;
+    Ok(value)
+}
+
+fn put<V: Serialize>(tree: &Tree, id: i32, value: &V) -> anyhow::Result<()> {
+    tree.insert(id.to_be_bytes(), serde_json::to_vec(value)?)?;
+    Ok(())
+}
+
+fn values<V: DeserializeOwned>(tree: &Tree) -> impl DoubleEndedIterator<Item = anyhow::Result<V>> {
+    tree.iter()
+        .values()
+        .map(|bytes| Ok(serde_json::from_slice(&bytes?)?))
+}
+
+/// Atomically applies `f` to the record stored under `id`, returning the
+/// result, or `None` if there is no such record. `f` may run more than once
+/// when another writer races it.
+fn modify<V, F>(tree: &Tree, id: i32, mut f: F) -> anyhow::Result<Option<V>>
+where
+    V: Serialize + DeserializeOwned,
+    F: FnMut(&mut V),
+{
+    let bytes = tree.update_and_fetch(id.to_be_bytes(), |old| {
+        let old = old?;
+        match serde_json::from_slice::<V>(old) {
+            Ok(mut value) => {
+                f(&mut value);
+                serde_json::to_vec(&value).ok()
+            }
+            // Left untouched, so the error surfaces when reading it back below.
+            Err(_) => Some(old.to_vec()),
+        }
+    })?;
+    let value = bytes
+        .map(|bytes| serde_json::from_slice(&bytes))
+        .transpose()?;
+    Ok(value)
+}
+
+/// Removes every record of `tree` that belongs to todo `id`.
+fn remove_for_todo<V, F>(tree: &Tree, id: i32, todo_id: F) -> anyhow::Result<()>
+where
+    V: DeserializeOwned,
+    F: Fn(&V) -> i32,
+{
+    for entry in tree.iter() {
+        let (key, bytes) = entry?;
+        let value: V = serde_json::from_slice(&bytes)?;
+        if todo_id(&value) == id {
+            tree.remove(key)?;
+        }
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl TodoRepository for TodoRepositoryForSled {
+    async fn create(&self, payload: CreateTodo) -> anyhow::Result<Todo> {
+        let labels = self.labels_of(&payload.label_ids)?;
+        self.find_project(payload.project_id).await?;
+        if let Some(parent_id) = payload.parent_id {
+            self.find(parent_id).await?;
+        }
+        // A new todo has no dependents yet, so 0 stands in for its id.
+        self.check_blocked_by(0, &payload.blocked_by)?;
+        let todo = {
+            let _creating = self.create_lock.lock().unwrap();
+            if !payload.limits.is_unlimited() {
+                let todos = values::<Todo>(&self.todos).collect::<anyhow::Result<Vec<_>>>()?;
+                payload
+                    .limits
+                    .check_todos(payload.project_id, payload.user_id, &todos)?;
+            }
+            let mut todo = Todo::new(self.next_id(TODOS)?, payload.text);
+            todo.due_date = payload.due_date;
+            todo.priority = payload.priority;
+            todo.parent_id = payload.parent_id;
+            todo.project_id = payload.project_id;
+            todo.user_id = payload.user_id;
+            todo.recurrence = payload.recurrence;
+            todo.lat = payload.lat;
+            todo.lon = payload.lon;
+            todo.place = payload.place;
+            todo.remind_at = payload.remind_at;
+            todo.set_labels(labels);
+            todo.set_blocked_by(payload.blocked_by);
+            put(&self.todos, todo.id, &todo)?;
+            todo
+        };
+        self.flush().await?;
+
+        Ok(todo)
+    }
+
+    async fn find(&self, id: i32) -> anyhow::Result<Todo> {
+        let todo = get(&self.todos, id)?.ok_or(RepositoryError::NotFound(id))?;
+        Ok(todo)
+    }
+
+    async fn all(
+        &self,
+        filter: &TodoFilter,
+        sort: TodoSort,
+        page: Page,
+    ) -> anyhow::Result<Vec<Todo>> {
+        let matching = values::<Todo>(&self.todos).rev().filter(|todo| {
+            todo.as_ref()
+                .map(|todo| filter.matches(todo))
+                .unwrap_or(true)
+        });
+        // Reversed key order is already newest first, so only other orders
+        // need the whole listing in memory.
+        if sort == TodoSort::default() {
+            return page.apply(matching).collect();
+        }
+        let mut todos = matching.collect::<anyhow::Result<Vec<Todo>>>()?;
+        todos.sort_by(|a, b| sort.compare(a, b));
+        Ok(page.apply(todos.into_iter()).collect())
+    }
+
+    async fn count(&self, filter: &TodoFilter) -> anyhow::Result<usize> {
+        if *filter == TodoFilter::default() {
+            return Ok(self.todos.len());
+        }
+        let mut count = 0;
+        for todo in values::<Todo>(&self.todos) {
+            if filter.matches(&todo?) {
+                count += 1;
+            }
+        }
+
+        Ok(count)
+    }
+
+    async fn update(&self, id: i32, payload: UpdateTodo) -> anyhow::Result<Todo> {
+        let labels = payload
+            .label_ids
+            .as_deref()
+            .map(|ids| self.labels_of(ids))
+            .transpose()?;
+        if let Some(blocked_by) = &payload.blocked_by {
+            self.check_blocked_by(id, blocked_by)?;
+        }
+        if let Some(project_id) = payload.project_id {
+            self.find_project(project_id).await?;
+        }
+        let mut was_completed = false;
+        let todo = modify(&self.todos, id, |todo: &mut Todo| {
+            was_completed = todo.completed;
+            if let Some(text) = &payload.text {
+                todo.text = text.clone();
+            }
+            if let Some(completed) = payload.completed {
+                todo.completed = completed;
+            }
+            if let Some(due_date) = payload.due_date {
+                todo.due_date = due_date;
+            }
+            if let Some(priority) = payload.priority {
+                todo.priority = priority;
+            }
+            if let Some(project_id) = payload.project_id {
+                todo.project_id = project_id;
+            }
+            if let Some(recurrence) = &payload.recurrence {
+                todo.recurrence = recurrence.clone();
+            }
+            if let Some(lat) = payload.lat {
+                todo.lat = lat;
+            }
+            if let Some(lon) = payload.lon {
+                todo.lon = lon;
+            }
+            if let Some(place) = &payload.place {
+                todo.place = place.clone();
+            }
+            if let Some(remind_at) = payload.remind_at {
+                todo.remind_at = remind_at;
+            }
+            if let Some(labels) = &labels {
+                todo.set_labels(labels.clone());
+            }
+            if let Some(blocked_by) = &payload.blocked_by {
+                todo.set_blocked_by(blocked_by.clone());
+            }
+            todo.updated_at = Utc::now();
+        })?
+        .ok_or(RepositoryError::NotFound(id))?;
+        self.flush().await?;
+        if todo.completed && !was_completed {
+            if let Some(next) = todo.next_occurrence(Utc::now()) {
+                self.create(next).await?;
+            }
+        }
+
+        Ok(todo)
+    }
+
+    async fn delete(&self, id: i32) -> anyhow::Result<()> {
+        for todo in values::<Todo>(&self.todos) {
+            if todo?.parent_id == Some(id) {
+                return Err(
+                    RepositoryError::Conflict(format!("todo has subtasks, id is {}", id)).into(),
+                );
+            }
+        }
+        self.todos
+            .remove(id.to_be_bytes())?
+            .ok_or(RepositoryError::NotFound(id))?;
+        self.unblock(id)?;
+        remove_for_todo(&self.time_entries, id, |entry: &TimeEntry| entry.todo_id)?;
+        remove_for_todo(&self.pomodoros, id, |pomodoro: &Pomodoro| pomodoro.todo_id)?;
+        remove_for_todo(&self.share_links, id, |link: &ShareLink| link.todo_id)?;
+        self.running_timers.remove(id.to_be_bytes())?;
+        self.running_pomodoros.remove(id.to_be_bytes())?;
+        self.flush().await?;
+
+        Ok(())
+    }
+
+    async fn snooze(&self, id: i32, until: Option<DateTime<Utc>>) -> anyhow::Result<Todo> {
+        let todo = modify(&self.todos, id, |todo: &mut Todo| {
+            todo.snoozed_until = until;
+            todo.updated_at = Utc::now();
+        })?
+        .ok_or(RepositoryError::NotFound(id))?;
+        self.flush().await?;
+
+        Ok(todo)
+    }
+
+    async fn start_timer(&self, id: i32) -> anyhow::Result<TimeEntry> {
+        self.find(id).await?;
+        let entry_id = self.next_id(TIME_ENTRIES)?;
+        self.running_timers
+            .compare_and_swap(
+                id.to_be_bytes(),
+                None as Option<&[u8]>,
+                Some(&entry_id.to_be_bytes()[..]),
+            )?
+            .map_err(|_| {
+                RepositoryError::Conflict(format!("timer already running, id is {}", id))
+            })?;
+        let entry = TimeEntry {
+            id: entry_id,
+            todo_id: id,
+            started_at: Utc::now(),
+            stopped_at: None,
+        };
+        put(&self.time_entries, entry_id, &entry)?;
+        self.flush().await?;
+
+        Ok(entry)
+    }
+
+    async fn stop_timer(&self, id: i32) -> anyhow::Result<TimeEntry> {
+        self.find(id).await?;
+        let entry_id = self
+            .running_timers
+            .remove(id.to_be_bytes())?
+            .ok_or_else(|| RepositoryError::Conflict(format!("no running timer, id is {}", id)))?;
+        let stopped_at = Utc::now();
+        let entry = modify(
+            &self.time_entries,
+            decode_id(&entry_id),
+            |entry: &mut TimeEntry| entry.stopped_at = Some(stopped_at),
+        )?
+        .ok_or_else(|| RepositoryError::Unexpected(format!("lost running timer, id is {}", id)))?;
+        self.add_time_spent(id, entry.duration())?;
+        self.flush().await?;
+
+        Ok(entry)
+    }
+
+    async fn time_entries(&self, id: i32) -> anyhow::Result<Vec<TimeEntry>> {
+        self.find(id).await?;
+        let mut entries = Vec::new();
+        for entry in values::<TimeEntry>(&self.time_entries) {
+            let entry = entry?;
+            if entry.todo_id == id {
+                entries.push(entry);
+            }
+        }
+
+        Ok(entries)
+    }
+
+    async fn start_pomodoro(&self, id: i32) -> anyhow::Result<Pomodoro> {
+        self.find(id).await?;
+        let pomodoro_id = self.next_id(POMODOROS)?;
+        self.running_pomodoros
+            .compare_and_swap(
+                id.to_be_bytes(),
+                None as Option<&[u8]>,
+                Some(&pomodoro_id.to_be_bytes()[..]),
+            )?
+            .map_err(|_| {
+                RepositoryError::Conflict(format!("pomodoro already running, id is {}", id))
+            })?;
+        let started_at = Utc::now();
+        let pomodoro = Pomodoro {
+            id: pomodoro_id,
+            todo_id: id,
+            started_at,
+            ends_at: started_at + Duration::minutes(POMODORO_MINUTES),
+            interruptions: 0,
+            finished_at: None,
+            completed: false,
+        };
+        put(&self.pomodoros, pomodoro_id, &pomodoro)?;
+        self.flush().await?;
+
+        Ok(pomodoro)
+    }
+
+    async fn interrupt_pomodoro(&self, pomodoro_id: i32) -> anyhow::Result<Pomodoro> {
+        if self.find_pomodoro(pomodoro_id)?.finished_at.is_some() {
+            return Err(RepositoryError::Conflict(format!(
+                "pomodoro already finished, id is {}",
+                pomodoro_id
+            ))
+            .into());
+        }
+        let pomodoro = modify(&self.pomodoros, pomodoro_id, |pomodoro: &mut Pomodoro| {
+            pomodoro.interruptions += 1
+        })?
+        .ok_or(RepositoryError::NotFound(pomodoro_id))?;
+        self.flush().await?;
+
+        Ok(pomodoro)
+    }
+
+    async fn finish_pomodoro(&self, pomodoro_id: i32) -> anyhow::Result<Pomodoro> {
+        let pomodoro = self.find_pomodoro(pomodoro_id)?;
+        let running = self
+            .running_pomodoros
+            .compare_and_swap(
+                pomodoro.todo_id.to_be_bytes(),
+                Some(&pomodoro_id.to_be_bytes()[..]),
+                None as Option<&[u8]>,
+            )?
+            .is_ok();
+        if pomodoro.finished_at.is_some() || !running {
+            return Err(RepositoryError::Conflict(format!(
+                "pomodoro already finished, id is {}",
+                pomodoro_id
+            ))
+            .into());
+        }
+        let finished_at = Utc::now();
+        let pomodoro = modify(&self.pomodoros, pomodoro_id, |pomodoro: &mut Pomodoro| {
+            pomodoro.finished_at = Some(finished_at);
+            pomodoro.completed = finished_at >= pomodoro.ends_at;
+        })?
+        .ok_or(RepositoryError::NotFound(pomodoro_id))?;
+
+        if pomodoro.completed {
+            let entry_id = self.next_id(TIME_ENTRIES)?;
+            let entry = TimeEntry {
+                id: entry_id,
+                todo_id: pomodoro.todo_id,
+                started_at: pomodoro.started_at,
+                stopped_at: Some(pomodoro.ends_at),
+            };
+            put(&self.time_entries, entry_id, &entry)?;
+            self.add_time_spent(pomodoro.todo_id, pomodoro.duration())?;
+        }
+        self.flush().await?;
+
+        Ok(pomodoro)
+    }
+
+    async fn pomodoro(&self, pomodoro_id: i32) -> anyhow::Result<Pomodoro> {
+        self.find_pomodoro(pomodoro_id)
+    }
+
+    async fn pomodoros(&self, id: i32) -> anyhow::Result<Vec<Pomodoro>> {
+        self.find(id).await?;
+        let mut pomodoros = Vec::new();
+        for pomodoro in values::<Pomodoro>(&self.pomodoros) {
+            let pomodoro = pomodoro?;
+            if pomodoro.todo_id == id {
+                pomodoros.push(pomodoro);
+            }
+        }
+
+        Ok(pomodoros)
+    }
+
+    async fn completed_pomodoros(&self, user_id: Option<i32>) -> anyhow::Result<Vec<Pomodoro>> {
+        let mut pomodoros = Vec::new();
+        for pomodoro in values::<Pomodoro>(&self.pomodoros) {
+            let pomodoro = pomodoro?;
+            if !pomodoro.completed {
+                continue;
+            }
+            if user_id.is_some() && self.find(pomodoro.todo_id).await?.user_id != user_id {
+                continue;
+            }
+            pomodoros.push(pomodoro);
+        }
+
+        Ok(pomodoros)
+    }
+
+    async fn create_label(&self, payload: CreateLabel) -> anyhow::Result<Label> {
+        let id = self.next_id(LABELS)?;
+        self.claim_label_name(payload.user_id, &payload.name, id)?;
+        let label = Label {
+            id,
+            name: payload.name,
+            user_id: payload.user_id,
+        };
+        put(&self.labels, id, &label)?;
+        self.flush().await?;
+
+        Ok(label)
+    }
+
+    async fn find_label(&self, id: i32) -> anyhow::Result<Label> {
+        let label = get(&self.labels, id)?.ok_or(RepositoryError::NotFound(id))?;
+        Ok(label)
+    }
+
+    async fn all_labels(&self, user_id: Option<i32>) -> anyhow::Result<Vec<Label>> {
+        let mut labels = values::<Label>(&self.labels).collect::<anyhow::Result<Vec<_>>>()?;
+        labels.retain(|label| user_id.is_none() || label.user_id == user_id);
+        labels.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(labels)
+    }
+
+    async fn update_label(&self, id: i32, payload: UpdateLabel) -> anyhow::Result<Label> {
+        let mut label = self.find_label(id).await?;
+        match payload.name {
+            Some(name) if name != label.name => {
+                self.claim_label_name(label.user_id, &name, id)?;
+                self.label_names
+                    .remove(label_name_key(label.user_id, &label.name))?;
+                label.name = name;
+            }
+            _ => return Ok(label),
+        }
+        put(&self.labels, id, &label)?;
+        self.replace_label(id, Some(&label))?;
+        self.flush().await?;
+
+        Ok(label)
+    }
+
+    async fn delete_label(&self, id: i32) -> anyhow::Result<()> {
+        let bytes = self
+            .labels
+            .remove(id.to_be_bytes())?
+            .ok_or(RepositoryError::NotFound(id))?;
+        let label: Label = serde_json::from_slice(&bytes)?;
+        self.label_names
+            .remove(label_name_key(label.user_id, &label.name))?;
+        self.replace_label(id, None)?;
+        self.flush().await?;
+
+        Ok(())
+    }
+
+    async fn blockers(&self, id: i32) -> anyhow::Result<Vec<Todo>> {
+        let todo = self.find(id).await?;
+        let mut blockers = Vec::new();
+        for blocker in todo.blocked_by.iter() {
+            match get::<Todo>(&self.todos, *blocker)? {
+                Some(blocker) if !blocker.completed => blockers.push(blocker),
+                _ => {}
+            }
+        }
+        Ok(blockers)
+    }
+
+    async fn nearby(&self, nearby: &Nearby) -> anyhow::Result<Vec<Todo>> {
+        let todos = values::<Todo>(&self.todos).collect::<anyhow::Result<Vec<Todo>>>()?;
+        Ok(nearby.select(todos))
+    }
+
+    async fn create_share_link(&self, link: ShareLink) -> anyhow::Result<ShareLink> {
+        self.find(link.todo_id).await?;
+        self.share_links
+            .insert(link.token.as_bytes(), serde_json::to_vec(&link)?)?;
+        self.flush().await?;
+
+        Ok(link)
+    }
+
+    async fn find_share_link(&self, token: &str) -> anyhow::Result<Option<ShareLink>> {
+        let link = self
+            .share_links
+            .get(token)?
+            .map(|bytes| serde_json::from_slice(&bytes))
+            .transpose()?;
+        Ok(link)
+    }
+
+    async fn delete_share_link(&self, id: i32, token: &str) -> anyhow::Result<()> {
+        match self.find_share_link(token).await? {
+            Some(link) if link.todo_id == id => {}
+            _ => return Err(RepositoryError::NotFound(id).into()),
+        }
+        self.share_links
+            .remove(token)?
+            .ok_or(RepositoryError::NotFound(id))?;
+        self.flush().await?;
+
+        Ok(())
+    }
+
+    async fn reorder(&self, id: i32, payload: ReorderTodo) -> anyhow::Result<Todo> {
+        {
+            let _reordering = self.reorder_lock.lock().unwrap();
+            let todos = values::<Todo>(&self.todos).collect::<anyhow::Result<Vec<_>>>()?;
+            for (todo_id, position) in reorder_positions(id, &payload, &todos)? {
+                modify(&self.todos, todo_id, |todo: &mut Todo| {
+                    todo.position = position;
+                })?;
+            }
+        }
+        self.flush().await?;
+
+        self.find(id).await
+    }
+
+    async fn create_project(&self, payload: CreateProject) -> anyhow::Result<Project> {
+        let project = Project {
+            id: self.next_id(PROJECTS)?,
+            name: payload.name,
+            user_id: payload.user_id,
+        };
+        put(&self.projects, project.id, &project)?;
+        self.flush().await?;
+
+        Ok(project)
+    }
+
+    async fn find_project(&self, id: i32) -> anyhow::Result<Project> {
+        let project = get(&self.projects, id)?.ok_or(RepositoryError::NotFound(id))?;
+        Ok(project)
+    }
+
+    async fn all_projects(&self, user_id: Option<i32>) -> anyhow::Result<Vec<Project>> {
+        let mut projects = values::<Project>(&self.projects).collect::<anyhow::Result<Vec<_>>>()?;
+        projects.retain(|project| project.listed_for(user_id));
+        Ok(projects)
+    }
+
+    async fn update_project(&self, id: i32, payload: UpdateProject) -> anyhow::Result<Project> {
+        let project = modify(&self.projects, id, |project: &mut Project| {
+            if let Some(name) = &payload.name {
+                project.name = name.clone();
+            }
+        })?
+        .ok_or(RepositoryError::NotFound(id))?;
+        self.flush().await?;
+
+        Ok(project)
+    }
+
+    async fn delete_project(&self, id: i32) -> anyhow::Result<()> {
+        check_project_deletable(id)?;
+        let project = self.find_project(id).await?;
+        let todos = values::<Todo>(&self.todos).collect::<anyhow::Result<Vec<_>>>()?;
+        for todo_id in project_todo_ids(&project, &todos)? {
+            self.delete(todo_id).await?;
+        }
+        self.projects.remove(id.to_be_bytes())?;
+        self.flush().await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl UserRepository for TodoRepositoryForSled {
+    async fn create_user(&self, payload: CreateUser) -> anyhow::Result<User> {
+        let id = self.next_id(USERS)?;
+        self.user_names
+            .compare_and_swap(
+                &payload.name,
+                None as Option<&[u8]>,
+                Some(&id.to_be_bytes()[..]),
+            )?
+            .map_err(|_| {
+                RepositoryError::Conflict(format!("user already exists, name is {}", payload.name))
+            })?;
+        let user = payload.into_user(id);
+        put(&self.users, id, &user)?;
+        self.flush().await?;
+
+        Ok(user)
+    }
+
+    async fn find_user_by_name(&self, name: &str) -> anyhow::Result<Option<User>> {
+        match self.user_names.get(name)? {
+            Some(id) => get(&self.users, decode_id(&id)),
+            None => Ok(None),
+        }
+    }
+
+    async fn create_api_key(&self, payload: CreateApiKey) -> anyhow::Result<ApiKey> {
+        let id = self.next_id(API_KEYS)?;
+        let key = payload.into_api_key(id);
+        put(&self.api_keys, id, &key)?;
+        self.api_key_hashes
+            .insert(&key.key_hash, &id.to_be_bytes()[..])?;
+        self.flush().await?;
+
+        Ok(key)
+    }
+
+    async fn find_api_key_by_hash(&self, key_hash: &str) -> anyhow::Result<Option<ApiKey>> {
+        match self.api_key_hashes.get(key_hash)? {
+            Some(id) => get(&self.api_keys, decode_id(&id)),
+            None => Ok(None),
+        }
+    }
+
+    async fn api_keys(&self, user_id: i32) -> anyhow::Result<Vec<ApiKey>> {
+        let mut keys = Vec::new();
+        for key in values::<ApiKey>(&self.api_keys) {
+            let key = key?;
+            if key.user_id == user_id {
+                keys.push(key);
+            }
+        }
+
+        Ok(keys)
+    }
+
+    async fn delete_api_key(&self, user_id: i32, id: i32) -> anyhow::Result<()> {
+        let key = get::<ApiKey>(&self.api_keys, id)?
+            .filter(|key| key.user_id == user_id)
+            .ok_or(RepositoryError::NotFound(id))?;
+        self.api_key_hashes.remove(&key.key_hash)?;
+        self.api_keys.remove(id.to_be_bytes())?;
+        self.flush().await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod test_utils {
+    use super::*;
+
+    impl TodoRepositoryForSled {
+        /// A database in a temporary directory that is removed on drop.
+        pub fn temporary() -> Self {
+            let db = sled::Config::new()
+                .temporary(true)
+                .open()
+                .expect("failed open temporary sled");
+            Self::new(db).expect("failed open sled trees")
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+
+        #[tokio::test]
+        async fn crud_scenario_sled() {
+            let repository = TodoRepositoryForSled::temporary();
+            let todo_text = "[crud_scenario] text";
+
+            // create
+            let created = repository
+                .create(CreateTodo::new(todo_text.to_string()))
+                .await
+                .expect("[create] returned Err");
+            assert_eq!(created.text, todo_text);
+            assert!(!created.completed);
+
+            // find
+            let todo = repository
+                .find(created.id)
+                .await
+                .expect("[find] returned Err");
+            assert_eq!(created, todo);
+
+            // all
+            let second = repository
+                .create(CreateTodo::new("[crud_scenario] second".to_string()))
+                .await
+                .expect("[create] returned Err");
+            let todos = repository
+                .all(&TodoFilter::default(), TodoSort::default(), Page::default())
+                .await
+                .expect("[all] returned Err");
+            assert_eq!(vec![second.clone(), created.clone()], todos);
+
+            // update
+            let updated_text = "[crud_scenario] updated text";
+            let todo = repository
+                .update(
+                    created.id,
+                    UpdateTodo {
+                        text: Some(updated_text.to_string()),
+                        completed: Some(true),
+                        label_ids: None,
+                        due_date: None,
+                        priority: None,
+                        blocked_by: None,
+                        project_id: None,
+                        recurrence: None,
+                        lat: None,
+                        lon: None,
+                        place: None,
+                        remind_at: None,
+                    },
+                )
+                .await
+                .expect("[update] returned Err");
+            assert_eq!(created.id, todo.id);
+            assert_eq!(todo.text, updated_text);
+            assert!(todo.completed);
+
+            // timer
+            let entry = repository
+                .start_timer(created.id)
+                .await
+                .expect("[start_timer] returned Err");
+            assert!(repository.start_timer(created.id).await.is_err());
+            let stopped = repository
+                .stop_timer(created.id)
+                .await
+                .expect("[stop_timer] returned Err");
+            assert_eq!(entry.id, stopped.id);
+            assert!(stopped.stopped_at.is_some());
+            assert!(repository.stop_timer(created.id).await.is_err());
+
+            // pomodoro
+            let pomodoro = repository
+                .start_pomodoro(created.id)
+                .await
+                .expect("[start_pomodoro] returned Err");
+            assert!(repository.start_pomodoro(created.id).await.is_err());
+            let pomodoro = repository
+                .interrupt_pomodoro(pomodoro.id)
+                .await
+                .expect("[interrupt_pomodoro] returned Err");
+            assert_eq!(pomodoro.interruptions, 1);
+            let pomodoro = repository
+                .finish_pomodoro(pomodoro.id)
+                .await
+                .expect("[finish_pomodoro] returned Err");
+            assert!(!pomodoro.completed);
+            assert!(repository.finish_pomodoro(pomodoro.id).await.is_err());
+            let completed = repository
+                .completed_pomodoros(None)
+                .await
+                .expect("[completed_pomodoros] returned Err");
+            assert!(completed.is_empty());
+
+            // delete
+            repository
+                .delete(created.id)
+                .await
+                .expect("[delete] returned Err");
+            assert!(repository.find(created.id).await.is_err());
+            let res = repository.delete(created.id).await;
+            assert!(matches!(
+                res.unwrap_err().downcast_ref::<RepositoryError>(),
+                Some(RepositoryError::NotFound(_))
+            ));
+            assert_eq!(repository.pomodoros.len(), 0);
+            assert_eq!(repository.count(&TodoFilter::default()).await.unwrap(), 1);
+        }
+    }
+}
@@ -0,0 +1,1763 @@
+use super::{
+    attach_blockers, attach_labels, check_project_deletable, foreign_project_todos,
+    reorder_positions, ApiKey, CreateApiKey, CreateLabel, CreateProject, CreateTodo, CreateUser,
+    DependencyLink, Label, LabelLink, Nearby, Page, Pomodoro, Project, ReorderTodo,
+    RepositoryError, ShareLink, TimeEntry, Todo, TodoFilter, TodoRepository, TodoSort, UpdateLabel,
+    UpdateProject, UpdateTodo, User, UserRepository, DEFAULT_PROJECT_ID, POMODORO_MINUTES,
+};
+use axum::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use sqlx::{Sqlite, SqlitePool, Transaction};
+use std::slice;
+
+/// Extended result code SQLite reports for a violated UNIQUE constraint.
+const SQLITE_CONSTRAINT_UNIQUE: &str = "2067";
+/// Extended result code SQLite reports for a violated FOREIGN KEY constraint.
+const SQLITE_CONSTRAINT_FOREIGNKEY: &str = "787";
+
+/// Binds the parameters of the todo listing, for the listing itself and
+/// for its plan.
+macro_rules! bind_listing {
+    ($query:expr, $filter:expr, $page:expr) => {
+        $query
+            .bind($filter.stale_before)
+            .bind($filter.awake_at)
+            .bind($filter.before_id)
+            .bind($filter.completed)
+            .bind($filter.text_pattern())
+            .bind($filter.label.as_deref())
+            .bind($filter.due_before)
+            .bind($filter.due_after)
+            .bind($filter.overdue_at)
+            .bind($filter.priority)
+            .bind($filter.parent_id)
+            .bind($filter.project_id)
+            .bind($filter.updated_after)
+            .bind($filter.reminder_due_at)
+            .bind($filter.ids_json())
+            .bind($filter.user_id)
+            .bind($page.limit.map(|limit| limit as i64).unwrap_or(-1))
+            .bind($page.offset as i64)
+    };
+}
+
+#[derive(Debug, Clone)]
+pub struct TodoRepositoryForSqlite {
+    pool: SqlitePool,
+    /// Logs the plan of every todo listing.
+    explain: bool,
+}
+
+impl TodoRepositoryForSqlite {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self {
+            pool,
+            explain: false,
+        }
+    }
+
+    /// Logs the query plan of every todo listing at debug level, to find
+    /// out why one is slow. They only show with `RUST_LOG=debug`.
+    pub fn with_query_plans(self) -> Self {
+        Self {
+            explain: true,
+            ..self
+        }
+    }
+
+    /// The plan SQLite picks for `query`, one step per line.
+    async fn listing_plan(
+        &self,
+        query: &str,
+        filter: &TodoFilter,
+        page: Page,
+    ) -> anyhow::Result<Vec<String>> {
+        let plan = bind_listing!(
+            sqlx::query_as::<_, (i64, i64, i64, String)>(&format!("explain query plan {}", query)),
+            filter,
+            page
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(plan.into_iter().map(|(_, _, _, detail)| detail).collect())
+    }
+
+    /// Brings the schema up to date. Run at startup, since a self-hosted
+    /// SQLite file has no separate `sqlx migrate run` step.
+    pub async fn migrate(&self) -> anyhow::Result<()> {
+        sqlx::migrate!("./migrations_sqlite")
+            .run(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn find_pomodoro(&self, pomodoro_id: i32) -> anyhow::Result<Pomodoro> {
+        let pomodoro = sqlx::query_as::<_, Pomodoro>(
+            r#"
+                select * from pomodoros where id=?
+            "#,
+        )
+        .bind(pomodoro_id)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => RepositoryError::NotFound(pomodoro_id),
+            _ => RepositoryError::Unexpected(e.to_string()),
+        })?;
+
+        Ok(pomodoro)
+    }
+
+    /// Fills in the labels and blockers of `todos`, which `select * from todos`
+    /// leaves empty.
+    async fn load_relations(&self, todos: &mut [Todo]) -> anyhow::Result<()> {
+        let ids: Vec<i32> = todos.iter().map(|todo| todo.id).collect();
+        let ids = serde_json::to_string(&ids)?;
+        let links = sqlx::query_as::<_, LabelLink>(
+            r#"
+                select todo_labels.todo_id, labels.id, labels.name, labels.user_id
+                from todo_labels join labels on labels.id=todo_labels.label_id
+                where todo_labels.todo_id in (select value from json_each(?))
+            "#,
+        )
+        .bind(&ids)
+        .fetch_all(&self.pool)
+        .await?;
+        attach_labels(todos, links);
+        let links = sqlx::query_as::<_, DependencyLink>(
+            r#"
+                select todo_id, blocker_id from todo_dependencies
+                where todo_id in (select value from json_each(?))
+            "#,
+        )
+        .bind(&ids)
+        .fetch_all(&self.pool)
+        .await?;
+        attach_blockers(todos, links);
+
+        Ok(())
+    }
+}
+
+fn is_unique_violation(e: &sqlx::Error) -> bool {
+    match e {
+        sqlx::Error::Database(db) => db.code().as_deref() == Some(SQLITE_CONSTRAINT_UNIQUE),
+        _ => false,
+    }
+}
+
+fn is_foreign_key_violation(e: &sqlx::Error) -> bool {
+    match e {
+        sqlx::Error::Database(db) => db.code().as_deref() == Some(SQLITE_CONSTRAINT_FOREIGNKEY),
+        _ => false,
+    }
+}
+
+/// Replaces the labels attached to todo `id`.
+async fn set_labels(
+    tx: &mut Transaction<'_, Sqlite>,
+    id: i32,
+    label_ids: &[i32],
+) -> anyhow::Result<()> {
+    sqlx::query(
+        r#"
+            delete from todo_labels where todo_id=?
+        "#,
+    )
+    .bind(id)
+    .execute(&mut *tx)
+    .await?;
+    for label_id in label_ids {
+        sqlx::query(
+            r#"
+                insert or ignore into todo_labels (todo_id, label_id)
+                values (?, ?)
+            "#,
+        )
+        .bind(id)
+        .bind(label_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| {
+            if is_foreign_key_violation(&e) {
+                RepositoryError::NotFound(*label_id)
+            } else {
+                RepositoryError::Unexpected(e.to_string())
+            }
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Replaces the todos that todo `id` is blocked by, refusing any that would
+/// close a cycle.
+async fn set_blockers(
+    tx: &mut Transaction<'_, Sqlite>,
+    id: i32,
+    blocked_by: &[i32],
+) -> anyhow::Result<()> {
+    let (cycle,) = sqlx::query_as::<_, (bool,)>(
+        r#"
+            with recursive reachable (id) as (
+                select value from json_each(?)
+                union
+                select todo_dependencies.blocker_id
+                from todo_dependencies join reachable on todo_dependencies.todo_id=reachable.id
+            )
+            select exists(select 1 from reachable where id=?)
+        "#,
+    )
+    .bind(serde_json::to_string(blocked_by)?)
+    .bind(id)
+    .fetch_one(&mut *tx)
+    .await?;
+    if cycle {
+        return Err(RepositoryError::Conflict(format!("dependency cycle, id is {}", id)).into());
+    }
+
+    sqlx::query(
+        r#"
+            delete from todo_dependencies where todo_id=?
+        "#,
+    )
+    .bind(id)
+    .execute(&mut *tx)
+    .await?;
+    for blocker_id in blocked_by {
+        sqlx::query(
+            r#"
+                insert or ignore into todo_dependencies (todo_id, blocker_id)
+                values (?, ?)
+            "#,
+        )
+        .bind(id)
+        .bind(blocker_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| {
+            if is_foreign_key_violation(&e) {
+                RepositoryError::NotFound(*blocker_id)
+            } else {
+                RepositoryError::Unexpected(e.to_string())
+            }
+        })?;
+    }
+
+    Ok(())
+}
+
+/// The todo listing in `sort` order. One statement is prepared and cached
+/// per order.
+///
+/// A negative LIMIT means no limit in SQLite, and its LIKE ignores case for
+/// ASCII letters only.
+fn listing_query(sort: TodoSort) -> String {
+    format!(
+        r#"
+            select * from todos
+            where (?1 is null or (completed=false and updated_at<?1))
+            and (?2 is null or snoozed_until is null or snoozed_until<=?2)
+            and (?3 is null or id<?3)
+            and (?4 is null or completed=?4)
+            and (?5 is null or text like ?5 escape '\')
+            and (?6 is null or exists (
+                select 1 from todo_labels join labels on labels.id=todo_labels.label_id
+                where todo_labels.todo_id=todos.id and labels.name=?6
+            ))
+            and (?7 is null or due_date<?7)
+            and (?8 is null or due_date>?8)
+            and (?9 is null or (completed=false and due_date<?9))
+            and (?10 is null or priority=?10)
+            and (?11 is null or parent_id=?11)
+            and (?12 is null or project_id=?12)
+            and (?13 is null or updated_at>?13)
+            and (?14 is null or (completed=false and remind_at<=?14))
+            and (?15 is null or id in (select value from json_each(?15)))
+            and (?16 is null or user_id=?16)
+            order by {}
+            limit ?17 offset ?18;
+        "#,
+        sort.order_by()
+    )
+}
+
+#[async_trait]
+impl TodoRepository for TodoRepositoryForSqlite {
+    async fn create(&self, payload: CreateTodo) -> anyhow::Result<Todo> {
+        let now = Utc::now();
+        let mut tx = self.pool.begin().await?;
+        let mut todo = sqlx::query_as::<_, Todo>(
+            r#"
+                insert into todos (
+                    text, completed, due_date, priority, parent_id, project_id, user_id,
+                    recurrence, lat, lon, place, remind_at, position, created_at, updated_at
+                )
+                values (
+                    ?, false, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?,
+                    (select coalesce(max(position), -1) + 1 from todos), ?, ?
+                )
+                returning *
+            "#,
+        )
+        .bind(payload.text.clone())
+        .bind(payload.due_date)
+        .bind(payload.priority)
+        .bind(payload.parent_id)
+        .bind(payload.project_id)
+        .bind(payload.user_id)
+        .bind(payload.recurrence.clone())
+        .bind(payload.lat)
+        .bind(payload.lon)
+        .bind(payload.place.clone())
+        .bind(payload.remind_at)
+        .bind(now)
+        .bind(now)
+        .fetch_one(&mut tx)
+        .await
+        .map_err(|e| match payload.parent_id {
+            Some(parent_id) if is_foreign_key_violation(&e) => RepositoryError::NotFound(parent_id),
+            _ => RepositoryError::Unexpected(e.to_string()),
+        })?;
+        if !payload.limits.is_unlimited() {
+            // The insert took SQLite's single write lock, so no other create
+            // can slip in before this count; it includes the new todo.
+            let (usage, project_usage) = sqlx::query_as::<_, (i64, i64)>(
+                r#"
+                    select count(*), count(case when project_id = ? then 1 end) from todos
+                    where user_id is ?
+                "#,
+            )
+            .bind(payload.project_id)
+            .bind(payload.user_id)
+            .fetch_one(&mut tx)
+            .await?;
+            payload.limits.check(
+                payload.project_id,
+                usage as usize - 1,
+                project_usage as usize - 1,
+            )?;
+        }
+        set_labels(&mut tx, todo.id, &payload.label_ids).await?;
+        set_blockers(&mut tx, todo.id, &payload.blocked_by).await?;
+        tx.commit().await?;
+        self.load_relations(slice::from_mut(&mut todo)).await?;
+
+        Ok(todo)
+    }
+
+    async fn find(&self, id: i32) -> anyhow::Result<Todo> {
+        let mut todo = sqlx::query_as::<_, Todo>(
+            r#"
+                select * from todos where id=?
+            "#,
+        )
+        .bind(id)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => RepositoryError::NotFound(id),
+            _ => RepositoryError::Unexpected(e.to_string()),
+        })?;
+        self.load_relations(slice::from_mut(&mut todo)).await?;
+
+        Ok(todo)
+    }
+
+    async fn all(
+        &self,
+        filter: &TodoFilter,
+        sort: TodoSort,
+        page: Page,
+    ) -> anyhow::Result<Vec<Todo>> {
+        let query = listing_query(sort);
+        if self.explain {
+            match self.listing_plan(&query, filter, page).await {
+                Ok(plan) => tracing::debug!("plan of todo listing:\n{}", plan.join("\n")),
+                Err(e) => tracing::warn!("fail explain todo listing: {:#}", e),
+            }
+        }
+        let mut todos = bind_listing!(sqlx::query_as::<_, Todo>(&query), filter, page)
+            .fetch_all(&self.pool)
+            .await?;
+        self.load_relations(&mut todos).await?;
+
+        Ok(todos)
+    }
+
+    async fn count(&self, filter: &TodoFilter) -> anyhow::Result<usize> {
+        let count = sqlx::query_scalar::<_, i64>(
+            r#"
+                select count(*) from todos
+                where (?1 is null or (completed=false and updated_at<?1))
+                and (?2 is null or snoozed_until is null or snoozed_until<=?2)
+                and (?3 is null or id<?3)
+                and (?4 is null or completed=?4)
+                and (?5 is null or text like ?5 escape '\')
+                and (?6 is null or exists (
+                    select 1 from todo_labels join labels on labels.id=todo_labels.label_id
+                    where todo_labels.todo_id=todos.id and labels.name=?6
+                ))
+                and (?7 is null or due_date<?7)
+                and (?8 is null or due_date>?8)
+                and (?9 is null or (completed=false and due_date<?9))
+                and (?10 is null or priority=?10)
+                and (?11 is null or parent_id=?11)
+                and (?12 is null or project_id=?12)
+                and (?13 is null or updated_at>?13)
+                and (?14 is null or (completed=false and remind_at<=?14))
+                and (?15 is null or id in (select value from json_each(?15)))
+                and (?16 is null or user_id=?16)
+            "#,
+        )
+        .bind(filter.stale_before)
+        .bind(filter.awake_at)
+        .bind(filter.before_id)
+        .bind(filter.completed)
+        .bind(filter.text_pattern())
+        .bind(filter.label.as_deref())
+        .bind(filter.due_before)
+        .bind(filter.due_after)
+        .bind(filter.overdue_at)
+        .bind(filter.priority)
+        .bind(filter.parent_id)
+        .bind(filter.project_id)
+        .bind(filter.updated_after)
+        .bind(filter.reminder_due_at)
+        .bind(filter.ids_json())
+        .bind(filter.user_id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(count as usize)
+    }
+
+    async fn update(&self, id: i32, payload: UpdateTodo) -> anyhow::Result<Todo> {
+        let old_todo = self.find(id).await?;
+        if let Some(project_id) = payload.project_id {
+            self.find_project(project_id).await?;
+        }
+        let mut tx = self.pool.begin().await?;
+        let mut todo = sqlx::query_as::<_, Todo>(
+            r#"
+                update todos
+                set text=?, completed=?, due_date=?, priority=?, project_id=?, recurrence=?,
+                    lat=?, lon=?, place=?, remind_at=?, updated_at=?
+                where id=?
+                returning *
+            "#,
+        )
+        .bind(payload.text.unwrap_or(old_todo.text))
+        .bind(payload.completed.unwrap_or(old_todo.completed))
+        .bind(payload.due_date.unwrap_or(old_todo.due_date))
+        .bind(payload.priority.unwrap_or(old_todo.priority))
+        .bind(payload.project_id.unwrap_or(old_todo.project_id))
+        .bind(payload.recurrence.unwrap_or(old_todo.recurrence))
+        .bind(payload.lat.unwrap_or(old_todo.lat))
+        .bind(payload.lon.unwrap_or(old_todo.lon))
+        .bind(payload.place.unwrap_or(old_todo.place))
+        .bind(payload.remind_at.unwrap_or(old_todo.remind_at))
+        .bind(Utc::now())
+        .bind(id)
+        .fetch_one(&mut tx)
+        .await?;
+        if let Some(label_ids) = &payload.label_ids {
+            set_labels(&mut tx, id, label_ids).await?;
+        }
+        if let Some(blocked_by) = &payload.blocked_by {
+            set_blockers(&mut tx, id, blocked_by).await?;
+        }
+        tx.commit().await?;
+        self.load_relations(slice::from_mut(&mut todo)).await?;
+        if todo.completed && !old_todo.completed {
+            if let Some(next) = todo.next_occurrence(Utc::now()) {
+                self.create(next).await?;
+            }
+        }
+
+        Ok(todo)
+    }
+
+    async fn delete(&self, id: i32) -> anyhow::Result<()> {
+        let result = sqlx::query(
+            r#"
+                delete from todos where id=?
+            "#,
+        )
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            if is_foreign_key_violation(&e) {
+                RepositoryError::Conflict(format!("todo has subtasks, id is {}", id))
+            } else {
+                RepositoryError::Unexpected(e.to_string())
+            }
+        })?;
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound(id).into());
+        }
+
+        Ok(())
+    }
+
+    async fn snooze(&self, id: i32, until: Option<DateTime<Utc>>) -> anyhow::Result<Todo> {
+        let mut todo = sqlx::query_as::<_, Todo>(
+            r#"
+                update todos set snoozed_until=?, updated_at=?
+                where id=?
+                returning *
+            "#,
+        )
+        .bind(until)
+        .bind(Utc::now())
+        .bind(id)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => RepositoryError::NotFound(id),
+            _ => RepositoryError::Unexpected(e.to_string()),
+        })?;
+        self.load_relations(slice::from_mut(&mut todo)).await?;
+
+        Ok(todo)
+    }
+
+    async fn start_timer(&self, id: i32) -> anyhow::Result<TimeEntry> {
+        self.find(id).await?;
+        let entry = sqlx::query_as::<_, TimeEntry>(
+            r#"
+                insert into time_entries (todo_id, started_at)
+                values (?, ?)
+                returning *
+            "#,
+        )
+        .bind(id)
+        .bind(Utc::now())
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| {
+            if is_unique_violation(&e) {
+                RepositoryError::Conflict(format!("timer already running, id is {}", id))
+            } else {
+                RepositoryError::Unexpected(e.to_string())
+            }
+        })?;
+
+        Ok(entry)
+    }
+
+    async fn stop_timer(&self, id: i32) -> anyhow::Result<TimeEntry> {
+        self.find(id).await?;
+        let mut tx = self.pool.begin().await?;
+        let entry = sqlx::query_as::<_, TimeEntry>(
+            r#"
+                update time_entries set stopped_at=?
+                where todo_id=? and stopped_at is null
+                returning *
+            "#,
+        )
+        .bind(Utc::now())
+        .bind(id)
+        .fetch_optional(&mut tx)
+        .await?
+        .ok_or_else(|| RepositoryError::Conflict(format!("no running timer, id is {}", id)))?;
+
+        sqlx::query(
+            r#"
+                update todos set time_spent=time_spent+?
+                where id=?
+            "#,
+        )
+        .bind(entry.duration())
+        .bind(id)
+        .execute(&mut tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(entry)
+    }
+
+    async fn time_entries(&self, id: i32) -> anyhow::Result<Vec<TimeEntry>> {
+        self.find(id).await?;
+        let entries = sqlx::query_as::<_, TimeEntry>(
+            r#"
+                select * from time_entries
+                where todo_id=?
+                order by id
+            "#,
+        )
+        .bind(id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(entries)
+    }
+
+    async fn start_pomodoro(&self, id: i32) -> anyhow::Result<Pomodoro> {
+        self.find(id).await?;
+        let started_at = Utc::now();
+        let pomodoro = sqlx::query_as::<_, Pomodoro>(
+            r#"
+                insert into pomodoros (todo_id, started_at, ends_at)
+                values (?, ?, ?)
+                returning *
+            "#,
+        )
+        .bind(id)
+        .bind(started_at)
+        .bind(started_at + Duration::minutes(POMODORO_MINUTES))
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| {
+            if is_unique_violation(&e) {
+                RepositoryError::Conflict(format!("pomodoro already running, id is {}", id))
+            } else {
+                RepositoryError::Unexpected(e.to_string())
+            }
+        })?;
+
+        Ok(pomodoro)
+    }
+
+    async fn interrupt_pomodoro(&self, pomodoro_id: i32) -> anyhow::Result<Pomodoro> {
+        self.find_pomodoro(pomodoro_id).await?;
+        let pomodoro = sqlx::query_as::<_, Pomodoro>(
+            r#"
+                update pomodoros set interruptions=interruptions+1
+                where id=? and finished_at is null
+                returning *
+            "#,
+        )
+        .bind(pomodoro_id)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| {
+            RepositoryError::Conflict(format!("pomodoro already finished, id is {}", pomodoro_id))
+        })?;
+
+        Ok(pomodoro)
+    }
+
+    async fn finish_pomodoro(&self, pomodoro_id: i32) -> anyhow::Result<Pomodoro> {
+        let pomodoro = self.find_pomodoro(pomodoro_id).await?;
+        let finished_at = Utc::now();
+        let completed = finished_at >= pomodoro.ends_at;
+
+        let mut tx = self.pool.begin().await?;
+        let pomodoro = sqlx::query_as::<_, Pomodoro>(
+            r#"
+                update pomodoros set finished_at=?, completed=?
+                where id=? and finished_at is null
+                returning *
+            "#,
+        )
+        .bind(finished_at)
+        .bind(completed)
+        .bind(pomodoro_id)
+        .fetch_optional(&mut tx)
+        .await?
+        .ok_or_else(|| {
+            RepositoryError::Conflict(format!("pomodoro already finished, id is {}", pomodoro_id))
+        })?;
+
+        if completed {
+            sqlx::query(
+                r#"
+                    insert into time_entries (todo_id, started_at, stopped_at)
+                    values (?, ?, ?)
+                "#,
+            )
+            .bind(pomodoro.todo_id)
+            .bind(pomodoro.started_at)
+            .bind(pomodoro.ends_at)
+            .execute(&mut tx)
+            .await?;
+            sqlx::query(
+                r#"
+                    update todos set time_spent=time_spent+?
+                    where id=?
+                "#,
+            )
+            .bind(pomodoro.duration())
+            .bind(pomodoro.todo_id)
+            .execute(&mut tx)
+            .await?;
+        }
+        tx.commit().await?;
+
+        Ok(pomodoro)
+    }
+
+    async fn pomodoro(&self, pomodoro_id: i32) -> anyhow::Result<Pomodoro> {
+        self.find_pomodoro(pomodoro_id).await
+    }
+
+    async fn pomodoros(&self, id: i32) -> anyhow::Result<Vec<Pomodoro>> {
+        self.find(id).await?;
+        let pomodoros = sqlx::query_as::<_, Pomodoro>(
+            r#"
+                select * from pomodoros
+                where todo_id=?
+                order by id
+            "#,
+        )
+        .bind(id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(pomodoros)
+    }
+
+    async fn completed_pomodoros(&self, user_id: Option<i32>) -> anyhow::Result<Vec<Pomodoro>> {
+        let pomodoros = sqlx::query_as::<_, Pomodoro>(
+            r#"
+                select pomodoros.* from pomodoros
+                join todos on todos.id=pomodoros.todo_id
+                where pomodoros.completed and (?1 is null or todos.user_id=?1)
+                order by pomodoros.id
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(pomodoros)
+    }
+
+    async fn create_label(&self, payload: CreateLabel) -> anyhow::Result<Label> {
+        let label = sqlx::query_as::<_, Label>(
+            r#"
+                insert into labels (name, user_id)
+                values (?, ?)
+                returning *
+            "#,
+        )
+        .bind(payload.name.clone())
+        .bind(payload.user_id)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| {
+            if is_unique_violation(&e) {
+                RepositoryError::Conflict(format!("label already exists, name is {}", payload.name))
+            } else {
+                RepositoryError::Unexpected(e.to_string())
+            }
+        })?;
+
+        Ok(label)
+    }
+
+    async fn find_label(&self, id: i32) -> anyhow::Result<Label> {
+        let label = sqlx::query_as::<_, Label>(
+            r#"
+                select * from labels where id=?
+            "#,
+        )
+        .bind(id)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => RepositoryError::NotFound(id),
+            _ => RepositoryError::Unexpected(e.to_string()),
+        })?;
+
+        Ok(label)
+    }
+
+    async fn all_labels(&self, user_id: Option<i32>) -> anyhow::Result<Vec<Label>> {
+        let labels = sqlx::query_as::<_, Label>(
+            r#"
+                select * from labels
+                where ?1 is null or user_id=?1
+                order by name
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(labels)
+    }
+
+    async fn update_label(&self, id: i32, payload: UpdateLabel) -> anyhow::Result<Label> {
+        let label = sqlx::query_as::<_, Label>(
+            r#"
+                update labels set name=coalesce(?, name)
+                where id=?
+                returning *
+            "#,
+        )
+        .bind(payload.name.clone())
+        .bind(id)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => RepositoryError::NotFound(id),
+            _ if is_unique_violation(&e) => RepositoryError::Conflict(format!(
+                "label already exists, name is {}",
+                payload.name.unwrap_or_default()
+            )),
+            _ => RepositoryError::Unexpected(e.to_string()),
+        })?;
+
+        Ok(label)
+    }
+
+    async fn delete_label(&self, id: i32) -> anyhow::Result<()> {
+        let result = sqlx::query(
+            r#"
+                delete from labels where id=?
+            "#,
+        )
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| RepositoryError::Unexpected(e.to_string()))?;
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound(id).into());
+        }
+
+        Ok(())
+    }
+
+    async fn blockers(&self, id: i32) -> anyhow::Result<Vec<Todo>> {
+        self.find(id).await?;
+        let mut todos = sqlx::query_as::<_, Todo>(
+            r#"
+                select todos.* from todos
+                join todo_dependencies on todos.id=todo_dependencies.blocker_id
+                where todo_dependencies.todo_id=? and todos.completed=false
+                order by todos.id
+            "#,
+        )
+        .bind(id)
+        .fetch_all(&self.pool)
+        .await?;
+        self.load_relations(&mut todos).await?;
+
+        Ok(todos)
+    }
+
+    async fn nearby(&self, nearby: &Nearby) -> anyhow::Result<Vec<Todo>> {
+        let ((min_lat, max_lat), (min_lon, max_lon)) = nearby.bounds();
+        let mut todos = sqlx::query_as::<_, Todo>(
+            r#"
+                select * from todos
+                where completed=false
+                and lat between ? and ? and lon between ? and ?
+            "#,
+        )
+        .bind(min_lat)
+        .bind(max_lat)
+        .bind(min_lon)
+        .bind(max_lon)
+        .fetch_all(&self.pool)
+        .await?;
+        self.load_relations(&mut todos).await?;
+
+        Ok(nearby.select(todos))
+    }
+
+    async fn create_share_link(&self, link: ShareLink) -> anyhow::Result<ShareLink> {
+        let link = sqlx::query_as::<_, ShareLink>(
+            r#"
+                insert into share_links (token, todo_id, expires_at, created_at)
+                values (?, ?, ?, ?)
+                returning *
+            "#,
+        )
+        .bind(&link.token)
+        .bind(link.todo_id)
+        .bind(link.expires_at)
+        .bind(link.created_at)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| {
+            if is_foreign_key_violation(&e) {
+                RepositoryError::NotFound(link.todo_id)
+            } else {
+                RepositoryError::Unexpected(e.to_string())
+            }
+        })?;
+
+        Ok(link)
+    }
+
+    async fn find_share_link(&self, token: &str) -> anyhow::Result<Option<ShareLink>> {
+        let link = sqlx::query_as::<_, ShareLink>(
+            r#"
+                select * from share_links where token=?
+            "#,
+        )
+        .bind(token)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(link)
+    }
+
+    async fn delete_share_link(&self, id: i32, token: &str) -> anyhow::Result<()> {
+        let result = sqlx::query(
+            r#"
+                delete from share_links where todo_id=? and token=?
+            "#,
+        )
+        .bind(id)
+        .bind(token)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| RepositoryError::Unexpected(e.to_string()))?;
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound(id).into());
+        }
+
+        Ok(())
+    }
+
+    async fn reorder(&self, id: i32, payload: ReorderTodo) -> anyhow::Result<Todo> {
+        let mut tx = self.pool.begin().await?;
+        // SQLite runs one write transaction at a time, so concurrent reorders
+        // take turns.
+        let todos = sqlx::query_as::<_, Todo>(
+            r#"
+                select * from todos
+                where user_id is (select user_id from todos where id=?)
+            "#,
+        )
+        .bind(id)
+        .fetch_all(&mut tx)
+        .await?;
+        for (todo_id, position) in reorder_positions(id, &payload, &todos)? {
+            sqlx::query(
+                r#"
+                    update todos set position=? where id=?
+                "#,
+            )
+            .bind(position)
+            .bind(todo_id)
+            .execute(&mut tx)
+            .await?;
+        }
+        tx.commit().await?;
+
+        self.find(id).await
+    }
+
+    async fn create_project(&self, payload: CreateProject) -> anyhow::Result<Project> {
+        let project = sqlx::query_as::<_, Project>(
+            r#"
+                insert into projects (name, user_id)
+                values (?, ?)
+                returning *
+            "#,
+        )
+        .bind(payload.name)
+        .bind(payload.user_id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(project)
+    }
+
+    async fn find_project(&self, id: i32) -> anyhow::Result<Project> {
+        let project = sqlx::query_as::<_, Project>(
+            r#"
+                select * from projects where id=?
+            "#,
+        )
+        .bind(id)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => RepositoryError::NotFound(id),
+            _ => RepositoryError::Unexpected(e.to_string()),
+        })?;
+
+        Ok(project)
+    }
+
+    async fn all_projects(&self, user_id: Option<i32>) -> anyhow::Result<Vec<Project>> {
+        let projects = sqlx::query_as::<_, Project>(
+            r#"
+                select * from projects
+                where ?1 is null or user_id=?1 or id=?2
+                order by id
+            "#,
+        )
+        .bind(user_id)
+        .bind(DEFAULT_PROJECT_ID)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(projects)
+    }
+
+    async fn update_project(&self, id: i32, payload: UpdateProject) -> anyhow::Result<Project> {
+        let project = sqlx::query_as::<_, Project>(
+            r#"
+                update projects set name=coalesce(?, name)
+                where id=?
+                returning *
+            "#,
+        )
+        .bind(payload.name)
+        .bind(id)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => RepositoryError::NotFound(id),
+            _ => RepositoryError::Unexpected(e.to_string()),
+        })?;
+
+        Ok(project)
+    }
+
+    async fn delete_project(&self, id: i32) -> anyhow::Result<()> {
+        check_project_deletable(id)?;
+        let mut tx = self.pool.begin().await?;
+        let foreign = sqlx::query_scalar::<_, i64>(
+            r#"
+                select count(*) from todos
+                where project_id=?1
+                and user_id is not (select user_id from projects where id=?1)
+            "#,
+        )
+        .bind(id)
+        .fetch_one(&mut tx)
+        .await?;
+        if foreign > 0 {
+            return Err(foreign_project_todos(id).into());
+        }
+        // Its todos go with it through `on delete cascade`, unless a todo
+        // elsewhere is a subtask of one of them.
+        let result = sqlx::query(
+            r#"
+                delete from projects where id=?
+            "#,
+        )
+        .bind(id)
+        .execute(&mut tx)
+        .await
+        .map_err(|e| {
+            if is_foreign_key_violation(&e) {
+                RepositoryError::Conflict(format!(
+                    "project todos have subtasks elsewhere, id is {}",
+                    id
+                ))
+            } else {
+                RepositoryError::Unexpected(e.to_string())
+            }
+        })?;
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound(id).into());
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl UserRepository for TodoRepositoryForSqlite {
+    async fn create_user(&self, payload: CreateUser) -> anyhow::Result<User> {
+        let user = sqlx::query_as::<_, User>(
+            r#"
+                insert into users (name, password_hash, created_at)
+                values (?, ?, ?)
+                returning *
+            "#,
+        )
+        .bind(payload.name.clone())
+        .bind(payload.password_hash.clone())
+        .bind(Utc::now())
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| {
+            if is_unique_violation(&e) {
+                RepositoryError::Conflict(format!("user already exists, name is {}", payload.name))
+            } else {
+                RepositoryError::Unexpected(e.to_string())
+            }
+        })?;
+
+        Ok(user)
+    }
+
+    async fn find_user_by_name(&self, name: &str) -> anyhow::Result<Option<User>> {
+        let user = sqlx::query_as::<_, User>(
+            r#"
+                select * from users where name=?
+            "#,
+        )
+        .bind(name)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(user)
+    }
+    async fn create_api_key(&self, payload: CreateApiKey) -> anyhow::Result<ApiKey> {
+        let key = sqlx::query_as::<_, ApiKey>(
+            r#"
+                insert into api_keys (user_id, name, prefix, key_hash, created_at)
+                values (?, ?, ?, ?, ?)
+                returning *
+            "#,
+        )
+        .bind(payload.user_id)
+        .bind(payload.name)
+        .bind(payload.prefix)
+        .bind(payload.key_hash)
+        .bind(Utc::now())
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(key)
+    }
+
+    async fn find_api_key_by_hash(&self, key_hash: &str) -> anyhow::Result<Option<ApiKey>> {
+        let key = sqlx::query_as::<_, ApiKey>(
+            r#"
+                select * from api_keys where key_hash=?
+            "#,
+        )
+        .bind(key_hash)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(key)
+    }
+
+    async fn api_keys(&self, user_id: i32) -> anyhow::Result<Vec<ApiKey>> {
+        let keys = sqlx::query_as::<_, ApiKey>(
+            r#"
+                select * from api_keys where user_id=? order by id asc
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(keys)
+    }
+
+    async fn delete_api_key(&self, user_id: i32, id: i32) -> anyhow::Result<()> {
+        let result = sqlx::query(
+            r#"
+                delete from api_keys where id=? and user_id=?
+            "#,
+        )
+        .bind(id)
+        .bind(user_id)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| RepositoryError::Unexpected(e.to_string()))?;
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound(id).into());
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod test_utils {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    impl TodoRepositoryForSqlite {
+        /// A migrated, private in-memory database. A single connection is used
+        /// because every `:memory:` connection opens a database of its own.
+        pub async fn in_memory() -> Self {
+            let pool = SqlitePoolOptions::new()
+                .max_connections(1)
+                .connect("sqlite::memory:")
+                .await
+                .expect("failed connect in-memory sqlite");
+            let repository = Self::new(pool);
+            repository
+                .migrate()
+                .await
+                .expect("failed migrate in-memory sqlite");
+            repository
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+        use crate::repositories::{SortField, SortOrder, TodoLimits};
+
+        #[tokio::test]
+        async fn crud_scenario_sqlite() {
+            let repository = TodoRepositoryForSqlite::in_memory().await;
+            let todo_text = "[crud_scenario] text";
+
+            // create
+            let created = repository
+                .create(CreateTodo::new(todo_text.to_string()))
+                .await
+                .expect("[create] returned Err");
+            assert_eq!(created.text, todo_text);
+            assert!(!created.completed);
+
+            // find
+            let todo = repository
+                .find(created.id)
+                .await
+                .expect("[find] returned Err");
+            assert_eq!(created, todo);
+
+            // plan
+            let plan = repository
+                .listing_plan(
+                    &listing_query(TodoSort::default()),
+                    &TodoFilter::default(),
+                    Page::default(),
+                )
+                .await
+                .expect("[plan] returned Err");
+            assert!(plan.iter().any(|step| step.contains("todos")));
+
+            // all
+            let todos = repository
+                .all(&TodoFilter::default(), TodoSort::default(), Page::default())
+                .await
+                .expect("[all] returned Err");
+            assert_eq!(vec![created.clone()], todos);
+            let stale_filter = TodoFilter {
+                stale_before: Some(Utc::now() + Duration::seconds(1)),
+                ..TodoFilter::default()
+            };
+            let todos = repository
+                .all(&stale_filter, TodoSort::default(), Page::default())
+                .await
+                .expect("[all] returned Err");
+            assert_eq!(vec![created.clone()], todos);
+
+            // page
+            let second = repository
+                .create(CreateTodo::new("[crud_scenario] second".to_string()))
+                .await
+                .expect("[create] returned Err");
+            let page = Page {
+                limit: Some(1),
+                offset: 1,
+            };
+            let todos = repository
+                .all(&TodoFilter::default(), TodoSort::default(), page)
+                .await
+                .expect("[all] returned Err");
+            assert_eq!(vec![created.clone()], todos);
+            let sort = TodoSort {
+                field: SortField::Text,
+                order: SortOrder::Asc,
+            };
+            let todos = repository
+                .all(&TodoFilter::default(), sort, Page::default())
+                .await
+                .expect("[all] returned Err");
+            assert_eq!(vec![second.clone(), created.clone()], todos);
+            repository
+                .snooze(second.id, Some(Utc::now() + Duration::hours(1)))
+                .await
+                .expect("[snooze] returned Err");
+            let awake_filter = TodoFilter {
+                awake_at: Some(Utc::now()),
+                ..TodoFilter::default()
+            };
+            let count = repository
+                .count(&awake_filter)
+                .await
+                .expect("[count] returned Err");
+            assert_eq!(count, 1);
+            let text_filter = TodoFilter {
+                text_contains: Some("SECOND".to_string()),
+                ..TodoFilter::default()
+            };
+            let todos = repository
+                .all(&text_filter, TodoSort::default(), Page::default())
+                .await
+                .expect("[all] returned Err");
+            assert_eq!(
+                vec![second.id],
+                todos.iter().map(|todo| todo.id).collect::<Vec<_>>()
+            );
+
+            // update
+            let updated_text = "[crud_scenario] updated text";
+            let todo = repository
+                .update(
+                    created.id,
+                    UpdateTodo {
+                        text: Some(updated_text.to_string()),
+                        completed: Some(true),
+                        label_ids: None,
+                        due_date: None,
+                        priority: None,
+                        blocked_by: None,
+                        project_id: None,
+                        recurrence: None,
+                        lat: None,
+                        lon: None,
+                        place: None,
+                        remind_at: None,
+                    },
+                )
+                .await
+                .expect("[update] returned Err");
+            assert_eq!(created.id, todo.id);
+            assert_eq!(todo.text, updated_text);
+            assert!(todo.completed);
+            let completed_filter = TodoFilter {
+                completed: Some(true),
+                ..TodoFilter::default()
+            };
+            let todos = repository
+                .all(&completed_filter, TodoSort::default(), Page::default())
+                .await
+                .expect("[all] returned Err");
+            assert_eq!(vec![todo.clone()], todos);
+
+            // due date
+            let due_date = Utc::now() - Duration::days(1);
+            let second = repository
+                .update(
+                    second.id,
+                    UpdateTodo {
+                        text: None,
+                        completed: None,
+                        label_ids: None,
+                        due_date: Some(Some(due_date)),
+                        priority: None,
+                        blocked_by: None,
+                        project_id: None,
+                        recurrence: None,
+                        lat: None,
+                        lon: None,
+                        place: None,
+                        remind_at: None,
+                    },
+                )
+                .await
+                .expect("[update] returned Err");
+            assert_eq!(second.due_date, Some(due_date));
+            let overdue_filter = TodoFilter {
+                overdue_at: Some(Utc::now()),
+                ..TodoFilter::default()
+            };
+            let todos = repository
+                .all(&overdue_filter, TodoSort::default(), Page::default())
+                .await
+                .expect("[all] returned Err");
+            assert_eq!(vec![second.clone()], todos);
+
+            // subtasks
+            let subtask = repository
+                .create(
+                    CreateTodo::new("[crud_scenario] subtask".to_string()).with_parent(second.id),
+                )
+                .await
+                .expect("[create] returned Err");
+            let parent_filter = TodoFilter {
+                parent_id: Some(second.id),
+                ..TodoFilter::default()
+            };
+            let todos = repository
+                .all(&parent_filter, TodoSort::default(), Page::default())
+                .await
+                .expect("[all] returned Err");
+            assert_eq!(vec![subtask.clone()], todos);
+            let ids_filter = TodoFilter {
+                ids: Some(vec![subtask.id, subtask.id + 1000]),
+                ..TodoFilter::default()
+            };
+            let todos = repository
+                .all(&ids_filter, TodoSort::default(), Page::default())
+                .await
+                .expect("[all] returned Err");
+            assert_eq!(vec![subtask.clone()], todos);
+            let res = repository.delete(second.id).await;
+            assert!(matches!(
+                res.unwrap_err().downcast_ref::<RepositoryError>(),
+                Some(RepositoryError::Conflict(_))
+            ));
+            repository
+                .delete(subtask.id)
+                .await
+                .expect("[delete] returned Err");
+
+            // dependencies
+            let blocked = repository
+                .create(
+                    CreateTodo::new("[crud_scenario] blocked".to_string())
+                        .with_blockers(vec![second.id]),
+                )
+                .await
+                .expect("[create] returned Err");
+            assert_eq!(vec![second.id], *blocked.blocked_by);
+            let blockers = repository
+                .blockers(blocked.id)
+                .await
+                .expect("[blockers] returned Err");
+            assert_eq!(vec![second.clone()], blockers);
+            let res = repository
+                .update(
+                    second.id,
+                    UpdateTodo {
+                        text: None,
+                        completed: None,
+                        label_ids: None,
+                        due_date: None,
+                        priority: None,
+                        blocked_by: Some(vec![blocked.id]),
+                        project_id: None,
+                        recurrence: None,
+                        lat: None,
+                        lon: None,
+                        place: None,
+                        remind_at: None,
+                    },
+                )
+                .await;
+            assert!(matches!(
+                res.unwrap_err().downcast_ref::<RepositoryError>(),
+                Some(RepositoryError::Conflict(_))
+            ));
+            repository
+                .delete(blocked.id)
+                .await
+                .expect("[delete] returned Err");
+
+            // projects
+            let project = repository
+                .create_project(CreateProject::new("[crud_scenario] project".to_string()))
+                .await
+                .expect("[create_project] returned Err");
+            let scoped = repository
+                .create(
+                    CreateTodo::new("[crud_scenario] scoped".to_string()).with_project(project.id),
+                )
+                .await
+                .expect("[create] returned Err");
+            assert_eq!(project.id, scoped.project_id);
+            let project_filter = TodoFilter {
+                project_id: Some(project.id),
+                ..TodoFilter::default()
+            };
+            let todos = repository
+                .all(&project_filter, TodoSort::default(), Page::default())
+                .await
+                .expect("[all] returned Err");
+            assert_eq!(vec![scoped.clone()], todos);
+            let limited = |limits| {
+                CreateTodo::new("[crud_scenario] limited".to_string())
+                    .with_project(project.id)
+                    .with_limits(limits)
+            };
+            let res = repository
+                .create(limited(TodoLimits {
+                    max_project_todos: Some(1),
+                    ..TodoLimits::default()
+                }))
+                .await;
+            assert!(matches!(
+                res.unwrap_err().downcast_ref::<RepositoryError>(),
+                Some(RepositoryError::ProjectTodoLimitReached {
+                    limit: 1,
+                    usage: 1,
+                    ..
+                })
+            ));
+            let usage = repository
+                .count(&TodoFilter::default())
+                .await
+                .expect("[count] returned Err");
+            let res = repository
+                .create(limited(TodoLimits {
+                    max_todos: Some(usage),
+                    ..TodoLimits::default()
+                }))
+                .await;
+            assert!(matches!(
+                res.unwrap_err().downcast_ref::<RepositoryError>(),
+                Some(RepositoryError::TodoLimitReached { limit, usage: reached })
+                    if *limit == usage && *reached == usage
+            ));
+            let todos = repository
+                .all(&project_filter, TodoSort::default(), Page::default())
+                .await
+                .expect("[all] returned Err");
+            assert_eq!(vec![scoped.clone()], todos);
+            let res = repository
+                .delete_project(crate::repositories::DEFAULT_PROJECT_ID)
+                .await;
+            assert!(matches!(
+                res.unwrap_err().downcast_ref::<RepositoryError>(),
+                Some(RepositoryError::Conflict(_))
+            ));
+            repository
+                .delete_project(project.id)
+                .await
+                .expect("[delete_project] returned Err");
+            let res = repository.find(scoped.id).await;
+            assert!(matches!(
+                res.unwrap_err().downcast_ref::<RepositoryError>(),
+                Some(RepositoryError::NotFound(_))
+            ));
+
+            // manual order
+            let moved = repository
+                .reorder(second.id, ReorderTodo::to_index(0))
+                .await
+                .expect("[reorder] returned Err");
+            assert_eq!(0, moved.position);
+            let todos = repository
+                .all(
+                    &TodoFilter::default(),
+                    TodoSort {
+                        field: SortField::Position,
+                        order: SortOrder::Asc,
+                    },
+                    Page::default(),
+                )
+                .await
+                .expect("[all] returned Err");
+            assert_eq!(second.id, todos[0].id);
+            assert!(todos
+                .iter()
+                .zip(0..)
+                .all(|(todo, index)| todo.position == index));
+
+            // labels
+            let label = repository
+                .create_label(CreateLabel::new("work".to_string()))
+                .await
+                .expect("[create_label] returned Err");
+            let res = repository
+                .create_label(CreateLabel::new("work".to_string()))
+                .await;
+            assert!(matches!(
+                res.unwrap_err().downcast_ref::<RepositoryError>(),
+                Some(RepositoryError::Conflict(_))
+            ));
+            let labelled = repository
+                .create(
+                    CreateTodo::new("[crud_scenario] labelled".to_string())
+                        .with_labels(vec![label.id]),
+                )
+                .await
+                .expect("[create] returned Err");
+            assert_eq!(vec![label.clone()], *labelled.labels);
+            let label_filter = TodoFilter {
+                label: Some("work".to_string()),
+                ..TodoFilter::default()
+            };
+            let todos = repository
+                .all(&label_filter, TodoSort::default(), Page::default())
+                .await
+                .expect("[all] returned Err");
+            assert_eq!(vec![labelled.clone()], todos);
+            repository
+                .delete_label(label.id)
+                .await
+                .expect("[delete_label] returned Err");
+            let labelled = repository
+                .find(labelled.id)
+                .await
+                .expect("[find] returned Err");
+            assert!(labelled.labels.is_empty());
+            repository
+                .delete(labelled.id)
+                .await
+                .expect("[delete] returned Err");
+
+            // recurrence
+            let recurring = repository
+                .create(
+                    CreateTodo::new("[crud_scenario] recurring".to_string())
+                        .with_recurrence("FREQ=DAILY;COUNT=2"),
+                )
+                .await
+                .expect("[create] returned Err");
+            let complete = || UpdateTodo::new(None, Some(true));
+            repository
+                .update(recurring.id, complete())
+                .await
+                .expect("[update] returned Err");
+            let recurring_filter = TodoFilter {
+                text_contains: Some("recurring".to_string()),
+                ..TodoFilter::default()
+            };
+            let todos = repository
+                .all(&recurring_filter, TodoSort::default(), Page::default())
+                .await
+                .expect("[all] returned Err");
+            assert_eq!(2, todos.len());
+            let next = &todos[0];
+            assert!(!next.completed);
+            assert!(next.due_date.is_some());
+            assert_eq!(Some("FREQ=DAILY;COUNT=1"), next.recurrence.as_deref());
+            repository
+                .update(next.id, complete())
+                .await
+                .expect("[update] returned Err");
+            let count = repository
+                .count(&recurring_filter)
+                .await
+                .expect("[count] returned Err");
+            assert_eq!(2, count);
+            for todo in todos {
+                repository
+                    .delete(todo.id)
+                    .await
+                    .expect("[delete] returned Err");
+            }
+
+            // locations
+            let located = repository
+                .create(
+                    CreateTodo::new("[crud_scenario] located".to_string())
+                        .with_location(51.5007, -0.1246),
+                )
+                .await
+                .expect("[create] returned Err");
+            let nearby = Nearby {
+                lat: 51.5014,
+                lon: -0.1419,
+                radius: 2000.0,
+            };
+            let todos = repository
+                .nearby(&nearby)
+                .await
+                .expect("[nearby] returned Err");
+            assert_eq!(vec![located.clone()], todos);
+            let todos = repository
+                .nearby(&Nearby {
+                    radius: 500.0,
+                    ..nearby
+                })
+                .await
+                .expect("[nearby] returned Err");
+            assert!(todos.is_empty());
+            repository
+                .delete(located.id)
+                .await
+                .expect("[delete] returned Err");
+
+            // timer
+            repository
+                .start_timer(todo.id)
+                .await
+                .expect("[start_timer] returned Err");
+            let res = repository.start_timer(todo.id).await;
+            assert!(matches!(
+                res.unwrap_err().downcast_ref::<RepositoryError>(),
+                Some(RepositoryError::Conflict(_))
+            ));
+            let entry = repository
+                .stop_timer(todo.id)
+                .await
+                .expect("[stop_timer] returned Err");
+            assert!(entry.stopped_at.is_some());
+            let entries = repository
+                .time_entries(todo.id)
+                .await
+                .expect("[time_entries] returned Err");
+            assert_eq!(vec![entry], entries);
+
+            // users
+            let user = repository
+                .create_user(CreateUser {
+                    name: "crud_scenario".to_string(),
+                    password_hash: "hash".to_string(),
+                })
+                .await
+                .expect("[create_user] returned Err");
+            let res = repository
+                .create_user(CreateUser {
+                    name: "crud_scenario".to_string(),
+                    password_hash: "other".to_string(),
+                })
+                .await;
+            assert!(matches!(
+                res.unwrap_err().downcast_ref::<RepositoryError>(),
+                Some(RepositoryError::Conflict(_))
+            ));
+            let found = repository
+                .find_user_by_name("crud_scenario")
+                .await
+                .expect("[find_user_by_name] returned Err");
+            assert_eq!(Some(user.clone()), found);
+            let found = repository
+                .find_user_by_name("nobody")
+                .await
+                .expect("[find_user_by_name] returned Err");
+            assert!(found.is_none());
+
+            // api keys
+            let key = repository
+                .create_api_key(CreateApiKey {
+                    user_id: user.id(),
+                    name: "crud_scenario".to_string(),
+                    prefix: "tk_0123".to_string(),
+                    key_hash: "hash".to_string(),
+                })
+                .await
+                .expect("[create_api_key] returned Err");
+            let found = repository
+                .find_api_key_by_hash("hash")
+                .await
+                .expect("[find_api_key_by_hash] returned Err");
+            assert_eq!(Some(key.clone()), found);
+            let keys = repository
+                .api_keys(user.id())
+                .await
+                .expect("[api_keys] returned Err");
+            assert_eq!(vec![key.clone()], keys);
+            let res = repository.delete_api_key(user.id() + 1, key.id()).await;
+            assert!(matches!(
+                res.unwrap_err().downcast_ref::<RepositoryError>(),
+                Some(RepositoryError::NotFound(_))
+            ));
+            repository
+                .delete_api_key(user.id(), key.id())
+                .await
+                .expect("[delete_api_key] returned Err");
+            let found = repository
+                .find_api_key_by_hash("hash")
+                .await
+                .expect("[find_api_key_by_hash] returned Err");
+            assert!(found.is_none());
+
+            // pomodoro, abandoned when finished early
+            let pomodoro = repository
+                .start_pomodoro(todo.id)
+                .await
+                .expect("[start_pomodoro] returned Err");
+            let pomodoro = repository
+                .finish_pomodoro(pomodoro.id)
+                .await
+                .expect("[finish_pomodoro] returned Err");
+            assert!(!pomodoro.completed);
+            let completed = repository
+                .completed_pomodoros(None)
+                .await
+                .expect("[completed_pomodoros] returned Err");
+            assert!(!completed.contains(&pomodoro));
+
+            // delete
+            repository
+                .delete(todo.id)
+                .await
+                .expect("[delete] returned Err");
+            let res = repository.find(created.id).await;
+            assert!(res.is_err());
+            let res = repository.delete(created.id).await;
+            assert!(matches!(
+                res.unwrap_err().downcast_ref::<RepositoryError>(),
+                Some(RepositoryError::NotFound(_))
+            ));
+        }
+    }
+}
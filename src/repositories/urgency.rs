@@ -0,0 +1,89 @@
+use super::{Page, SortOrder, Todo, TodoFilter, TodoRepository, TodoSort};
+use chrono::{DateTime, Duration, Utc};
+use std::collections::{HashMap, HashSet};
+
+/// Weight of each step of `Priority` in `urgency`.
+const URGENCY_PRIORITY: f64 = 2.0;
+/// Weight of a due date that has passed; one due in `d` days weighs
+/// `URGENCY_DUE / (1 + d)`.
+const URGENCY_DUE: f64 = 6.0;
+/// Weight of age, reached at `URGENCY_MAX_AGE_DAYS` and not growing after.
+const URGENCY_AGE: f64 = 1.0;
+const URGENCY_MAX_AGE_DAYS: f64 = 30.0;
+/// Weight of each open todo waiting for this one.
+const URGENCY_WAITING: f64 = 0.5;
+/// Weight of waiting for an open todo, which sinks the todo below every
+/// todo that can be started right away.
+const URGENCY_BLOCKED: f64 = -20.0;
+
+/// How pressing `todo` is at `now`, higher first: from its priority, how
+/// close its due date is, its age, and how many open todos wait for it.
+/// Waiting for an open todo itself outweighs everything else.
+fn urgency(todo: &Todo, now: DateTime<Utc>, waiting: usize, blocked: bool) -> f64 {
+    let days = |duration: Duration| duration.num_minutes() as f64 / (24.0 * 60.0);
+    let priority = f64::from(todo.priority as i32) * URGENCY_PRIORITY;
+    let due = todo.due_date.map_or(0.0, |due_date| {
+        URGENCY_DUE / (1.0 + days(due_date - now).max(0.0))
+    });
+    let age = days(now - todo.created_at).clamp(0.0, URGENCY_MAX_AGE_DAYS) / URGENCY_MAX_AGE_DAYS
+        * URGENCY_AGE;
+    let waiting = waiting as f64 * URGENCY_WAITING;
+    let blocked = if blocked { URGENCY_BLOCKED } else { 0.0 };
+    priority + due + age + waiting + blocked
+}
+
+/// The todos matching `filter` by `urgency`, most urgent first in the
+/// default `desc` order, then `page` of them. Ties are broken by id, in the
+/// same direction.
+///
+/// Dependencies count only while open, and may be outside `filter`, so all
+/// todos of `filter`'s user are loaded once and `filter` is applied here.
+pub async fn all_by_urgency<T: TodoRepository>(
+    repository: &T,
+    filter: &TodoFilter,
+    order: SortOrder,
+    page: Page,
+) -> anyhow::Result<Vec<Todo>> {
+    let now = Utc::now();
+    let todos = repository
+        .all(
+            &TodoFilter {
+                user_id: filter.user_id,
+                ..TodoFilter::default()
+            },
+            TodoSort::default(),
+            Page::default(),
+        )
+        .await?;
+    let open_ids: HashSet<i32> = todos
+        .iter()
+        .filter(|todo| !todo.completed)
+        .map(|todo| todo.id)
+        .collect();
+    let mut waiting: HashMap<i32, usize> = HashMap::new();
+    for todo in todos.iter().filter(|todo| !todo.completed) {
+        for blocker_id in todo.blocked_by() {
+            *waiting.entry(*blocker_id).or_default() += 1;
+        }
+    }
+
+    let mut scored: Vec<(f64, Todo)> = todos
+        .into_iter()
+        .filter(|todo| filter.matches(todo))
+        .map(|todo| {
+            let waiting = waiting.get(&todo.id).copied().unwrap_or(0);
+            let blocked = todo.blocked_by().iter().any(|id| open_ids.contains(id));
+            (urgency(&todo, now, waiting, blocked), todo)
+        })
+        .collect();
+    scored.sort_by(|(a, x), (b, y)| a.total_cmp(b).then(x.id.cmp(&y.id)));
+    if order == SortOrder::Desc {
+        scored.reverse();
+    }
+    Ok(scored
+        .into_iter()
+        .skip(page.offset)
+        .take(page.limit.unwrap_or(usize::MAX))
+        .map(|(_, todo)| todo)
+        .collect())
+}
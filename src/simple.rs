@@ -0,0 +1,129 @@
+use crate::auth::Owner;
+use crate::chat::ChatWebhook;
+use crate::events::TodoEvents;
+use crate::handlers::{
+    create_and_notify, is_limit_reached, repository_error_status, validation_messages,
+};
+use crate::repositories::{CreateTodo, Page, TodoFilter, TodoLimits, TodoRepository, TodoSort};
+use axum::{
+    async_trait,
+    extract::{Extension, FromRequest, RequestParts},
+    http::StatusCode,
+};
+use chrono::Utc;
+use std::sync::Arc;
+use validator::Validate;
+
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Enables the `/simple` endpoints. Without it they answer 404.
+///
+/// Callers present `api_key` in `x-api-key`. Once `AuthConfig` is set they
+/// present an access token or API key of theirs instead, as for the REST
+/// resources.
+#[derive(Debug, Clone)]
+pub struct SimpleApiConfig {
+    api_key: String,
+}
+
+impl SimpleApiConfig {
+    pub fn new(api_key: String) -> Self {
+        Self { api_key }
+    }
+}
+
+type PlainText = (StatusCode, String);
+
+/// Adds a todo from a plain-text body, for callers such as IFTTT, Shortcuts
+/// or voice assistants that cannot build JSON.
+pub async fn simple_add<T: TodoRepository>(
+    SimpleCaller(owner): SimpleCaller,
+    Extension(repository): Extension<Arc<T>>,
+    limits: Option<Extension<TodoLimits>>,
+    chat: Option<Extension<ChatWebhook>>,
+    events: Option<Extension<TodoEvents>>,
+    body: String,
+) -> Result<PlainText, StatusCode> {
+    let payload = CreateTodo::new(body.trim().to_string());
+    if let Err(errors) = payload.validate() {
+        let message = validation_messages(&errors).join(", ");
+        return Ok((StatusCode::UNPROCESSABLE_ENTITY, message));
+    }
+    let limits = limits.map(|Extension(limits)| limits).unwrap_or_default();
+    let todo = match create_and_notify(
+        &*repository,
+        owner,
+        payload.with_limits(limits),
+        chat.as_ref().map(|Extension(chat)| chat),
+        events.as_ref().map(|Extension(events)| events),
+    )
+    .await
+    {
+        Ok(todo) => todo,
+        Err(e) if is_limit_reached(&e) => return Ok((StatusCode::FORBIDDEN, e.to_string())),
+        Err(e) => return Err(repository_error_status(e)),
+    };
+    Ok((StatusCode::CREATED, format!("Added: {}", todo.text())))
+}
+
+/// Answers with the text of the oldest open, unsnoozed todo.
+pub async fn simple_next<T: TodoRepository>(
+    SimpleCaller(owner): SimpleCaller,
+    Extension(repository): Extension<Arc<T>>,
+) -> Result<PlainText, StatusCode> {
+    let now = Utc::now();
+    let filter = TodoFilter {
+        awake_at: Some(now),
+        completed: Some(false),
+        user_id: owner.user_id(),
+        ..TodoFilter::default()
+    };
+    let next = repository
+        .all(&filter, TodoSort::default(), Page::default())
+        .await
+        .map_err(repository_error_status)?
+        .into_iter()
+        .min_by_key(|todo| todo.id());
+
+    let message = match next {
+        Some(todo) => todo.text().to_string(),
+        None => "Nothing to do".to_string(),
+    };
+    Ok((StatusCode::OK, message))
+}
+
+/// A caller let through to the `/simple` endpoints, whose todos they see.
+/// `RequireAuth` has already checked the credentials of one with an
+/// `Owner`; any other must present the configured key. Rejects with 404
+/// without `SimpleApiConfig`.
+pub struct SimpleCaller(Owner);
+
+#[async_trait]
+impl<B: Send> FromRequest<B> for SimpleCaller {
+    type Rejection = StatusCode;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let Extension(config) = Extension::<SimpleApiConfig>::from_request(req)
+            .await
+            .map_err(|_| StatusCode::NOT_FOUND)?;
+        let owner = Owner::from_request(req).await.unwrap_or_default();
+        if owner.user_id().is_some() {
+            return Ok(Self(owner));
+        }
+        let given = req
+            .headers()
+            .and_then(|headers| headers.get(API_KEY_HEADER))
+            .map(|value| value.as_bytes())
+            .unwrap_or_default();
+        if !constant_time_eq(given, config.api_key.as_bytes()) {
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+        Ok(Self(owner))
+    }
+}
+
+/// Compares without returning early, so response timing does not reveal how
+/// much of a guessed key was right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
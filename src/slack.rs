@@ -0,0 +1,220 @@
+use crate::auth::Owner;
+use crate::chat::ChatWebhook;
+use crate::events::TodoEvents;
+use crate::handlers::{
+    create_and_notify, is_limit_reached, repository_error_status, validation_messages,
+};
+use crate::repositories::{
+    CreateTodo, Page, Todo, TodoFilter, TodoLimits, TodoRepository, TodoSort,
+};
+use axum::{
+    body::Bytes,
+    extract::Extension,
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::sync::Arc;
+use validator::Validate;
+
+pub const SLACK_SIGNATURE_HEADER: &str = "x-slack-signature";
+pub const SLACK_TIMESTAMP_HEADER: &str = "x-slack-request-timestamp";
+/// Requests signed further from now than this are rejected as replays.
+const MAX_REQUEST_AGE_SECS: i64 = 60 * 5;
+/// Slack renders at most 50 blocks per message.
+const LIST_LIMIT: usize = 40;
+
+const HELP: &str = "Usage: `/todo add <text>` or `/todo list`";
+
+/// Enables the Slack slash command. Without it the route answers 404.
+#[derive(Debug, Clone)]
+pub struct SlackConfig {
+    signing_secret: String,
+}
+
+impl SlackConfig {
+    pub fn new(signing_secret: String) -> Self {
+        Self { signing_secret }
+    }
+}
+
+/// The fields of Slack's form-encoded payload we use; the rest are ignored.
+#[derive(Debug, Deserialize)]
+struct SlashCommand {
+    text: String,
+}
+
+/// Handles `/todo add <text>` and `/todo list`.
+///
+/// Slack shows any non-200 answer as a generic failure, so problems with the
+/// command itself are reported back as an ephemeral message instead.
+pub async fn slack_command<T: TodoRepository>(
+    headers: HeaderMap,
+    Extension(repository): Extension<Arc<T>>,
+    config: Option<Extension<SlackConfig>>,
+    limits: Option<Extension<TodoLimits>>,
+    chat: Option<Extension<ChatWebhook>>,
+    events: Option<Extension<TodoEvents>>,
+    body: Bytes,
+) -> Result<Json<Value>, StatusCode> {
+    let Extension(config) = config.ok_or(StatusCode::NOT_FOUND)?;
+    if !verify_signature(
+        &config.signing_secret,
+        &headers,
+        &body,
+        Utc::now().timestamp(),
+    ) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    let command: SlashCommand =
+        serde_urlencoded::from_bytes(&body).map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    let text = command.text.trim();
+    let (name, argument) = text.split_once(' ').unwrap_or((text, ""));
+    let message = match name {
+        "add" => {
+            let limits = limits.map(|Extension(limits)| limits).unwrap_or_default();
+            let chat = chat.as_ref().map(|Extension(chat)| chat);
+            let events = events.as_ref().map(|Extension(events)| events);
+            add_todo(&*repository, limits, chat, events, argument.trim()).await?
+        }
+        "list" => list_todos(&*repository).await?,
+        _ => ephemeral(HELP),
+    };
+
+    Ok(Json(message))
+}
+
+async fn add_todo<T: TodoRepository>(
+    repository: &T,
+    limits: TodoLimits,
+    chat: Option<&ChatWebhook>,
+    events: Option<&TodoEvents>,
+    text: &str,
+) -> Result<Value, StatusCode> {
+    let payload = CreateTodo::new(text.to_string());
+    if let Err(errors) = payload.validate() {
+        return Ok(ephemeral(&format!(
+            "Can not add todo: {}",
+            validation_messages(&errors).join(", ")
+        )));
+    }
+
+    let todo = match create_and_notify(
+        repository,
+        Owner::default(),
+        payload.with_limits(limits),
+        chat,
+        events,
+    )
+    .await
+    {
+        Ok(todo) => todo,
+        Err(e) if is_limit_reached(&e) => {
+            return Ok(ephemeral(&format!("Can not add todo: {}", e)))
+        }
+        Err(e) => return Err(repository_error_status(e)),
+    };
+    let text = format!("Added {}", todo_line(&todo));
+    Ok(json!({
+        "response_type": "in_channel",
+        "text": text,
+        "blocks": [section(&text)],
+    }))
+}
+
+async fn list_todos<T: TodoRepository>(repository: &T) -> Result<Value, StatusCode> {
+    let now = Utc::now();
+    let filter = TodoFilter {
+        awake_at: Some(now),
+        completed: Some(false),
+        ..TodoFilter::default()
+    };
+    let todos: Vec<Todo> = repository
+        .all(&filter, TodoSort::default(), Page::default())
+        .await
+        .map_err(repository_error_status)?
+        .into_iter()
+        .collect();
+    if todos.is_empty() {
+        return Ok(ephemeral("No open todos"));
+    }
+
+    let title = format!("*Open todos ({})*", todos.len());
+    let mut blocks = vec![section(&title)];
+    blocks.extend(
+        todos
+            .iter()
+            .take(LIST_LIMIT)
+            .map(|todo| section(&todo_line(todo))),
+    );
+    if todos.len() > LIST_LIMIT {
+        blocks.push(section(&format!("… and {} more", todos.len() - LIST_LIMIT)));
+    }
+
+    Ok(json!({
+        "response_type": "ephemeral",
+        "text": title,
+        "blocks": blocks,
+    }))
+}
+
+fn todo_line(todo: &Todo) -> String {
+    format!("`#{}` {}", todo.id(), escape(todo.text()))
+}
+
+fn section(text: &str) -> Value {
+    json!({
+        "type": "section",
+        "text": { "type": "mrkdwn", "text": text },
+    })
+}
+
+fn ephemeral(text: &str) -> Value {
+    json!({
+        "response_type": "ephemeral",
+        "text": text,
+    })
+}
+
+/// Escapes the characters Slack's mrkdwn treats as control sequences.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Checks Slack's `v0` request signature: an HMAC-SHA256 over
+/// `v0:<timestamp>:<body>` keyed with the app's signing secret.
+fn verify_signature(secret: &str, headers: &HeaderMap, body: &[u8], now: i64) -> bool {
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+    let (timestamp, signature) = match (
+        header(SLACK_TIMESTAMP_HEADER),
+        header(SLACK_SIGNATURE_HEADER),
+    ) {
+        (Some(timestamp), Some(signature)) => (timestamp, signature),
+        _ => return false,
+    };
+
+    let fresh = timestamp
+        .parse::<i64>()
+        .map(|timestamp| (now - timestamp).abs() <= MAX_REQUEST_AGE_SECS)
+        .unwrap_or(false);
+    let signature = match signature
+        .strip_prefix("v0=")
+        .and_then(|signature| hex::decode(signature).ok())
+    {
+        Some(signature) => signature,
+        None => return false,
+    };
+
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any size");
+    mac.update(format!("v0:{}:", timestamp).as_bytes());
+    mac.update(body);
+    fresh && mac.verify_slice(&signature).is_ok()
+}
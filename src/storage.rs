@@ -0,0 +1,226 @@
+use crate::create_app;
+use crate::grpc::GrpcServer;
+use crate::reminders::ReminderScheduler;
+use crate::repositories::{
+    TodoRepository, TodoRepositoryForDb, TodoRepositoryForMemory, TodoRepositoryForRedis,
+    TodoRepositoryForSled, TodoRepositoryForSqlite, UserRepository, WriteBatching,
+};
+use anyhow::Context;
+use axum::Router;
+use sqlx::{
+    postgres::{PgConnectOptions, PgPool},
+    sqlite::{SqliteConnectOptions, SqlitePool},
+    ConnectOptions,
+};
+use std::{fmt, str::FromStr, time::Duration};
+use thiserror::Error;
+
+/// Which `TodoRepository` implementation backs the app.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageBackend {
+    /// Process memory only; everything is lost on restart.
+    Memory,
+    /// Process memory with a JSON snapshot at `$DATABASE_URL`.
+    File,
+    Sqlite,
+    Postgres,
+    Redis,
+    Sled,
+}
+
+#[derive(Debug, Error)]
+#[error(
+    "unknown storage backend: [{0}], expected one of memory, file, sqlite, postgres, redis, sled"
+)]
+pub struct ParseStorageBackendError(String);
+
+impl FromStr for StorageBackend {
+    type Err = ParseStorageBackendError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "memory" => Ok(StorageBackend::Memory),
+            "file" => Ok(StorageBackend::File),
+            "sqlite" => Ok(StorageBackend::Sqlite),
+            "postgres" => Ok(StorageBackend::Postgres),
+            "redis" => Ok(StorageBackend::Redis),
+            "sled" => Ok(StorageBackend::Sled),
+            _ => Err(ParseStorageBackendError(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for StorageBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            StorageBackend::Memory => "memory",
+            StorageBackend::File => "file",
+            StorageBackend::Sqlite => "sqlite",
+            StorageBackend::Postgres => "postgres",
+            StorageBackend::Redis => "redis",
+            StorageBackend::Sled => "sled",
+        };
+        f.write_str(name)
+    }
+}
+
+impl StorageBackend {
+    /// Picks the backend from the scheme of `database_url`, for deployments
+    /// that only set `$DATABASE_URL`. Anything unrecognised is Postgres.
+    pub fn from_url(database_url: &str) -> Self {
+        match database_url.split(':').next() {
+            Some("sqlite") => StorageBackend::Sqlite,
+            Some("file") => StorageBackend::File,
+            Some("sled") => StorageBackend::Sled,
+            Some("redis" | "rediss") => StorageBackend::Redis,
+            _ => StorageBackend::Postgres,
+        }
+    }
+}
+
+/// Tuning of the backends; each takes what applies to it and ignores the
+/// rest.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StorageOptions {
+    /// `file` only.
+    pub write_batching: Option<WriteBatching>,
+    /// Prepared statements each SQL connection keeps, sqlx's default of 100
+    /// if unset. Every query is prepared once per connection and then
+    /// reused from this cache.
+    pub statement_cache_capacity: Option<usize>,
+    /// SQL statements taking longer are logged at warn level, rather than
+    /// after sqlx's default of one second.
+    pub slow_statement_threshold: Option<Duration>,
+    /// Logs the plan of every todo listing on the SQL backends at debug
+    /// level, so `RUST_LOG=debug` is needed to see them.
+    pub query_plans: bool,
+}
+
+impl StorageOptions {
+    fn tune<O: ConnectOptions>(&self, options: &mut O) {
+        if let Some(threshold) = self.slow_statement_threshold {
+            options.log_slow_statements(log::LevelFilter::Warn, threshold);
+        }
+    }
+}
+
+/// The app built by `create_app_with`, and the repository to flush once the
+/// server has shut down, if its backend holds writes back.
+#[derive(Debug)]
+pub struct Storage {
+    pub app: Router,
+    snapshot: Option<TodoRepositoryForMemory>,
+}
+
+impl Storage {
+    /// Writes what the backend has not written yet, e.g. a pending batch of
+    /// the `file` backend.
+    pub async fn flush(&self) -> anyhow::Result<()> {
+        match &self.snapshot {
+            Some(repository) => repository.flush().await,
+            None => Ok(()),
+        }
+    }
+}
+
+/// Connects to `backend` and builds the app on top of it, starting
+/// `reminders` against the same repository. Every backend but `memory` needs
+/// `database_url`; a `file:` or `sled:` prefix on it is optional. `grpc` is
+/// served on the same repository.
+pub async fn create_app_with(
+    backend: StorageBackend,
+    database_url: Option<&str>,
+    options: StorageOptions,
+    grpc: Option<GrpcServer>,
+    reminders: Option<ReminderScheduler>,
+) -> anyhow::Result<Storage> {
+    let mut snapshot = None;
+    let require_url =
+        || database_url.with_context(|| format!("{} storage needs $DATABASE_URL", backend));
+
+    let app = match backend {
+        StorageBackend::Memory => launch(TodoRepositoryForMemory::new(), grpc, reminders),
+        StorageBackend::File => {
+            let url = require_url()?;
+            let path = url.strip_prefix("file:").unwrap_or(url);
+            let mut repository = TodoRepositoryForMemory::open(path)
+                .with_context(|| format!("fail open snapshot file [{}]", path))?;
+            if let Some(batching) = options.write_batching {
+                repository = repository.with_write_batching(batching);
+            }
+            snapshot = Some(repository.clone());
+            launch(repository, grpc, reminders)
+        }
+        StorageBackend::Sqlite => {
+            let url = require_url()?;
+            let mut connect = SqliteConnectOptions::from_str(url)
+                .with_context(|| format!("invalid sqlite url [{}]", url))?
+                .create_if_missing(true);
+            if let Some(capacity) = options.statement_cache_capacity {
+                connect = connect.statement_cache_capacity(capacity);
+            }
+            options.tune(&mut connect);
+            let pool = SqlitePool::connect_with(connect)
+                .await
+                .with_context(|| format!("fail connect database, url is [{}]", url))?;
+            let mut repository = TodoRepositoryForSqlite::new(pool);
+            if options.query_plans {
+                repository = repository.with_query_plans();
+            }
+            repository
+                .migrate()
+                .await
+                .context("fail migrate sqlite database")?;
+            launch(repository, grpc, reminders)
+        }
+        StorageBackend::Postgres => {
+            let url = require_url()?;
+            let mut connect = PgConnectOptions::from_str(url)
+                .with_context(|| format!("invalid postgres url [{}]", url))?;
+            if let Some(capacity) = options.statement_cache_capacity {
+                connect = connect.statement_cache_capacity(capacity);
+            }
+            options.tune(&mut connect);
+            let pool = PgPool::connect_with(connect)
+                .await
+                .with_context(|| format!("fail connect database, url is [{}]", url))?;
+            let mut repository = TodoRepositoryForDb::new(pool);
+            if options.query_plans {
+                repository = repository.with_query_plans();
+            }
+            launch(repository, grpc, reminders)
+        }
+        StorageBackend::Redis => {
+            let url = require_url()?;
+            let client =
+                redis::Client::open(url).with_context(|| format!("invalid redis url [{}]", url))?;
+            let repository = TodoRepositoryForRedis::new(client)
+                .await
+                .with_context(|| format!("fail connect database, url is [{}]", url))?;
+            launch(repository, grpc, reminders)
+        }
+        StorageBackend::Sled => {
+            let url = require_url()?;
+            let path = url.strip_prefix("sled:").unwrap_or(url);
+            let db =
+                sled::open(path).with_context(|| format!("fail open sled database [{}]", path))?;
+            launch(TodoRepositoryForSled::new(db)?, grpc, reminders)
+        }
+    };
+
+    Ok(Storage { app, snapshot })
+}
+
+fn launch<T: TodoRepository + UserRepository>(
+    repository: T,
+    grpc: Option<GrpcServer>,
+    reminders: Option<ReminderScheduler>,
+) -> Router {
+    if let Some(grpc) = grpc {
+        grpc.spawn(repository.clone());
+    }
+    if let Some(reminders) = reminders {
+        reminders.spawn(repository.clone());
+    }
+    create_app(repository)
+}
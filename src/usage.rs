@@ -0,0 +1,274 @@
+use crate::errors::ErrorCode;
+use axum::{
+    body::HttpBody,
+    extract::{Extension, RequestParts},
+    http::{header, Method, Request, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::Utc;
+use serde::Serialize;
+use serde_json::json;
+use std::{
+    collections::{BTreeMap, HashMap},
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+use tower::{Layer, Service};
+
+/// Without `AuthConfig` every caller shares this account.
+pub const ANONYMOUS: &str = "anonymous";
+
+/// Hard limits per key and calendar month (UTC). `None` is unlimited.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct UsageQuota {
+    pub monthly_requests: Option<u64>,
+    pub monthly_mutations: Option<u64>,
+}
+
+/// What one key used in the current month.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct KeyUsage {
+    month: String,
+    requests: u64,
+    /// Requests with a method other than GET, HEAD or OPTIONS.
+    mutations: u64,
+    bytes_in: u64,
+    bytes_out: u64,
+    /// Requests per `METHOD /route`, with ids and tokens masked.
+    routes: BTreeMap<String, u64>,
+}
+
+impl KeyUsage {
+    fn exceeds(&self, quota: &UsageQuota, mutation: bool) -> bool {
+        let over =
+            |used: u64, limit: Option<u64>| limit.map(|limit| used >= limit).unwrap_or(false);
+        over(self.requests, quota.monthly_requests)
+            || (mutation && over(self.mutations, quota.monthly_mutations))
+    }
+}
+
+/// Counts requests and bytes per user and API key, in memory. Only
+/// authenticated requests count, so there is one entry per account at most.
+#[derive(Debug, Clone, Default)]
+pub struct UsageTracker {
+    quota: UsageQuota,
+    usage: Arc<Mutex<HashMap<String, KeyUsage>>>,
+}
+
+impl UsageTracker {
+    pub fn new(quota: UsageQuota) -> Self {
+        Self {
+            quota,
+            ..Self::default()
+        }
+    }
+
+    /// The usage of `key` this month, starting over when the month turns.
+    fn current<'a>(usage: &'a mut HashMap<String, KeyUsage>, key: &str) -> &'a mut KeyUsage {
+        let month = Utc::now().format("%Y-%m").to_string();
+        let entry = usage.entry(key.to_string()).or_default();
+        if entry.month != month {
+            *entry = KeyUsage {
+                month,
+                ..KeyUsage::default()
+            };
+        }
+        entry
+    }
+
+    pub fn usage(&self, key: &str) -> KeyUsage {
+        let mut usage = self.usage.lock().unwrap();
+        Self::current(&mut usage, key).clone()
+    }
+
+    fn exceeds(&self, key: &str, mutation: bool) -> bool {
+        let mut usage = self.usage.lock().unwrap();
+        Self::current(&mut usage, key).exceeds(&self.quota, mutation)
+    }
+
+    fn record(&self, key: &str, route: String, mutation: bool, bytes_in: u64, bytes_out: u64) {
+        let mut usage = self.usage.lock().unwrap();
+        let usage = Self::current(&mut usage, key);
+        usage.requests += 1;
+        if mutation {
+            usage.mutations += 1;
+        }
+        usage.bytes_in += bytes_in;
+        usage.bytes_out += bytes_out;
+        *usage.routes.entry(route).or_default() += 1;
+    }
+}
+
+/// The account a request counts against, left unset by `UsageLayer` until
+/// `RequireAuth` knows who makes it.
+#[derive(Debug, Clone, Default)]
+pub struct UsageAccount(Arc<Mutex<Option<String>>>);
+
+impl UsageAccount {
+    fn key(&self) -> Option<String> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+/// Usage of requests made with the access token of user `user_id`.
+pub fn user_usage_key(user_id: i32) -> String {
+    format!("user:{}", user_id)
+}
+
+/// Usage of requests made with API key `id`.
+pub fn api_key_usage_key(id: i32) -> String {
+    format!("key:{}", id)
+}
+
+/// Answers 429 with the quota that was spent.
+#[derive(Debug)]
+pub struct QuotaExceeded(UsageQuota);
+
+impl IntoResponse for QuotaExceeded {
+    fn into_response(self) -> Response {
+        let body = json!({
+            "code": ErrorCode::QuotaExceeded.code(),
+            "message": "Monthly quota exceeded",
+            "quota": self.0,
+        });
+        (StatusCode::TOO_MANY_REQUESTS, Json(body)).into_response()
+    }
+}
+
+/// Accounts the request to `key` and refuses it once the key's monthly
+/// quota is spent. Does nothing outside `UsageLayer`.
+pub fn charge<B>(req: &RequestParts<B>, key: String) -> Result<(), QuotaExceeded> {
+    let extensions = match req.extensions() {
+        Some(extensions) => extensions,
+        None => return Ok(()),
+    };
+    let (tracker, account) = match (
+        extensions.get::<UsageTracker>(),
+        extensions.get::<UsageAccount>(),
+    ) {
+        (Some(tracker), Some(account)) => (tracker, account),
+        _ => return Ok(()),
+    };
+    if tracker.exceeds(&key, is_mutation(req.method())) {
+        return Err(QuotaExceeded(tracker.quota));
+    }
+    *account.0.lock().unwrap() = Some(key);
+    Ok(())
+}
+
+/// `GET /todos/7` counts as `GET /todos/:id`, and share links alike.
+fn route_of<B>(req: &Request<B>) -> String {
+    let mut previous = "";
+    let segments: Vec<&str> = req
+        .uri()
+        .path()
+        .split('/')
+        .map(|segment| {
+            let masked = if !segment.is_empty() && segment.bytes().all(|b| b.is_ascii_digit()) {
+                ":id"
+            } else if previous == "shared" {
+                ":token"
+            } else {
+                segment
+            };
+            previous = segment;
+            masked
+        })
+        .collect();
+    format!("{} {}", req.method(), segments.join("/"))
+}
+
+fn is_mutation(method: &Method) -> bool {
+    !matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+}
+
+/// Measures every request and accounts it to the key `charge` settled on,
+/// if any.
+#[derive(Debug, Clone)]
+pub struct UsageLayer {
+    tracker: UsageTracker,
+}
+
+impl UsageLayer {
+    pub fn new(tracker: UsageTracker) -> Self {
+        Self { tracker }
+    }
+}
+
+impl<S> Layer<S> for UsageLayer {
+    type Service = Usage<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Usage {
+            inner,
+            tracker: self.tracker.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Usage<S> {
+    inner: S,
+    tracker: UsageTracker,
+}
+
+type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send + 'static>>;
+
+impl<S, B> Service<Request<B>> for Usage<S>
+where
+    S: Service<Request<B>, Response = Response>,
+    S::Future: Send + 'static,
+    S::Error: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = BoxFuture<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request<B>) -> Self::Future {
+        let tracker = self.tracker.clone();
+        let account = UsageAccount::default();
+        let mutation = is_mutation(req.method());
+        let route = route_of(&req);
+        let bytes_in = req
+            .headers()
+            .get(header::CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(0);
+        req.extensions_mut().insert(tracker.clone());
+        req.extensions_mut().insert(account.clone());
+        let future = self.inner.call(req);
+
+        Box::pin(async move {
+            let res = future.await?;
+            if let Some(key) = account.key() {
+                let bytes_out = res.body().size_hint().exact().unwrap_or(0);
+                tracker.record(&key, route, mutation, bytes_in, bytes_out);
+            }
+            Ok(res)
+        })
+    }
+}
+
+/// The caller's own usage this month, with the quota it counts against.
+pub async fn usage(
+    tracker: Option<Extension<UsageTracker>>,
+    account: Option<Extension<UsageAccount>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let Extension(tracker) = tracker.ok_or(StatusCode::NOT_FOUND)?;
+    let key = account
+        .and_then(|Extension(account)| account.key())
+        .ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(json!({
+        "key": key,
+        "usage": tracker.usage(&key),
+        "quota": tracker.quota,
+    })))
+}
@@ -0,0 +1,169 @@
+use crate::dead_letters::{DeadLetters, Delivery};
+use crate::events::{TodoChange, TodoEvents};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use reqwest::header::CONTENT_TYPE;
+use sha2::Sha256;
+use std::{fmt, sync::Arc, time::Duration};
+use tokio::{sync::broadcast::error::RecvError, task::JoinHandle};
+
+pub const WEBHOOK_SIGNATURE_HEADER: &str = "x-todo-signature";
+pub const WEBHOOK_TIMESTAMP_HEADER: &str = "x-todo-timestamp";
+pub const WEBHOOK_EVENT_HEADER: &str = "x-todo-event";
+
+/// Attempts per delivery before it goes to the dead letters.
+const MAX_DELIVERY_ATTEMPTS: u32 = 5;
+/// Wait before the first retry, doubled for every one after it.
+const RETRY_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Delivers todo events as signed JSON to every registered endpoint.
+///
+/// Each request carries `x-todo-timestamp` and `x-todo-signature`, the
+/// latter being `sha256=` and the hex HMAC-SHA256 of `{timestamp}.{body}`
+/// keyed with the shared secret. Receivers should check it and reject stale
+/// timestamps.
+#[derive(Clone)]
+pub struct Webhooks {
+    endpoints: Arc<Vec<String>>,
+    secret: Arc<String>,
+    client: reqwest::Client,
+    retry_backoff: Duration,
+    dead_letters: Option<DeadLetters>,
+}
+
+impl fmt::Debug for Webhooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Webhooks")
+            .field("endpoints", &self.endpoints)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Webhooks {
+    pub fn new(endpoints: Vec<String>, secret: String) -> Self {
+        Self {
+            endpoints: Arc::new(endpoints),
+            secret: Arc::new(secret),
+            client: reqwest::Client::new(),
+            retry_backoff: RETRY_BACKOFF,
+            dead_letters: None,
+        }
+    }
+
+    /// Keeps deliveries that run out of attempts in `dead_letters`.
+    pub fn with_dead_letters(self, dead_letters: DeadLetters) -> Self {
+        Self {
+            dead_letters: Some(dead_letters),
+            ..self
+        }
+    }
+
+    /// Shortens the wait between attempts, which only tests want.
+    #[cfg(test)]
+    pub fn with_retry_backoff(self, retry_backoff: Duration) -> Self {
+        Self {
+            retry_backoff,
+            ..self
+        }
+    }
+
+    pub fn signature(&self, timestamp: i64, body: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.secret.as_bytes())
+            .expect("HMAC takes keys of any size");
+        mac.update(format!("{}.", timestamp).as_bytes());
+        mac.update(body.as_bytes());
+        format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+    }
+
+    /// Delivers every change published to `events` until the process exits.
+    pub fn subscribe(self, events: &TodoEvents) -> JoinHandle<()> {
+        let mut changes = events.subscribe();
+        tokio::spawn(async move {
+            loop {
+                match changes.recv().await {
+                    Ok(change) => self.dispatch(&change),
+                    Err(RecvError::Lagged(missed)) => {
+                        tracing::warn!("webhooks fell behind, {} changes missed", missed)
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        })
+    }
+
+    /// Sends the change to every endpoint on tasks of their own, so a slow
+    /// receiver never holds up the others.
+    fn dispatch(&self, change: &TodoChange) {
+        let body = change.payload().to_string();
+        for url in self.endpoints.iter() {
+            self.deliver(url.clone(), change.name(), change.todo_id(), body.clone());
+        }
+    }
+
+    pub fn deliver(&self, url: String, event: &'static str, todo_id: i32, body: String) {
+        let webhooks = self.clone();
+        tokio::spawn(async move {
+            if let Err(e) = webhooks.post(&url, event, &body).await {
+                tracing::error!(
+                    "fail deliver {} of todo {} to {}: {}",
+                    event,
+                    todo_id,
+                    url,
+                    e
+                );
+                if let Some(dead_letters) = webhooks.dead_letters.clone() {
+                    let delivery = Delivery::Webhook {
+                        webhooks,
+                        url,
+                        event,
+                        todo_id,
+                        body,
+                    };
+                    dead_letters.push(delivery, e.to_string());
+                }
+            }
+        });
+    }
+
+    /// Retries with exponential backoff. A 4xx answer other than 408 or
+    /// 429 means the receiver will not take it, so it is not retried.
+    async fn post(&self, url: &str, event: &str, body: &str) -> reqwest::Result<()> {
+        let mut attempt = 1;
+        let mut backoff = self.retry_backoff;
+        loop {
+            let timestamp = Utc::now().timestamp();
+            let result = self
+                .client
+                .post(url)
+                .header(CONTENT_TYPE, "application/json")
+                .header(WEBHOOK_EVENT_HEADER, event)
+                .header(WEBHOOK_TIMESTAMP_HEADER, timestamp)
+                .header(WEBHOOK_SIGNATURE_HEADER, self.signature(timestamp, body))
+                .body(body.to_string())
+                .send()
+                .await
+                .and_then(reqwest::Response::error_for_status);
+            let e = match result {
+                Ok(_) => return Ok(()),
+                Err(e) => e,
+            };
+            let permanent = e
+                .status()
+                .map(|status| status.is_client_error() && ![408, 429].contains(&status.as_u16()))
+                .unwrap_or(false);
+            if permanent || attempt >= MAX_DELIVERY_ATTEMPTS {
+                return Err(e);
+            }
+            tracing::warn!(
+                "fail deliver {} to {}, attempt {}: {}",
+                event,
+                url,
+                attempt,
+                e
+            );
+            tokio::time::sleep(backoff).await;
+            attempt += 1;
+            backoff *= 2;
+        }
+    }
+}